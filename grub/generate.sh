@@ -18,6 +18,7 @@ fi
 cat > grub.cfg << EOF
 default=$GRUB_DEFAULT
 timeout=$GRUB_TIMEOUT
+insmod all_video
 
 menuentry "elsOS with serial, azerty" {
    multiboot2 /boot/elsos.bin serial
@@ -38,4 +39,21 @@ menuentry "elsOS, qwerty ansi" {
    multiboot2 /boot/elsos.bin qwerty
    boot
 }
+
+menuentry "elsOS with serial and gdb, azerty" {
+   multiboot2 /boot/elsos.bin serial gdb
+   boot
+}
+
+menuentry "elsOS running the tests, azerty" {
+   multiboot2 /boot/elsos.bin serial init=test.sh
+   module2 /boot/test.sh test.sh
+   boot
+}
+
+menuentry "elsOS with serial in VGA text mode, azerty" {
+   set gfxpayload=text
+   multiboot2 /boot/elsos.bin serial
+   boot
+}
 EOF
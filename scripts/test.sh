@@ -0,0 +1,18 @@
+# run at boot with init=test.sh, from the "elsOS running the tests" entry
+
+echo "running the boot tests"
+failed=""
+
+for command in cpuinfo cpus bootinfo cmdline jiffies entropy rand
+do
+	$command || failed="$failed $command"
+done
+
+# the shell itself
+name=elsOS
+echo "hello from ${name}, \$? is $?"
+false && failed="$failed &&"
+true || failed="$failed ||"
+if false; then failed="$failed if"; else echo "if and else work"; fi
+
+echo "failed:$failed"
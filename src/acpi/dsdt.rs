@@ -0,0 +1,74 @@
+use core::mem::size_of;
+use super::SdtHeader;
+
+const AML_NAME_OP: u8 = 0x08;
+const AML_ROOT_CHAR: u8 = b'\\';
+const AML_PACKAGE_OP: u8 = 0x12;
+const AML_BYTE_PREFIX: u8 = 0x0a;
+const AML_ZERO_OP: u8 = 0x00;
+const AML_ONE_OP: u8 = 0x01;
+
+// SLP_TYPa and SLP_TYPb values to write in PM1a_CNT and PM1b_CNT to enter S5
+#[derive(Copy, Clone)]
+pub struct SleepState
+{
+	pub slp_typa: u16,
+	pub slp_typb: u16
+}
+
+// we do not have an AML interpreter, but the \_S5 object is almost always a
+// plain package of integers that can be found by scanning the DSDT bytes:
+// NameOp [\] _S5_ PackageOp PkgLength NumElements SLP_TYPa SLP_TYPb ...
+pub fn find_s5(dsdt: &SdtHeader) -> Option<SleepState>
+{
+	let aml = unsafe
+	{
+		core::slice::from_raw_parts((dsdt as *const _ as usize + size_of::<SdtHeader>()) as *const u8,
+									(dsdt.length as usize).saturating_sub(size_of::<SdtHeader>()))
+	};
+
+	for i in 1..aml.len().saturating_sub(4)
+	{
+		if &aml[i..i + 4] != b"_S5_"
+		{
+			continue;
+		}
+		let is_name = aml[i - 1] == AML_NAME_OP || (i >= 2 && aml[i - 2] == AML_NAME_OP && aml[i - 1] == AML_ROOT_CHAR);
+		if !is_name || aml.get(i + 4) != Some(&AML_PACKAGE_OP)
+		{
+			continue;
+		}
+		return parse_package(&aml[i + 5..]);
+	}
+	None
+}
+
+fn parse_package(package: &[u8]) -> Option<SleepState>
+{
+	// the two high bits of the first PkgLength byte tell how many bytes follow
+	let pkg_length_bytes = (*package.first()? >> 6) as usize + 1;
+	// skip NumElements
+	let mut elements = package.get(pkg_length_bytes + 1..)?;
+
+	let slp_typa = parse_integer(&mut elements)?;
+	let slp_typb = parse_integer(&mut elements)?;
+	Some(SleepState
+	{
+		slp_typa,
+		slp_typb
+	})
+}
+
+fn parse_integer(aml: &mut &[u8]) -> Option<u16>
+{
+	let (value, length) = match *aml.first()?
+	{
+		AML_BYTE_PREFIX => (*aml.get(1)? as u16, 2),
+		AML_ZERO_OP => (0, 1),
+		AML_ONE_OP => (1, 1),
+		// not a constant, the caller falls back
+		_ => return None
+	};
+	*aml = aml.get(length..)?;
+	Some(value)
+}
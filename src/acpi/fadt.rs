@@ -0,0 +1,76 @@
+use super::SdtHeader;
+
+// address spaces of a generic address structure
+pub const ADDRESS_SPACE_MEMORY: u8 = 0;
+pub const ADDRESS_SPACE_IO: u8 = 1;
+
+// bit 10 of the FADT flags: the reset register is supported
+const FLAG_RESET_REG_SUP: u32 = 1 << 10;
+
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct GenericAddress
+{
+	pub address_space: u8,
+	pub bit_width: u8,
+	pub bit_offset: u8,
+	pub access_size: u8,
+	pub address: u64
+}
+
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct Fadt
+{
+	pub header: SdtHeader,
+	pub firmware_ctrl: u32,
+	pub dsdt: u32,
+	reserved0: u8,
+	pub preferred_pm_profile: u8,
+	pub sci_interrupt: u16,
+	pub smi_command: u32,
+	pub acpi_enable: u8,
+	pub acpi_disable: u8,
+	pub s4bios_request: u8,
+	pub pstate_control: u8,
+	pub pm1a_event_block: u32,
+	pub pm1b_event_block: u32,
+	pub pm1a_control_block: u32,
+	pub pm1b_control_block: u32,
+	pub pm2_control_block: u32,
+	pub pm_timer_block: u32,
+	pub gpe0_block: u32,
+	pub gpe1_block: u32,
+	pub pm1_event_length: u8,
+	pub pm1_control_length: u8,
+	pub pm2_control_length: u8,
+	pub pm_timer_length: u8,
+	pub gpe0_length: u8,
+	pub gpe1_length: u8,
+	pub gpe1_base: u8,
+	pub cstate_control: u8,
+	pub worst_c2_latency: u16,
+	pub worst_c3_latency: u16,
+	pub flush_size: u16,
+	pub flush_stride: u16,
+	pub duty_offset: u8,
+	pub duty_width: u8,
+	pub day_alarm: u8,
+	pub month_alarm: u8,
+	pub century: u8,
+	pub boot_architecture_flags: u16,
+	reserved1: u8,
+	pub flags: u32,
+	// only present since ACPI 2.0, check the header length before using them
+	pub reset_register: GenericAddress,
+	pub reset_value: u8
+}
+
+impl Fadt
+{
+	pub fn has_reset_register(&self) -> bool
+	{
+		let length = self.header.length as usize;
+		length >= core::mem::size_of::<Fadt>() && self.flags & FLAG_RESET_REG_SUP != 0
+	}
+}
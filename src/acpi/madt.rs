@@ -0,0 +1,289 @@
+use core::mem::size_of;
+use super::SdtHeader;
+use crate::sync::Once;
+
+pub const MAX_CPUS: usize = 16;
+pub const MAX_IOAPICS: usize = 4;
+pub const MAX_OVERRIDES: usize = 16;
+
+const ENTRY_LOCAL_APIC: u8 = 0;
+const ENTRY_IO_APIC: u8 = 1;
+const ENTRY_INTERRUPT_SOURCE_OVERRIDE: u8 = 2;
+const ENTRY_LOCAL_APIC_NMI: u8 = 4;
+const ENTRY_LOCAL_APIC_ADDRESS_OVERRIDE: u8 = 5;
+
+// bit 0 of the MADT flags: the system also has a legacy 8259 PIC
+const PCAT_COMPAT: u32 = 1;
+
+// bit 0 of a local APIC entry flags: the processor can be used
+const LAPIC_ENABLED: u32 = 1;
+
+#[repr(C, packed)]
+struct MadtHeader
+{
+	header: SdtHeader,
+	local_apic_address: u32,
+	flags: u32
+}
+
+#[repr(C, packed)]
+struct EntryHeader
+{
+	entry_type: u8,
+	length: u8
+}
+
+#[repr(C, packed)]
+struct LocalApicEntry
+{
+	header: EntryHeader,
+	processor_id: u8,
+	apic_id: u8,
+	flags: u32
+}
+
+#[repr(C, packed)]
+struct IoApicEntry
+{
+	header: EntryHeader,
+	id: u8,
+	reserved: u8,
+	address: u32,
+	gsi_base: u32
+}
+
+#[repr(C, packed)]
+struct InterruptSourceOverrideEntry
+{
+	header: EntryHeader,
+	bus: u8,
+	source: u8,
+	gsi: u32,
+	flags: u16
+}
+
+#[repr(C, packed)]
+struct LocalApicNmiEntry
+{
+	header: EntryHeader,
+	processor_id: u8,
+	flags: u16,
+	lint: u8
+}
+
+#[repr(C, packed)]
+struct LocalApicAddressOverrideEntry
+{
+	header: EntryHeader,
+	reserved: u16,
+	address: u64
+}
+
+#[derive(Copy, Clone)]
+pub struct Cpu
+{
+	pub processor_id: u8,
+	pub apic_id: u8
+}
+
+#[derive(Copy, Clone)]
+pub struct IoApic
+{
+	pub id: u8,
+	pub address: u32,
+	pub gsi_base: u32
+}
+
+#[derive(Copy, Clone)]
+pub struct InterruptOverride
+{
+	pub source: u8,
+	pub gsi: u32,
+	// MPS INTI flags: bits 0-1 polarity, bits 2-3 trigger mode
+	pub flags: u16
+}
+
+impl InterruptOverride
+{
+	pub fn active_low(&self) -> bool
+	{
+		self.flags & 0b11 == 0b11
+	}
+
+	pub fn level_triggered(&self) -> bool
+	{
+		(self.flags >> 2) & 0b11 == 0b11
+	}
+}
+
+pub struct Madt
+{
+	pub found: bool,
+	pub local_apic_address: u32,
+	pub has_8259: bool,
+	pub cpus: [Cpu; MAX_CPUS],
+	pub cpu_count: usize,
+	pub ioapics: [IoApic; MAX_IOAPICS],
+	pub ioapic_count: usize,
+	pub overrides: [InterruptOverride; MAX_OVERRIDES],
+	pub override_count: usize,
+	// LINT pin of the local APICs wired to the NMI, 0xff if none
+	pub nmi_lint: u8
+}
+
+impl Madt
+{
+	pub fn cpus(&self) -> &[Cpu]
+	{
+		&self.cpus[..self.cpu_count]
+	}
+
+	pub fn ioapics(&self) -> &[IoApic]
+	{
+		&self.ioapics[..self.ioapic_count]
+	}
+
+	pub fn overrides(&self) -> &[InterruptOverride]
+	{
+		&self.overrides[..self.override_count]
+	}
+
+	// ISA irqs are identity mapped to GSIs unless the firmware says otherwise
+	pub fn isa_override(&self, irq: u8) -> Option<&InterruptOverride>
+	{
+		self.overrides().iter().find(|o| o.source == irq)
+	}
+}
+
+// what the machine looks like without a MADT
+const EMPTY: Madt = Madt
+{
+	found: false,
+	local_apic_address: 0,
+	has_8259: true,
+	cpus: [Cpu {processor_id: 0, apic_id: 0}; MAX_CPUS],
+	cpu_count: 0,
+	ioapics: [IoApic {id: 0, address: 0, gsi_base: 0}; MAX_IOAPICS],
+	ioapic_count: 0,
+	overrides: [InterruptOverride {source: 0, gsi: 0, flags: 0}; MAX_OVERRIDES],
+	override_count: 0,
+	nmi_lint: 0xff
+};
+
+// parsed once by init on the boot cpu
+static MADT: Once<Madt> = Once::new();
+
+pub fn get() -> &'static Madt
+{
+	MADT.get().unwrap_or(&EMPTY)
+}
+
+pub fn init() -> bool
+{
+	let table = match super::find_table(b"APIC")
+	{
+		Some(table) => table,
+		None =>
+		{
+			crate::log_info!("acpi", "no MADT found");
+			return false;
+		}
+	};
+
+	unsafe
+	{
+		let madt_header = &*(table as *const _ as *const MadtHeader);
+		let mut madt = EMPTY;
+
+		madt.local_apic_address = madt_header.local_apic_address;
+		madt.has_8259 = madt_header.flags & PCAT_COMPAT != 0;
+
+		let start = table as *const _ as usize;
+		let end = start + table.length as usize;
+		let mut address = start + size_of::<MadtHeader>();
+		while address + size_of::<EntryHeader>() <= end
+		{
+			let entry = &*(address as *const EntryHeader);
+			if entry.length < 2
+			{
+				break;
+			}
+			parse_entry(&mut madt, address, entry.entry_type);
+			address += entry.length as usize;
+		}
+		madt.found = true;
+
+		let local_apic_address = madt.local_apic_address;
+		crate::log_info!("acpi", "MADT: {} cpu(s), {} I/O APIC(s), local APIC at {:#08x}",
+						madt.cpu_count, madt.ioapic_count, local_apic_address);
+		MADT.call_once(|| madt);
+	}
+	true
+}
+
+unsafe fn parse_entry(madt: &mut Madt, address: usize, entry_type: u8)
+{
+	match entry_type
+	{
+		ENTRY_LOCAL_APIC =>
+		{
+			let entry = &*(address as *const LocalApicEntry);
+			if entry.flags & LAPIC_ENABLED != 0 && madt.cpu_count < MAX_CPUS
+			{
+				madt.cpus[madt.cpu_count] = Cpu
+				{
+					processor_id: entry.processor_id,
+					apic_id: entry.apic_id
+				};
+				madt.cpu_count += 1;
+			}
+		},
+		ENTRY_IO_APIC =>
+		{
+			let entry = &*(address as *const IoApicEntry);
+			if madt.ioapic_count < MAX_IOAPICS
+			{
+				madt.ioapics[madt.ioapic_count] = IoApic
+				{
+					id: entry.id,
+					address: entry.address,
+					gsi_base: entry.gsi_base
+				};
+				madt.ioapic_count += 1;
+			}
+		},
+		ENTRY_INTERRUPT_SOURCE_OVERRIDE =>
+		{
+			let entry = &*(address as *const InterruptSourceOverrideEntry);
+			if madt.override_count < MAX_OVERRIDES
+			{
+				madt.overrides[madt.override_count] = InterruptOverride
+				{
+					source: entry.source,
+					gsi: entry.gsi,
+					flags: entry.flags
+				};
+				madt.override_count += 1;
+			}
+		},
+		ENTRY_LOCAL_APIC_NMI =>
+		{
+			let entry = &*(address as *const LocalApicNmiEntry);
+			// 0xff means all processors
+			if entry.processor_id == 0xff || entry.processor_id == 0
+			{
+				madt.nmi_lint = entry.lint;
+			}
+		},
+		ENTRY_LOCAL_APIC_ADDRESS_OVERRIDE =>
+		{
+			let entry = &*(address as *const LocalApicAddressOverrideEntry);
+			let address = entry.address;
+			if address <= u32::MAX as u64
+			{
+				madt.local_apic_address = address as u32;
+			}
+		},
+		_ => {}
+	}
+}
@@ -0,0 +1,424 @@
+use core::mem::size_of;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crate::arch::port::{outb, inw, outw};
+use crate::memory;
+use crate::ok_fail;
+use crate::sync::Spinlock;
+
+pub mod dsdt;
+pub mod fadt;
+pub mod madt;
+
+const RSDP_SIGNATURE: &[u8; 8] = b"RSD PTR ";
+
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct Rsdp
+{
+	pub signature: [u8; 8],
+	pub checksum: u8,
+	pub oem_id: [u8; 6],
+	pub revision: u8,
+	pub rsdt_address: u32
+}
+
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct Xsdp
+{
+	pub rsdp: Rsdp,
+	pub length: u32,
+	pub xsdt_address: u64,
+	pub extended_checksum: u8,
+	reserved: [u8; 3]
+}
+
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct SdtHeader
+{
+	pub signature: [u8; 4],
+	pub length: u32,
+	pub revision: u8,
+	pub checksum: u8,
+	pub oem_id: [u8; 6],
+	pub oem_table_id: [u8; 8],
+	pub oem_revision: u32,
+	pub creator_id: u32,
+	pub creator_revision: u32
+}
+
+impl SdtHeader
+{
+	pub fn signature(&self) -> &str
+	{
+		core::str::from_utf8(&self.signature).unwrap_or("????")
+	}
+
+	// the whole table, header included, has to sum to 0
+	pub fn is_valid(&self) -> bool
+	{
+		unsafe
+		{
+			checksum(self as *const _ as *const u8, self.length as usize)
+		}
+	}
+}
+
+// PM1 control register bits
+const SCI_EN: u16 = 1 << 0;
+const SLP_TYP_SHIFT: u16 = 10;
+const SLP_TYP: u16 = 0b111 << SLP_TYP_SHIFT;
+const SLP_EN: u16 = 1 << 13;
+
+#[derive(Copy, Clone)]
+struct Acpi
+{
+	revision: u8,
+	root: *const SdtHeader,
+	// the XSDT stores 64 bit pointers, the RSDT 32 bit ones
+	entry_size: usize,
+	fadt: *const fadt::Fadt,
+	dsdt: *const SdtHeader,
+	s5: Option<dsdt::SleepState>
+}
+
+// the tables are mapped once and never freed
+unsafe impl Send for Acpi {}
+
+static ACPI: Spinlock<Acpi> = Spinlock::new(Acpi
+{
+	revision: 0,
+	root: core::ptr::null(),
+	entry_size: 4,
+	fadt: core::ptr::null(),
+	dsdt: core::ptr::null(),
+	s5: None
+});
+
+// set by multiboot::parse from the ACPI_OLD and ACPI_NEW tags
+pub static RSDP_ADDRESS: AtomicUsize = AtomicUsize::new(0);
+
+// a copy, the lock is not held while the tables are read
+fn acpi() -> Acpi
+{
+	*ACPI.lock()
+}
+
+unsafe fn checksum(ptr: *const u8, len: usize) -> bool
+{
+	let mut sum: u8 = 0;
+
+	for i in 0..len
+	{
+		sum = sum.wrapping_add(*ptr.add(i));
+	}
+	sum == 0
+}
+
+// tables can be anywhere in physical memory, map the header to read the length
+// then map the whole table
+unsafe fn map_table(address: usize) -> Option<&'static SdtHeader>
+{
+	if !memory::identity_map(address, size_of::<SdtHeader>(), memory::flags::PTE_RW)
+	{
+		return None;
+	}
+	let header = &*(address as *const SdtHeader);
+	if !memory::identity_map(address, header.length as usize, memory::flags::PTE_RW)
+	{
+		return None;
+	}
+	Some(header)
+}
+
+pub fn init() -> bool
+{
+	unsafe
+	{
+		let ok = init_tables();
+		crate::logln!("[{}] found ACPI tables", ok_fail(ok));
+		ok
+	}
+}
+
+unsafe fn init_tables() -> bool
+{
+	let rsdp_address = RSDP_ADDRESS.load(Ordering::Relaxed);
+	if rsdp_address == 0
+	{
+		return false;
+	}
+
+	let rsdp = &*(rsdp_address as *const Rsdp);
+	if &rsdp.signature != RSDP_SIGNATURE || !checksum(rsdp as *const _ as *const u8, size_of::<Rsdp>())
+	{
+		return false;
+	}
+
+	ACPI.lock().revision = rsdp.revision;
+	let root_address = if rsdp.revision >= 2
+	{
+		let xsdp = &*(rsdp_address as *const Xsdp);
+		if checksum(xsdp as *const _ as *const u8, xsdp.length as usize) && xsdp.xsdt_address <= u32::MAX as u64
+		{
+			ACPI.lock().entry_size = 8;
+			xsdp.xsdt_address as usize
+		}
+		else
+		{
+			rsdp.rsdt_address as usize
+		}
+	}
+	else
+	{
+		rsdp.rsdt_address as usize
+	};
+
+	match map_table(root_address)
+	{
+		Some(root) if root.is_valid() =>
+		{
+			ACPI.lock().root = root;
+			crate::log_info!("acpi", "ACPI revision {}, {} at {:#08x}", rsdp.revision, root.signature(), root_address);
+			init_fadt();
+			madt::init();
+			true
+		},
+		_ => false
+	}
+}
+
+unsafe fn init_fadt()
+{
+	let table = match find_table(b"FACP")
+	{
+		Some(table) => table,
+		None =>
+		{
+			crate::log_info!("acpi", "no FADT found");
+			return;
+		}
+	};
+	let fadt = &*(table as *const _ as *const fadt::Fadt);
+	let mut s5 = None;
+
+	if let Some(dsdt) = map_table(fadt.dsdt as usize)
+	{
+		if &dsdt.signature == b"DSDT" && dsdt.is_valid()
+		{
+			s5 = dsdt::find_s5(dsdt);
+			ACPI.lock().dsdt = dsdt;
+		}
+	}
+	{
+		let mut acpi = ACPI.lock();
+		acpi.fadt = fadt;
+		acpi.s5 = s5;
+	}
+	if let Some(s5) = s5
+	{
+		crate::log_info!("acpi", "ACPI \\_S5 found, SLP_TYPa {} SLP_TYPb {}", s5.slp_typa, s5.slp_typb);
+	}
+	else
+	{
+		crate::log_info!("acpi", "ACPI \\_S5 not found, poweroff will not be available");
+	}
+}
+
+// the firmware may start in legacy mode, ask it to hand over the power
+// management registers to us
+unsafe fn enable(fadt: &fadt::Fadt)
+{
+	let pm1a_control = fadt.pm1a_control_block as u16;
+
+	if inw(pm1a_control) & SCI_EN != 0 || fadt.smi_command == 0 || fadt.acpi_enable == 0
+	{
+		return;
+	}
+	outb(fadt.smi_command as u16, fadt.acpi_enable);
+	for _ in 0..1_000_000
+	{
+		if inw(pm1a_control) & SCI_EN != 0
+		{
+			break;
+		}
+		crate::arch::port::io_wait();
+	}
+}
+
+// the other bits of the control register, SCI_EN among them, are kept
+fn sleep(control_block: u16, slp_typ: u16)
+{
+	let control = inw(control_block) & !(SLP_TYP | SLP_EN);
+
+	outw(control_block, control | ((slp_typ << SLP_TYP_SHIFT) & SLP_TYP) | SLP_EN);
+}
+
+// enter the S5 soft off state, only returns on failure
+pub fn shutdown() -> bool
+{
+	unsafe
+	{
+		let acpi = acpi();
+		let s5 = match acpi.s5
+		{
+			Some(s5) if !acpi.fadt.is_null() => s5,
+			_ => return false
+		};
+		let fadt = &*acpi.fadt;
+
+		enable(fadt);
+		crate::arch::interrupts::disable();
+		sleep(fadt.pm1a_control_block as u16, s5.slp_typa);
+		if fadt.pm1b_control_block != 0
+		{
+			sleep(fadt.pm1b_control_block as u16, s5.slp_typb);
+		}
+		// give the hardware some time to power off
+		for _ in 0..1_000_000
+		{
+			crate::arch::port::io_wait();
+		}
+		false
+	}
+}
+
+// write the reset value to the FADT reset register, only returns on failure
+pub fn reboot() -> bool
+{
+	unsafe
+	{
+		let acpi = acpi();
+		if acpi.fadt.is_null() || !(*acpi.fadt).has_reset_register()
+		{
+			return false;
+		}
+		let fadt = &*acpi.fadt;
+		let register = fadt.reset_register;
+		let address = register.address;
+
+		match register.address_space
+		{
+			fadt::ADDRESS_SPACE_IO if address <= u16::MAX as u64 =>
+			{
+				outb(address as u16, fadt.reset_value);
+			},
+			fadt::ADDRESS_SPACE_MEMORY if address <= u32::MAX as u64 =>
+			{
+				if !memory::map_mmio(address as usize, 1)
+				{
+					return false;
+				}
+				core::ptr::write_volatile(address as *mut u8, fadt.reset_value);
+			},
+			_ => return false
+		}
+		for _ in 0..1_000_000
+		{
+			crate::arch::port::io_wait();
+		}
+		false
+	}
+}
+
+fn print_table(table: &SdtHeader)
+{
+	let length = table.length;
+	let revision = table.revision;
+	let oem_id = core::str::from_utf8(&table.oem_id).unwrap_or("");
+	let oem_table_id = core::str::from_utf8(&table.oem_table_id).unwrap_or("");
+
+	crate::println!("  {} {:#010x} {:6} bytes  rev {}  {} {}{}", table.signature(), table as *const _ as usize,
+					length, revision, oem_id, oem_table_id, if table.is_valid() { "" } else { "  (bad checksum)" });
+}
+
+crate::shell_command!("acpi", &[], "list the ACPI tables", |_| print_tables());
+
+pub fn print_tables()
+{
+	unsafe
+	{
+		let acpi = acpi();
+		if acpi.root.is_null()
+		{
+			crate::println!("no ACPI tables found");
+			return;
+		}
+		crate::println!("ACPI revision {}, RSDP at {:#010x}", acpi.revision, RSDP_ADDRESS.load(Ordering::Relaxed));
+		print_table(&*acpi.root);
+		for address in entries()
+		{
+			if let Some(table) = map_table(address)
+			{
+				print_table(table);
+			}
+		}
+		if !acpi.dsdt.is_null()
+		{
+			print_table(&*acpi.dsdt);
+		}
+		crate::println!("poweroff: {}   reset register: {}",
+						if acpi.s5.is_some() { "yes" } else { "no" },
+						if !acpi.fadt.is_null() && (*acpi.fadt).has_reset_register() { "yes" } else { "no" });
+	}
+}
+
+// iterate over the physical addresses stored in the RSDT or XSDT
+fn entries() -> impl Iterator<Item = usize>
+{
+	let Acpi { root, entry_size, .. } = acpi();
+	let count = if root.is_null()
+	{
+		0
+	}
+	else
+	{
+		unsafe
+		{
+			((*root).length as usize - size_of::<SdtHeader>()) / entry_size
+		}
+	};
+
+	(0..count).filter_map(move |i|
+	{
+		unsafe
+		{
+			let entry = (root as usize + size_of::<SdtHeader>() + i * entry_size) as *const u8;
+			let address = if entry_size == 8
+			{
+				core::ptr::read_unaligned(entry as *const u64)
+			}
+			else
+			{
+				core::ptr::read_unaligned(entry as *const u32) as u64
+			};
+			if address > u32::MAX as u64
+			{
+				None
+			}
+			else
+			{
+				Some(address as usize)
+			}
+		}
+	})
+}
+
+pub fn find_table(signature: &[u8; 4]) -> Option<&'static SdtHeader>
+{
+	for address in entries()
+	{
+		unsafe
+		{
+			if let Some(table) = map_table(address)
+			{
+				if &table.signature == signature && table.is_valid()
+				{
+					return Some(table);
+				}
+			}
+		}
+	}
+	None
+}
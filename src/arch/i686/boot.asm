@@ -1,7 +1,11 @@
 global start
 
-section .bss
-align 16
+global stack_guard
+
+section .bss align=4096
+; left unmapped once paging is set up, a stack overflow will fault on it
+stack_guard:
+resb 4096
 stack_bottom:
 resb 16384
 stack_top:
@@ -0,0 +1,267 @@
+use crate::sync::Once;
+use super::instructions;
+
+const LEAF_VENDOR: u32 = 0x0000_0000;
+const LEAF_FEATURES: u32 = 0x0000_0001;
+const LEAF_EXTENDED_FEATURES: u32 = 0x0000_0007;
+const LEAF_MAX_EXTENDED: u32 = 0x8000_0000;
+const LEAF_EXTENDED_INFO: u32 = 0x8000_0001;
+const LEAF_BRAND_START: u32 = 0x8000_0002;
+const LEAF_BRAND_END: u32 = 0x8000_0004;
+
+#[derive(Copy, Clone)]
+enum Register
+{
+	Ecx1,
+	Edx1,
+	Ebx7,
+	Edx80000001
+}
+
+#[derive(Copy, Clone)]
+pub enum Feature
+{
+	Fpu,
+	Pse,
+	Tsc,
+	Msr,
+	Pae,
+	Cx8,
+	Apic,
+	Pge,
+	Cmov,
+	Mmx,
+	Fxsr,
+	Sse,
+	Sse2,
+	Htt,
+	Sse3,
+	Ssse3,
+	Sse41,
+	Sse42,
+	X2apic,
+	Xsave,
+	Avx,
+	Rdrand,
+	Rdseed,
+	Nx
+}
+
+impl Feature
+{
+	const ALL: [Feature; 24] =
+	[
+		Feature::Fpu, Feature::Pse, Feature::Tsc, Feature::Msr, Feature::Pae, Feature::Cx8,
+		Feature::Apic, Feature::Pge, Feature::Cmov, Feature::Mmx, Feature::Fxsr, Feature::Sse,
+		Feature::Sse2, Feature::Htt, Feature::Sse3, Feature::Ssse3, Feature::Sse41, Feature::Sse42,
+		Feature::X2apic, Feature::Xsave, Feature::Avx, Feature::Rdrand, Feature::Rdseed, Feature::Nx
+	];
+
+	// where the cpu reports the feature
+	fn location(&self) -> (Register, u32)
+	{
+		match self
+		{
+			Feature::Fpu => (Register::Edx1, 0),
+			Feature::Pse => (Register::Edx1, 3),
+			Feature::Tsc => (Register::Edx1, 4),
+			Feature::Msr => (Register::Edx1, 5),
+			Feature::Pae => (Register::Edx1, 6),
+			Feature::Cx8 => (Register::Edx1, 8),
+			Feature::Apic => (Register::Edx1, 9),
+			Feature::Pge => (Register::Edx1, 13),
+			Feature::Cmov => (Register::Edx1, 15),
+			Feature::Mmx => (Register::Edx1, 23),
+			Feature::Fxsr => (Register::Edx1, 24),
+			Feature::Sse => (Register::Edx1, 25),
+			Feature::Sse2 => (Register::Edx1, 26),
+			Feature::Htt => (Register::Edx1, 28),
+			Feature::Sse3 => (Register::Ecx1, 0),
+			Feature::Ssse3 => (Register::Ecx1, 9),
+			Feature::Sse41 => (Register::Ecx1, 19),
+			Feature::Sse42 => (Register::Ecx1, 20),
+			Feature::X2apic => (Register::Ecx1, 21),
+			Feature::Xsave => (Register::Ecx1, 26),
+			Feature::Avx => (Register::Ecx1, 28),
+			Feature::Rdrand => (Register::Ecx1, 30),
+			Feature::Rdseed => (Register::Ebx7, 18),
+			Feature::Nx => (Register::Edx80000001, 20)
+		}
+	}
+
+	pub fn name(&self) -> &'static str
+	{
+		match self
+		{
+			Feature::Fpu => "fpu",
+			Feature::Pse => "pse",
+			Feature::Tsc => "tsc",
+			Feature::Msr => "msr",
+			Feature::Pae => "pae",
+			Feature::Cx8 => "cx8",
+			Feature::Apic => "apic",
+			Feature::Pge => "pge",
+			Feature::Cmov => "cmov",
+			Feature::Mmx => "mmx",
+			Feature::Fxsr => "fxsr",
+			Feature::Sse => "sse",
+			Feature::Sse2 => "sse2",
+			Feature::Htt => "htt",
+			Feature::Sse3 => "sse3",
+			Feature::Ssse3 => "ssse3",
+			Feature::Sse41 => "sse4.1",
+			Feature::Sse42 => "sse4.2",
+			Feature::X2apic => "x2apic",
+			Feature::Xsave => "xsave",
+			Feature::Avx => "avx",
+			Feature::Rdrand => "rdrand",
+			Feature::Rdseed => "rdseed",
+			Feature::Nx => "nx"
+		}
+	}
+}
+
+pub struct CpuInfo
+{
+	vendor: [u8; 12],
+	brand: [u8; 48],
+	pub family: u32,
+	pub model: u32,
+	pub stepping: u32,
+	pub max_leaf: u32,
+	pub max_extended_leaf: u32,
+	ecx1: u32,
+	edx1: u32,
+	ebx7: u32,
+	edx80000001: u32
+}
+
+impl CpuInfo
+{
+	fn read() -> CpuInfo
+	{
+		let (max_leaf, ebx, ecx, edx) = instructions::cpuid(LEAF_VENDOR, 0);
+		let mut info = CpuInfo
+		{
+			vendor: [0; 12],
+			brand: [0; 48],
+			family: 0,
+			model: 0,
+			stepping: 0,
+			max_leaf,
+			max_extended_leaf: 0,
+			ecx1: 0,
+			edx1: 0,
+			ebx7: 0,
+			edx80000001: 0
+		};
+
+		// the vendor string is stored in ebx, edx, ecx
+		info.vendor[0..4].copy_from_slice(&ebx.to_le_bytes());
+		info.vendor[4..8].copy_from_slice(&edx.to_le_bytes());
+		info.vendor[8..12].copy_from_slice(&ecx.to_le_bytes());
+
+		if max_leaf >= LEAF_FEATURES
+		{
+			let (eax, _, ecx, edx) = instructions::cpuid(LEAF_FEATURES, 0);
+			let base_family = (eax >> 8) & 0xf;
+			let base_model = (eax >> 4) & 0xf;
+
+			info.stepping = eax & 0xf;
+			info.family = base_family;
+			info.model = base_model;
+			if base_family == 0xf
+			{
+				info.family += (eax >> 20) & 0xff;
+			}
+			if base_family == 0x6 || base_family == 0xf
+			{
+				info.model += ((eax >> 16) & 0xf) << 4;
+			}
+			info.ecx1 = ecx;
+			info.edx1 = edx;
+		}
+		if max_leaf >= LEAF_EXTENDED_FEATURES
+		{
+			info.ebx7 = instructions::cpuid(LEAF_EXTENDED_FEATURES, 0).1;
+		}
+
+		info.max_extended_leaf = instructions::cpuid(LEAF_MAX_EXTENDED, 0).0;
+		if info.max_extended_leaf >= LEAF_EXTENDED_INFO
+		{
+			info.edx80000001 = instructions::cpuid(LEAF_EXTENDED_INFO, 0).3;
+		}
+		if info.max_extended_leaf >= LEAF_BRAND_END
+		{
+			for (i, leaf) in (LEAF_BRAND_START..=LEAF_BRAND_END).enumerate()
+			{
+				let (eax, ebx, ecx, edx) = instructions::cpuid(leaf, 0);
+				for (j, register) in [eax, ebx, ecx, edx].iter().enumerate()
+				{
+					let offset = i * 16 + j * 4;
+					info.brand[offset..offset + 4].copy_from_slice(&register.to_le_bytes());
+				}
+			}
+		}
+		info
+	}
+
+	pub fn vendor(&self) -> &str
+	{
+		core::str::from_utf8(&self.vendor).unwrap_or("unknown")
+	}
+
+	pub fn brand(&self) -> &str
+	{
+		let len = self.brand.iter().position(|c| *c == 0).unwrap_or(self.brand.len());
+		core::str::from_utf8(&self.brand[..len]).unwrap_or("unknown").trim()
+	}
+
+	pub fn has(&self, feature: Feature) -> bool
+	{
+		let (register, bit) = feature.location();
+		let value = match register
+		{
+			Register::Ecx1 => self.ecx1,
+			Register::Edx1 => self.edx1,
+			Register::Ebx7 => self.ebx7,
+			Register::Edx80000001 => self.edx80000001
+		};
+		value & (1 << bit) != 0
+	}
+}
+
+static INFO: Once<CpuInfo> = Once::new();
+
+// all the cpus are expected to be identical, the bootstrap processor reads it
+pub fn info() -> &'static CpuInfo
+{
+	INFO.call_once(CpuInfo::read)
+}
+
+pub fn has(feature: Feature) -> bool
+{
+	info().has(feature)
+}
+
+crate::shell_command!("cpuinfo", &[], "show the cpu vendor, model and features", |_| print_info());
+
+pub fn print_info()
+{
+	let info = info();
+
+	crate::println!("vendor:   {}", info.vendor());
+	crate::println!("brand:    {}", if info.brand().is_empty() { "unknown" } else { info.brand() });
+	crate::println!("family:   {:#x}   model: {:#x}   stepping: {:#x}", info.family, info.model, info.stepping);
+	crate::println!("leaves:   {:#x}   extended: {:#x}", info.max_leaf, info.max_extended_leaf);
+	crate::print!("features:");
+	for (i, feature) in Feature::ALL.iter().filter(|feature| info.has(**feature)).enumerate()
+	{
+		if i != 0 && i % 12 == 0
+		{
+			crate::print!("\n         ");
+		}
+		crate::print!(" {}", feature.name());
+	}
+	crate::println!();
+}
@@ -0,0 +1,173 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::acpi::madt::MAX_CPUS;
+use crate::sync::IrqSpinlock;
+use super::cpuid::{self, Feature};
+use super::instructions;
+use super::smp;
+
+// CR0 bits
+const CR0_MP: u32 = 1 << 1;
+const CR0_EM: u32 = 1 << 2;
+const CR0_TS: u32 = 1 << 3;
+const CR0_NE: u32 = 1 << 5;
+
+// CR4 bits
+const CR4_OSFXSR: u32 = 1 << 9;
+const CR4_OSXMMEXCPT: u32 = 1 << 10;
+
+// all the SIMD exceptions masked, round to nearest
+const MXCSR_DEFAULT: u32 = 0x1f80;
+
+// FXSAVE needs 512 bytes aligned on 16, FNSAVE only uses the first 108
+#[derive(Copy, Clone)]
+#[repr(C, align(16))]
+pub struct FpuState
+{
+	area: [u8; 512],
+	used: bool
+}
+
+impl FpuState
+{
+	pub const fn new() -> FpuState
+	{
+		FpuState
+		{
+			area: [0; 512],
+			used: false
+		}
+	}
+}
+
+#[derive(Copy, Clone)]
+struct Cpu
+{
+	// whose registers are currently loaded in the FPU
+	owner: *mut FpuState,
+	// the context running on the cpu, will get the FPU on the next #NM
+	current: *mut FpuState,
+	kernel: FpuState
+}
+
+// the states pointed to are only used under the lock of their cpu
+unsafe impl Send for Cpu {}
+
+// each cpu only takes its own entry
+static CPUS: [IrqSpinlock<Cpu>; MAX_CPUS] = [const
+{
+	IrqSpinlock::new(Cpu
+	{
+		owner: core::ptr::null_mut(),
+		current: core::ptr::null_mut(),
+		kernel: FpuState::new()
+	})
+}; MAX_CPUS];
+
+static FXSR: AtomicBool = AtomicBool::new(false);
+
+fn has_fxsr() -> bool
+{
+	FXSR.load(Ordering::Relaxed)
+}
+
+// called on every cpu, the FPU starts unowned so the first use traps
+pub unsafe fn init() -> bool
+{
+	if !cpuid::has(Feature::Fpu)
+	{
+		return false;
+	}
+
+	let mut cr0 = instructions::read_cr0();
+	cr0 &= !CR0_EM;
+	cr0 |= CR0_MP | CR0_NE;
+	instructions::write_cr0(cr0);
+
+	let fxsr = cpuid::has(Feature::Fxsr);
+	FXSR.store(fxsr, Ordering::Relaxed);
+	if fxsr && cpuid::has(Feature::Sse)
+	{
+		instructions::write_cr4(instructions::read_cr4() | CR4_OSFXSR | CR4_OSXMMEXCPT);
+	}
+	instructions::fninit();
+
+	let mut cpu = CPUS[smp::current()].lock();
+	cpu.owner = core::ptr::null_mut();
+	cpu.current = &mut cpu.kernel;
+	instructions::write_cr0(instructions::read_cr0() | CR0_TS);
+	true
+}
+
+pub fn is_sse_enabled() -> bool
+{
+	instructions::read_cr4() & CR4_OSFXSR != 0
+}
+
+// to be called by the context switch: the registers are only saved and
+// restored if the next context actually uses the FPU
+pub unsafe fn switch_to(state: *mut FpuState)
+{
+	let mut cpu = CPUS[smp::current()].lock();
+
+	cpu.current = state;
+	if cpu.owner == state
+	{
+		instructions::clts();
+	}
+	else
+	{
+		instructions::write_cr0(instructions::read_cr0() | CR0_TS);
+	}
+}
+
+unsafe fn save(state: &mut FpuState)
+{
+	if has_fxsr()
+	{
+		instructions::fxsave(state.area.as_mut_ptr());
+	}
+	else
+	{
+		instructions::fnsave(state.area.as_mut_ptr());
+	}
+}
+
+unsafe fn restore(state: &mut FpuState)
+{
+	if !state.used
+	{
+		// first use, start from a clean FPU
+		state.used = true;
+		instructions::fninit();
+		if is_sse_enabled()
+		{
+			instructions::ldmxcsr(MXCSR_DEFAULT);
+		}
+	}
+	else if has_fxsr()
+	{
+		instructions::fxrstor(state.area.as_ptr());
+	}
+	else
+	{
+		instructions::frstor(state.area.as_ptr());
+	}
+}
+
+// #NM: the running context wants the FPU, hand it over
+pub unsafe fn device_not_available()
+{
+	let mut cpu = CPUS[smp::current()].lock();
+
+	instructions::clts();
+	if cpu.owner == cpu.current || cpu.current.is_null()
+	{
+		return;
+	}
+	if !cpu.owner.is_null()
+	{
+		save(&mut *cpu.owner);
+	}
+	restore(&mut *cpu.current);
+	cpu.owner = cpu.current;
+}
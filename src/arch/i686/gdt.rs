@@ -7,6 +7,7 @@ struct gdt_descriptor
 	base: u32
 }
 
+#[derive(Copy, Clone)]
 #[repr(C, packed)]
 struct gdt_entry
 {
@@ -18,6 +19,7 @@ struct gdt_entry
 	base2: u8
 }
 
+#[derive(Copy, Clone)]
 #[repr(C, packed)]
 struct gdt
 {
@@ -118,6 +120,162 @@ pub fn init()
 	}
 }
 
+// limit and base of the shared GDT, used by the application processors
+// trampoline before they load their own
+pub fn shared_descriptor() -> (u16, u32)
+{
+	(GDT_DESCRIPTOR.limit, GDT_DESCRIPTOR.base)
+}
+
+pub const KERNEL_CODE_SELECTOR: u16 = 0x08;
+pub const KERNEL_DATA_SELECTOR: u16 = 0x10;
+pub const TSS_SELECTOR: u16 = 0x38;
+pub const DOUBLE_FAULT_TSS_SELECTOR: u16 = 0x40;
+
+// EFLAGS with only the always set bit, interrupts disabled
+const EFLAGS_DEFAULT: u32 = 0x2;
+
+// the hardware task state segment, we only use it for esp0/ss0 and task gates
+#[derive(Copy, Clone, Default)]
+#[repr(C, packed)]
+pub struct Tss
+{
+	pub link: u32,
+	pub esp0: u32,
+	pub ss0: u32,
+	pub esp1: u32,
+	pub ss1: u32,
+	pub esp2: u32,
+	pub ss2: u32,
+	pub cr3: u32,
+	pub eip: u32,
+	pub eflags: u32,
+	pub eax: u32,
+	pub ecx: u32,
+	pub edx: u32,
+	pub ebx: u32,
+	pub esp: u32,
+	pub ebp: u32,
+	pub esi: u32,
+	pub edi: u32,
+	pub es: u32,
+	pub cs: u32,
+	pub ss: u32,
+	pub ds: u32,
+	pub fs: u32,
+	pub gs: u32,
+	pub ldtr: u32,
+	pub trap: u16,
+	pub iomap_base: u16
+}
+
+// the base entries of the shared GDT, followed by the TSS of the cpu and the
+// one of its double fault task
+#[repr(C, packed)]
+struct cpu_gdt
+{
+	base: gdt,
+	tss: gdt_entry,
+	double_fault_tss: gdt_entry
+}
+
+// each cpu has its own GDT, as a TSS descriptor can only be used by one cpu.
+// the GDT comes first, the GDTR of a cpu points to its tables
+#[repr(C)]
+pub struct CpuTables
+{
+	gdt: cpu_gdt,
+	descriptor: gdt_descriptor,
+	pub tss: Tss,
+	pub double_fault_tss: Tss
+}
+
+extern "C"
+{
+	fn _double_fault_task();
+}
+
+impl gdt_entry
+{
+	fn system(base: u32, limit: u32, access_byte: u8) -> gdt_entry
+	{
+		gdt_entry
+		{
+			limit0: limit as u16,
+			base0: base as u16,
+			base1: (base >> 16) as u8,
+			access_byte,
+			// byte granularity, the flags stay at 0
+			limit1_flags: ((limit >> 16) & 0x0f) as u8,
+			base2: (base >> 24) as u8
+		}
+	}
+}
+
+impl CpuTables
+{
+	pub fn new(stack_top: u32, double_fault_stack_top: u32) -> CpuTables
+	{
+		let tss = Tss
+		{
+			esp0: stack_top,
+			ss0: KERNEL_DATA_SELECTOR as u32,
+			// no I/O permission bitmap
+			iomap_base: size_of::<Tss>() as u16,
+			..Tss::default()
+		};
+
+		// the double fault task starts from scratch on its own stack
+		let mut double_fault_tss = tss;
+		double_fault_tss.esp0 = double_fault_stack_top;
+		double_fault_tss.esp = double_fault_stack_top;
+		double_fault_tss.eip = _double_fault_task as *const () as usize as u32;
+		double_fault_tss.eflags = EFLAGS_DEFAULT;
+		double_fault_tss.cs = KERNEL_CODE_SELECTOR as u32;
+		double_fault_tss.ss = KERNEL_DATA_SELECTOR as u32;
+		double_fault_tss.ds = KERNEL_DATA_SELECTOR as u32;
+		double_fault_tss.es = KERNEL_DATA_SELECTOR as u32;
+		double_fault_tss.fs = KERNEL_DATA_SELECTOR as u32;
+		double_fault_tss.gs = KERNEL_DATA_SELECTOR as u32;
+
+		CpuTables
+		{
+			gdt: cpu_gdt
+			{
+				base: GDT,
+				tss: gdt_entry::system(0, 0, 0),
+				double_fault_tss: gdt_entry::system(0, 0, 0)
+			},
+			descriptor: gdt_descriptor
+			{
+				limit: size_of::<cpu_gdt>() as u16 - 1,
+				base: 0
+			},
+			tss,
+			double_fault_tss
+		}
+	}
+
+	// the tables must not move once loaded, hence the 'static
+	pub unsafe fn load(&'static mut self)
+	{
+		// present, ring 0, 32 bit available TSS
+		self.gdt.tss = gdt_entry::system(&self.tss as *const _ as u32, size_of::<Tss>() as u32 - 1, 0b1000_1001);
+		self.gdt.double_fault_tss = gdt_entry::system(&self.double_fault_tss as *const _ as u32, size_of::<Tss>() as u32 - 1, 0b1000_1001);
+		self.double_fault_tss.cr3 = super::instructions::read_cr3();
+		self.descriptor.base = &self.gdt as *const _ as u32;
+		_gdt_flush(&self.descriptor);
+		super::instructions::ltr(TSS_SELECTOR);
+	}
+
+	// tables of the running cpu, only valid once they have been loaded
+	pub unsafe fn current() -> &'static mut CpuTables
+	{
+		let (_, base) = super::instructions::sgdt();
+		&mut *(base as *mut CpuTables)
+	}
+}
+
 /***************************************************************************************************
 * documentation (for more details: https://wiki.osdev.org/Global_Descriptor_Table)
 *
@@ -18,3 +18,179 @@ pub unsafe fn cli()
 {
 	asm!("cli");
 }
+
+#[inline(always)]
+pub fn read_eflags() -> u32
+{
+	let eflags: u32;
+	unsafe
+	{
+		asm!("pushfd", "pop {}", out(reg) eflags, options(nomem, preserves_flags));
+	}
+	eflags
+}
+
+#[inline(always)]
+pub unsafe fn ltr(selector: u16)
+{
+	asm!("ltr {0:x}", in(reg) selector, options(nostack, preserves_flags));
+}
+
+#[inline(always)]
+pub fn read_cr3() -> u32
+{
+	let cr3: u32;
+	unsafe
+	{
+		asm!("mov {}, cr3", out(reg) cr3, options(nomem, nostack, preserves_flags));
+	}
+	cr3
+}
+
+#[inline(always)]
+pub fn read_cr0() -> u32
+{
+	let cr0: u32;
+	unsafe
+	{
+		asm!("mov {}, cr0", out(reg) cr0, options(nomem, nostack, preserves_flags));
+	}
+	cr0
+}
+
+#[inline(always)]
+pub unsafe fn write_cr0(cr0: u32)
+{
+	asm!("mov cr0, {}", in(reg) cr0, options(nostack, preserves_flags));
+}
+
+// clear CR0.TS, the next FPU instruction will not trap
+#[inline(always)]
+pub unsafe fn clts()
+{
+	asm!("clts", options(nomem, nostack, preserves_flags));
+}
+
+#[inline(always)]
+pub fn read_cr2() -> u32
+{
+	let cr2: u32;
+	unsafe
+	{
+		asm!("mov {}, cr2", out(reg) cr2, options(nomem, nostack, preserves_flags));
+	}
+	cr2
+}
+
+#[inline(always)]
+pub unsafe fn invlpg(address: usize)
+{
+	asm!("invlpg [{}]", in(reg) address, options(nostack, preserves_flags));
+}
+
+#[inline(always)]
+pub fn sgdt() -> (u16, u32)
+{
+	let mut descriptor = [0u16; 3];
+	unsafe
+	{
+		asm!("sgdt [{}]", in(reg) descriptor.as_mut_ptr(), options(nostack, preserves_flags));
+	}
+	(descriptor[0], descriptor[1] as u32 | (descriptor[2] as u32) << 16)
+}
+
+#[inline(always)]
+pub fn read_cr4() -> u32
+{
+	let cr4: u32;
+	unsafe
+	{
+		asm!("mov {}, cr4", out(reg) cr4, options(nomem, nostack, preserves_flags));
+	}
+	cr4
+}
+
+#[inline(always)]
+pub unsafe fn write_cr4(cr4: u32)
+{
+	asm!("mov cr4, {}", in(reg) cr4, options(nostack, preserves_flags));
+}
+
+#[inline(always)]
+pub unsafe fn rdmsr(msr: u32) -> u64
+{
+	let low: u32;
+	let high: u32;
+	asm!("rdmsr", in("ecx") msr, out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags));
+	(high as u64) << 32 | low as u64
+}
+
+#[inline(always)]
+pub unsafe fn wrmsr(msr: u32, value: u64)
+{
+	asm!("wrmsr", in("ecx") msr, in("eax") value as u32, in("edx") (value >> 32) as u32, options(nostack, preserves_flags));
+}
+
+#[inline(always)]
+pub fn cpuid(leaf: u32, subleaf: u32) -> (u32, u32, u32, u32)
+{
+	let eax: u32;
+	let ebx: u32;
+	let ecx: u32;
+	let edx: u32;
+	unsafe
+	{
+		asm!("cpuid",
+				inout("eax") leaf => eax,
+				out("ebx") ebx,
+				inout("ecx") subleaf => ecx,
+				out("edx") edx,
+				options(nomem, nostack, preserves_flags));
+	}
+	(eax, ebx, ecx, edx)
+}
+
+#[inline(always)]
+pub unsafe fn fninit()
+{
+	asm!("fninit", options(nomem, nostack, preserves_flags));
+}
+
+#[inline(always)]
+pub unsafe fn fxsave(area: *mut u8)
+{
+	asm!("fxsave [{}]", in(reg) area, options(nostack, preserves_flags));
+}
+
+#[inline(always)]
+pub unsafe fn fxrstor(area: *const u8)
+{
+	asm!("fxrstor [{}]", in(reg) area, options(nostack, preserves_flags));
+}
+
+#[inline(always)]
+pub unsafe fn fnsave(area: *mut u8)
+{
+	asm!("fnsave [{}]", in(reg) area, options(nostack, preserves_flags));
+}
+
+#[inline(always)]
+pub unsafe fn frstor(area: *const u8)
+{
+	asm!("frstor [{}]", in(reg) area, options(nostack, preserves_flags));
+}
+
+#[inline(always)]
+pub unsafe fn ldmxcsr(mxcsr: u32)
+{
+	asm!("ldmxcsr [{}]", in(reg) &mxcsr, options(nostack, preserves_flags));
+}
+
+#[inline(always)]
+pub fn int3()
+{
+	unsafe
+	{
+		asm!("int3", options(nomem, nostack));
+	}
+}
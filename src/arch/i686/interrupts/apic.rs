@@ -0,0 +1,164 @@
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use crate::acpi::madt;
+use crate::arch::i686::{cpuid, instructions};
+use crate::memory;
+use super::pit;
+
+const IA32_APIC_BASE_MSR: u32 = 0x1b;
+const IA32_APIC_BASE_ENABLE: u64 = 1 << 11;
+
+
+// registers, as offsets from the local APIC base address
+const REG_ID: usize = 0x020;
+const REG_VERSION: usize = 0x030;
+const REG_TPR: usize = 0x080;
+const REG_EOI: usize = 0x0b0;
+const REG_SVR: usize = 0x0f0;
+const REG_ESR: usize = 0x280;
+const REG_ICR_LOW: usize = 0x300;
+const REG_ICR_HIGH: usize = 0x310;
+const REG_LVT_TIMER: usize = 0x320;
+const REG_LVT_LINT0: usize = 0x350;
+const REG_LVT_LINT1: usize = 0x360;
+const REG_LVT_ERROR: usize = 0x370;
+const REG_TIMER_INITIAL: usize = 0x380;
+const REG_TIMER_CURRENT: usize = 0x390;
+const REG_TIMER_DIVIDE: usize = 0x3e0;
+
+const SVR_ENABLE: u32 = 1 << 8;
+const ICR_DELIVERY_PENDING: u32 = 1 << 12;
+const LVT_MASKED: u32 = 1 << 16;
+const LVT_TIMER_PERIODIC: u32 = 1 << 17;
+const LVT_DELIVERY_NMI: u32 = 0b100 << 8;
+const TIMER_DIVIDE_BY_16: u32 = 0b0011;
+
+pub const SPURIOUS_VECTOR: u8 = 0xff;
+pub const ERROR_VECTOR: u8 = 0xfe;
+// the timer replaces the PIT on irq 0
+pub const TIMER_VECTOR: u8 = 0x20;
+
+const CALIBRATION_MS: u32 = 10;
+
+// "noapic" keeps the 8259 PIC and the PIT
+crate::boot_param!(NOAPIC: bool = false, "noapic");
+
+// set up by the boot cpu, read by all of them on every interrupt
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static BASE: AtomicUsize = AtomicUsize::new(0);
+// timer ticks per millisecond, with a divider of 16
+static TICKS_PER_MS: AtomicU32 = AtomicU32::new(0);
+
+unsafe fn read(register: usize) -> u32
+{
+	core::ptr::read_volatile((BASE.load(Ordering::Relaxed) + register) as *const u32)
+}
+
+unsafe fn write(register: usize, value: u32)
+{
+	core::ptr::write_volatile((BASE.load(Ordering::Relaxed) + register) as *mut u32, value);
+}
+
+pub fn is_supported() -> bool
+{
+	cpuid::has(cpuid::Feature::Apic)
+}
+
+pub fn is_enabled() -> bool
+{
+	ENABLED.load(Ordering::Acquire)
+}
+
+pub fn id() -> u8
+{
+	unsafe
+	{
+		(read(REG_ID) >> 24) as u8
+	}
+}
+
+pub unsafe fn init() -> bool
+{
+	let madt = madt::get();
+	if NOAPIC.get() || !is_supported() || !madt.found || madt.ioapic_count == 0
+	{
+		return false;
+	}
+
+	let base = madt.local_apic_address as usize;
+	if !memory::map_mmio(base, 0x1000)
+	{
+		return false;
+	}
+	BASE.store(base, Ordering::Relaxed);
+
+	let msr = instructions::rdmsr(IA32_APIC_BASE_MSR);
+	instructions::wrmsr(IA32_APIC_BASE_MSR, msr | IA32_APIC_BASE_ENABLE);
+
+	init_local();
+	calibrate_timer();
+	ENABLED.store(true, Ordering::Release);
+
+	crate::log_info!("apic", "local APIC {} version {:#x}, timer at {} ticks/ms", id(), read(REG_VERSION) & 0xff,
+					TICKS_PER_MS.load(Ordering::Relaxed));
+	true
+}
+
+// per-cpu configuration of the local APIC
+pub unsafe fn init_local()
+{
+	write(REG_TPR, 0);
+	write(REG_LVT_TIMER, LVT_MASKED);
+	// external interrupts come through the I/O APIC, not the virtual wire
+	write(REG_LVT_LINT0, LVT_MASKED);
+	write(REG_LVT_LINT1, if madt::get().nmi_lint == 1 { LVT_DELIVERY_NMI } else { LVT_MASKED });
+	write(REG_LVT_ERROR, ERROR_VECTOR as u32);
+	// the error status register has to be written before being read
+	write(REG_ESR, 0);
+	write(REG_ESR, 0);
+	write(REG_SVR, SVR_ENABLE | SPURIOUS_VECTOR as u32);
+	send_eoi();
+}
+
+// count the timer ticks during a known amount of time measured with the PIT
+unsafe fn calibrate_timer()
+{
+	write(REG_TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+	write(REG_TIMER_INITIAL, u32::MAX);
+	pit::wait_ms(CALIBRATION_MS);
+	write(REG_LVT_TIMER, LVT_MASKED);
+	let elapsed = u32::MAX - read(REG_TIMER_CURRENT);
+	write(REG_TIMER_INITIAL, 0);
+	TICKS_PER_MS.store(elapsed / CALIBRATION_MS, Ordering::Relaxed);
+}
+
+pub unsafe fn start_timer(hz: u32)
+{
+	write(REG_TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+	write(REG_LVT_TIMER, LVT_TIMER_PERIODIC | TIMER_VECTOR as u32);
+	write(REG_TIMER_INITIAL, (TICKS_PER_MS.load(Ordering::Relaxed) * 1000 / hz).max(1));
+}
+
+pub unsafe fn send_eoi()
+{
+	write(REG_EOI, 0);
+}
+
+pub unsafe fn error_status() -> u32
+{
+	write(REG_ESR, 0);
+	read(REG_ESR)
+}
+
+pub unsafe fn send_ipi(apic_id: u8, command: u32)
+{
+	write(REG_ICR_HIGH, (apic_id as u32) << 24);
+	write(REG_ICR_LOW, command);
+}
+
+pub unsafe fn wait_ipi_delivery()
+{
+	while read(REG_ICR_LOW) & ICR_DELIVERY_PENDING != 0
+	{
+		core::hint::spin_loop();
+	}
+}
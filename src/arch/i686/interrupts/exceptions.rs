@@ -1,3 +1,6 @@
+use crate::arch::i686::gdt::CpuTables;
+use crate::arch::i686::instructions;
+use crate::memory;
 use super::State;
 
 pub enum ExceptionClass
@@ -64,3 +67,37 @@ pub fn handler(state: &State)
 		panic!("{:02x} - {}", interrupt, exception.message);
 	}
 }
+
+// reached through the double fault task gate, on the emergency stack of the
+// cpu. the cpu saved the interrupted state in its main TSS
+#[no_mangle]
+pub unsafe extern "C" fn double_fault_handler(error: u32) -> !
+{
+	let tss = CpuTables::current().tss;
+	// the lock is released before the panic reads the state
+	*crate::INTERRUPT_STATE.lock() = State
+	{
+		ds: tss.ds,
+		edi: tss.edi,
+		esi: tss.esi,
+		ebp: tss.ebp,
+		esp: tss.esp,
+		ebx: tss.ebx,
+		edx: tss.edx,
+		ecx: tss.ecx,
+		eax: tss.eax,
+		interrupt: 0x08,
+		error,
+		eip: tss.eip,
+		cs: tss.cs,
+		eflags: tss.eflags
+	};
+
+	// the next push would land in an unmapped page: a guard page was hit
+	let esp = tss.esp;
+	if !memory::is_mapped(esp.wrapping_sub(4) as usize)
+	{
+		panic!("08 - {} - kernel stack overflow (esp {:08x}, cr2 {:08x})", EXCEPTIONS[0x08].message, esp, instructions::read_cr2());
+	}
+	panic!("08 - {} - error {:08x}", EXCEPTIONS[0x08].message, error);
+}
@@ -105,6 +105,16 @@ pub unsafe fn init()
 	}
 }
 
+// the handler runs as a separate task, with the state of the interrupted
+// code saved in the current TSS
+pub unsafe fn set_task_gate(vector: u8, tss_selector: u16)
+{
+	let gate = &mut IDT[vector as usize];
+	gate.segment = tss_selector;
+	gate.set_isr(0);
+	gate.set_type(GateType::Task);
+}
+
 pub unsafe fn load()
 {
 	i686::instructions::lidt(&DESCRIPTOR);
@@ -0,0 +1,123 @@
+use crate::acpi::madt::{self, IoApic};
+use crate::memory;
+use super::irq::{self, ISA_IRQS};
+
+const IOREGSEL: usize = 0x00;
+const IOWIN: usize = 0x10;
+
+const REG_VERSION: u32 = 0x01;
+const REG_REDIRECTION_TABLE: u32 = 0x10;
+
+const REDIRECTION_ACTIVE_LOW: u32 = 1 << 13;
+const REDIRECTION_LEVEL_TRIGGERED: u32 = 1 << 15;
+const REDIRECTION_MASKED: u32 = 1 << 16;
+
+// ISA irqs are routed to the same vectors as the remapped 8259
+const IRQ_BASE_VECTOR: u8 = 0x20;
+// IRQ0 is the PIT, replaced by the local APIC timer
+const IRQ_PIT: u8 = 0;
+// IRQ2 is the cascade of the slave 8259, it never fires
+const IRQ_CASCADE: u8 = 2;
+
+unsafe fn read(ioapic: &IoApic, register: u32) -> u32
+{
+	let base = ioapic.address as usize;
+	core::ptr::write_volatile((base + IOREGSEL) as *mut u32, register);
+	core::ptr::read_volatile((base + IOWIN) as *const u32)
+}
+
+unsafe fn write(ioapic: &IoApic, register: u32, value: u32)
+{
+	let base = ioapic.address as usize;
+	core::ptr::write_volatile((base + IOREGSEL) as *mut u32, register);
+	core::ptr::write_volatile((base + IOWIN) as *mut u32, value);
+}
+
+fn max_redirection_entry(ioapic: &IoApic) -> u32
+{
+	unsafe
+	{
+		(read(ioapic, REG_VERSION) >> 16) & 0xff
+	}
+}
+
+fn ioapic_for(gsi: u32) -> Option<&'static IoApic>
+{
+	madt::get().ioapics().iter().find(|ioapic|
+	{
+		gsi >= ioapic.gsi_base && gsi <= ioapic.gsi_base + max_redirection_entry(ioapic)
+	})
+}
+
+unsafe fn set_redirection(ioapic: &IoApic, gsi: u32, low: u32, destination: u8)
+{
+	let register = REG_REDIRECTION_TABLE + (gsi - ioapic.gsi_base) * 2;
+	write(ioapic, register, REDIRECTION_MASKED);
+	write(ioapic, register + 1, (destination as u32) << 24);
+	write(ioapic, register, low);
+}
+
+// translate an ISA irq to its GSI and flags, then route it to its vector
+pub fn route_irq(irq: u8, destination: u8, masked: bool) -> bool
+{
+	let mut gsi = irq as u32;
+	let mut flags = 0;
+
+	unsafe
+	{
+		if let Some(isa_override) = madt::get().isa_override(irq)
+		{
+			gsi = isa_override.gsi;
+			if isa_override.active_low()
+			{
+				flags |= REDIRECTION_ACTIVE_LOW;
+			}
+			if isa_override.level_triggered()
+			{
+				flags |= REDIRECTION_LEVEL_TRIGGERED;
+			}
+		}
+		if masked
+		{
+			flags |= REDIRECTION_MASKED;
+		}
+		match ioapic_for(gsi)
+		{
+			Some(ioapic) =>
+			{
+				set_redirection(ioapic, gsi, flags | (IRQ_BASE_VECTOR + irq) as u32, destination);
+				true
+			},
+			None => false
+		}
+	}
+}
+
+pub unsafe fn init(destination: u8) -> bool
+{
+	for ioapic in madt::get().ioapics()
+	{
+		if !memory::map_mmio(ioapic.address as usize, 0x1000)
+		{
+			return false;
+		}
+		// mask everything, the handled ISA irqs are unmasked below
+		for entry in 0..=max_redirection_entry(ioapic)
+		{
+			set_redirection(ioapic, ioapic.gsi_base + entry, REDIRECTION_MASKED, 0);
+		}
+		let id = ioapic.id;
+		let address = ioapic.address;
+		let gsi_base = ioapic.gsi_base;
+		crate::log_info!("ioapic", "I/O APIC {} at {:#08x}, gsi {}-{}", id, address, gsi_base,
+						gsi_base + max_redirection_entry(ioapic));
+	}
+	for irq in 0..ISA_IRQS
+	{
+		if irq != IRQ_CASCADE
+		{
+			route_irq(irq, destination, irq == IRQ_PIT || !irq::is_handled(irq));
+		}
+	}
+	true
+}
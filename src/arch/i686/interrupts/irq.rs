@@ -1,5 +1,5 @@
+use core::sync::atomic::Ordering;
 use super::State;
-use super::pic;
 
 pub struct IRQ
 {
@@ -7,12 +7,15 @@ pub struct IRQ
 	pub handler: unsafe fn(&State)
 }
 
-pub static IRQS: [IRQ; 15] =
+pub const ISA_IRQS: u8 = 16;
+
+pub static IRQS: [IRQ; ISA_IRQS as usize] =
 [
 	IRQ {message: "Programmable Interrupt Timer Interrupt", handler: pit_interrupt},
 	IRQ {message: "Keyboard Interrupt", handler: keyboard_interrupt},
 	IRQ {message: "", handler: unhandled_interrupt},
-	IRQ {message: "", handler: unhandled_interrupt},
+	IRQ {message: "COM2 Interrupt", handler: com2_interrupt},
+	IRQ {message: "COM1 Interrupt", handler: com1_interrupt},
 	IRQ {message: "", handler: unhandled_interrupt},
 	IRQ {message: "", handler: unhandled_interrupt},
 	IRQ {message: "", handler: unhandled_interrupt},
@@ -26,16 +29,32 @@ pub static IRQS: [IRQ; 15] =
 	IRQ {message: "", handler: unhandled_interrupt}
 ];
 
+// the irqs without a driver keep an empty message
+pub fn is_handled(irq: u8) -> bool
+{
+	IRQS.get(irq as usize).is_some_and(|irq| !irq.message.is_empty())
+}
+
 pub unsafe fn handler(state: &State)
 {
 	let irq = state.interrupt - 0x20;
-	(IRQS[irq as usize].handler)(state);
-	pic::send_eoi(irq as u8);
+	crate::random::add_interrupt_randomness(irq as u8);
+	match IRQS.get(irq as usize)
+	{
+		Some(entry) => (entry.handler)(state),
+		None => unhandled_interrupt(state)
+	}
+	super::end_of_interrupt(irq as u8);
 }
 
 unsafe fn pit_interrupt(_state: &State)
 {
-	crate::time::JIFFIES += 1;
+	// every cpu has its own local APIC timer, only count the jiffies once
+	if crate::arch::smp::is_bsp()
+	{
+		crate::time::JIFFIES.fetch_add(1, Ordering::Relaxed);
+	}
+	crate::arch::smp::tick();
 }
 
 unsafe fn keyboard_interrupt(_state: &State)
@@ -43,6 +62,18 @@ unsafe fn keyboard_interrupt(_state: &State)
 	crate::keyboard::get_scancode();
 }
 
+unsafe fn com1_interrupt(_state: &State)
+{
+	crate::serial::interrupt(4);
+	crate::tty::serial::input();
+}
+
+unsafe fn com2_interrupt(_state: &State)
+{
+	crate::debug::gdb::serial_interrupt();
+	crate::serial::interrupt(3);
+}
+
 unsafe fn unhandled_interrupt(state: &State)
 {
 	let interrupt = state.interrupt;
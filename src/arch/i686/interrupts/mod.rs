@@ -1,9 +1,13 @@
 use crate::arch::i686::instructions;
+use crate::ok_fail;
 
+pub mod apic;
 mod exceptions;
 pub mod idt;
+pub mod ioapic;
 mod irq;
 mod pic;
+pub mod pit;
 mod software;
 
 #[inline(always)]
@@ -12,6 +16,31 @@ pub unsafe fn init()
 	idt::init();
 	idt::load();
 	pic::init();
+	if apic::init()
+	{
+		pic::disable();
+		let ioapic_ok = ioapic::init(apic::id());
+		crate::logln!("[{}] initialized I/O APIC", ok_fail(ioapic_ok));
+		apic::start_timer(crate::time::HZ);
+		crate::logln!("[{}] switched to local APIC timer at {}Hz", ok_fail(true), crate::time::HZ);
+	}
+	else
+	{
+		pit::set_frequency(crate::time::HZ);
+		crate::log_info!("interrupts", "using the 8259 PIC and the PIT at {}Hz", crate::time::HZ);
+	}
+}
+
+unsafe fn end_of_interrupt(irq: u8)
+{
+	if apic::is_enabled()
+	{
+		apic::send_eoi();
+	}
+	else
+	{
+		pic::send_eoi(irq);
+	}
 }
 
 #[inline(always)]
@@ -25,6 +54,15 @@ pub unsafe fn disable()
 	instructions::cli();
 }
 
+// EFLAGS.IF
+const INTERRUPT_FLAG: u32 = 1 << 9;
+
+#[inline(always)]
+pub fn are_enabled() -> bool
+{
+	instructions::read_eflags() & INTERRUPT_FLAG != 0
+}
+
 #[derive(Copy, Clone, Debug)]
 #[repr(C, packed)]
 pub struct State
@@ -53,37 +91,33 @@ impl State
 {
 	fn save(self)
 	{
-		unsafe
-		{
-			let state = &mut crate::INTERRUPT_STATE;
-			state.eax = self.eax;
-			state.ebx = self.ebx;
-			state.ecx = self.ecx;
-			state.edx = self.edx;
-
-			state.esi = self.esi;
-			state.edi = self.edi;
-			state.esp = self.esp;
-			state.ebp = self.ebp;
-
-			state.cs = self.cs;
-			state.ds = self.ds;
-
-			state.interrupt = self.interrupt;
-			state.error = self.error;
-
-			state.eip = self.eip;
-			state.eflags = self.eflags;
-		}
+		*crate::INTERRUPT_STATE.lock() = self;
 	}
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn interrupt_handler(state: &State) -> usize
+pub unsafe extern "C" fn interrupt_handler(state: &mut State) -> usize
 {
 	let interrupt = state.interrupt;
 	match interrupt
 	{
+		// #DB and #BP belong to gdb once it is attached
+		0x01 | 0x03 if crate::debug::gdb::is_enabled() =>
+		{
+			crate::debug::gdb::handle_exception(state);
+			0
+		},
+		0x01 | 0x03 if crate::debug::kdb::is_enabled() =>
+		{
+			crate::debug::kdb::handle_exception(state);
+			0
+		},
+		// #NM, lazy FPU switching
+		0x07 =>
+		{
+			super::fpu::device_not_available();
+			0
+		},
 		0x00..=0x1f =>
 		{
 			exceptions::handler(state);
@@ -98,6 +132,14 @@ pub unsafe extern "C" fn interrupt_handler(state: &State) -> usize
 		{
 			software::handler(state)
 		}
+		0xfe =>
+		{
+			crate::serial_println!("local APIC error {:#x}", apic::error_status());
+			apic::send_eoi();
+			0
+		}
+		// spurious interrupts must not be acknowledged
+		0xff => 0,
 		_ =>
 		{
 			crate::serial_println!("Got unhandled interrupt {:02x}", interrupt);
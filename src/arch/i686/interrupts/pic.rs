@@ -106,3 +106,10 @@ pub unsafe fn send_eoi(irq: u8)
 	}
 	PIC1.send_eoi();
 }
+
+// mask every irq, used when the I/O APIC takes over
+pub unsafe fn disable()
+{
+	outb(PIC1.data, 0xff);
+	outb(PIC2.data, 0xff);
+}
@@ -0,0 +1,51 @@
+use crate::arch::port::{inb, outb};
+
+const PIT_FREQUENCY: u32 = 1193182;
+
+const CHANNEL0_DATA: u16 = 0x40;
+const CHANNEL2_DATA: u16 = 0x42;
+const COMMAND: u16 = 0x43;
+// bit 0 is the gate of channel 2, bit 1 enables the PC speaker, bit 5 is the
+// output of channel 2
+const CHANNEL2_GATE: u16 = 0x61;
+
+const CHANNEL0: u8 = 0b0000_0000;
+const CHANNEL2: u8 = 0b1000_0000;
+const ACCESS_LOHI: u8 = 0b0011_0000;
+const MODE_ONESHOT: u8 = 0b0000_0010;
+const MODE_SQUARE_WAVE: u8 = 0b0000_0110;
+
+fn divisor(hz: u32) -> u16
+{
+	(PIT_FREQUENCY / hz).clamp(1, 0xffff) as u16
+}
+
+// periodic IRQ0 at the given frequency
+pub fn set_frequency(hz: u32)
+{
+	let divisor = divisor(hz);
+
+	outb(COMMAND, CHANNEL0 | ACCESS_LOHI | MODE_SQUARE_WAVE);
+	outb(CHANNEL0_DATA, divisor as u8);
+	outb(CHANNEL0_DATA, (divisor >> 8) as u8);
+}
+
+// busy wait using channel 2, which does not raise any interrupt, used to
+// calibrate the other timers
+pub fn wait_ms(ms: u32)
+{
+	let divisor = divisor(1000 / ms.clamp(1, 50));
+
+	// enable the gate, disable the speaker
+	outb(CHANNEL2_GATE, (inb(CHANNEL2_GATE) & 0xfd) | 0x01);
+	outb(COMMAND, CHANNEL2 | ACCESS_LOHI | MODE_ONESHOT);
+	outb(CHANNEL2_DATA, divisor as u8);
+	outb(CHANNEL2_DATA, (divisor >> 8) as u8);
+
+	// pulse the gate to start counting
+	let gate = inb(CHANNEL2_GATE) & 0xfe;
+	outb(CHANNEL2_GATE, gate);
+	outb(CHANNEL2_GATE, gate | 0x01);
+
+	while inb(CHANNEL2_GATE) & 0x20 == 0 {}
+}
@@ -1,7 +1,11 @@
 use super::State;
 
 use crate::arch;
+use crate::memory;
+use crate::sync::Mutex;
+use crate::tty::ldisc::Termios;
 
+use core::mem::{align_of, size_of};
 use core::slice;
 
 #[derive(Copy, Clone)]
@@ -11,12 +15,23 @@ pub struct Syscall
 	pub handler: unsafe fn(u32, u32, u32) -> usize
 }
 
-pub static SYSCALLS: [Syscall; 2] =
+pub static SYSCALLS: [Syscall; 4] =
 [
 	Syscall {name: "read", handler: sys_read},
-	Syscall {name: "write", handler: sys_write}
+	Syscall {name: "write", handler: sys_write},
+	Syscall {name: "getrandom", handler: sys_getrandom},
+	Syscall {name: "ioctl", handler: sys_ioctl}
 ];
 
+// returned negated
+const EBADF: isize = 9;
+const EINTR: isize = 4;
+const ENOTTY: isize = 25;
+const EFAULT: isize = 14;
+
+// stdin, stdout and stderr are the tty
+const TTY_FILE_DESCRIPTORS: u32 = 3;
+
 
 pub unsafe fn handler(state: &State) -> usize
 {
@@ -42,22 +57,32 @@ unsafe fn sys_dummy(_arg1: u32, _arg2: u32, _arg3: u32) -> usize
 	0
 }
 
-unsafe fn sys_read(_file_descriptor: u32, buffer: u32, len: u32) -> usize
+// only one reader consumes the tty input at a time
+static READER: Mutex<()> = Mutex::new(());
+
+unsafe fn sys_read(file_descriptor: u32, buffer: u32, len: u32) -> usize
 {
+	if file_descriptor >= TTY_FILE_DESCRIPTORS
+	{
+		return -EBADF as usize;
+	}
 	let len = len as usize;
 	let buffer = slice::from_raw_parts_mut(buffer as *mut u8, len);
-	let buf = &mut crate::keyboard::BUFFER.assume_init_mut();
-	while buf.len() < len
+	let _reader = READER.lock();
+
+	loop
 	{
+		match crate::tty::read(buffer)
+		{
+			Some(Ok(len)) => return len,
+			Some(Err(_)) => return -EINTR as usize,
+			None => {}
+		}
+		// the syscall gate disabled the interrupts, let the keyboard fill the line
 		super::enable();
 		arch::halt();
 		super::disable();
 	}
-	for i in 0..len
-	{
-		buffer[i] = buf.remove(0) as u8;
-	}
-	return len;
 }
 
 unsafe fn sys_write(_file_descriptor: u32, buffer: u32, len: u32) -> usize
@@ -70,3 +95,34 @@ unsafe fn sys_write(_file_descriptor: u32, buffer: u32, len: u32) -> usize
 	}
 	0
 }
+
+// only the termios requests of the tty
+unsafe fn sys_ioctl(file_descriptor: u32, request: u32, arg: u32) -> usize
+{
+	if file_descriptor >= TTY_FILE_DESCRIPTORS
+	{
+		return -EBADF as usize;
+	}
+	let termios = arg as usize;
+	if termios == 0 || !termios.is_multiple_of(align_of::<Termios>())
+		|| !memory::is_range_mapped(termios as *const u8, size_of::<Termios>())
+	{
+		return -EFAULT as usize;
+	}
+	if crate::tty::ioctl(request, &mut *(termios as *mut Termios))
+	{
+		0
+	}
+	else
+	{
+		-ENOTTY as usize
+	}
+}
+
+unsafe fn sys_getrandom(buffer: u32, len: u32, _flags: u32) -> usize
+{
+	let len = len as usize;
+	let buffer = slice::from_raw_parts_mut(buffer as *mut u8, len);
+	crate::random::get_random_bytes(buffer);
+	len
+}
@@ -318,6 +318,17 @@ isr_common:
 
 	iret
 
+; entry point of the double fault task, reached through a task gate on a
+; fresh stack. the cpu pushed the error code, it becomes the first argument
+global _double_fault_task
+_double_fault_task:
+	extern double_fault_handler
+	call double_fault_handler
+.hang:
+	cli
+	hlt
+	jmp .hang
+
 ; define an isr_table to get easily pointers from rust code
 global _isr_table
 _isr_table:
@@ -1,14 +1,15 @@
 use core::arch::asm;
 
+pub mod cpuid;
+pub mod fpu;
 pub mod gdt;
 pub mod instructions;
 pub mod interrupts;
 pub mod port;
 pub mod rand;
+pub mod smp;
 pub mod syscall;
 
-pub use rand::rand;
-
 pub fn init()
 {
 	gdt::init();
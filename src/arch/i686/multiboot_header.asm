@@ -8,6 +8,15 @@ header_start:
 
     ; insert optional multiboot tags here
 
+    ; framebuffer, optional: the VGA text mode is kept without it
+    dw 5    ; type
+    dw 1    ; flags
+    dd 20   ; size
+    dd 1024 ; width
+    dd 768  ; height
+    dd 32   ; depth
+    dd 0    ; padding, the tags are 8 bytes aligned
+
     ; required end tag
     dw 0    ; type
     dw 0    ; flags
@@ -1,13 +1,61 @@
 use core::arch::asm;
+use super::cpuid::{self, Feature};
 
-pub fn rand() -> u32
+// RDRAND and RDSEED can transiently fail when the hardware is out of entropy
+const RETRIES: usize = 10;
+
+pub fn rdtsc() -> u64
 {
+	let low: u32;
+	let high: u32;
+
 	unsafe
 	{
-		let random_value: u32;
+		asm!("rdtsc", out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags));
+	}
+	(high as u64) << 32 | low as u64
+}
 
-		asm!("rdtsc");
-		asm!("mov {}, eax", out(reg) random_value);
-		random_value
+pub fn rdrand() -> Option<u32>
+{
+	if !cpuid::has(Feature::Rdrand)
+	{
+		return None;
+	}
+	for _ in 0..RETRIES
+	{
+		let value: u32;
+		let ok: u8;
+		unsafe
+		{
+			asm!("rdrand {0:e}", "setc {1}", out(reg) value, out(reg_byte) ok, options(nomem, nostack));
+		}
+		if ok != 0
+		{
+			return Some(value);
+		}
+	}
+	None
+}
+
+pub fn rdseed() -> Option<u32>
+{
+	if !cpuid::has(Feature::Rdseed)
+	{
+		return None;
+	}
+	for _ in 0..RETRIES
+	{
+		let value: u32;
+		let ok: u8;
+		unsafe
+		{
+			asm!("rdseed {0:e}", "setc {1}", out(reg) value, out(reg_byte) ok, options(nomem, nostack));
+		}
+		if ok != 0
+		{
+			return Some(value);
+		}
 	}
+	None
 }
@@ -0,0 +1,285 @@
+use alloc::boxed::Box;
+use core::ffi::c_void;
+use core::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use crate::acpi::madt::{self, MAX_CPUS};
+use crate::libc;
+use crate::memory;
+use crate::ok_fail;
+use crate::sync::IrqSpinlock;
+use super::gdt::{self, CpuTables};
+use super::instructions;
+use super::interrupts::{self, apic, pit};
+
+const TRAMPOLINE_BASE: usize = 0x8000;
+const AP_STACK_SIZE: usize = 16384;
+const DOUBLE_FAULT_STACK_SIZE: usize = 16384;
+
+// ICR delivery modes and flags
+const ICR_INIT: u32 = 0b101 << 8;
+const ICR_STARTUP: u32 = 0b110 << 8;
+const ICR_LEVEL_ASSERT: u32 = 1 << 14;
+
+const DOUBLE_FAULT_VECTOR: u8 = 0x08;
+
+extern "C"
+{
+	static _ap_trampoline_start: c_void;
+	static _ap_trampoline_end: c_void;
+	static _ap_gdt_descriptor: c_void;
+	static _ap_cr3: c_void;
+	static _ap_cr4: c_void;
+	static _ap_stack: c_void;
+	static _ap_cpu: c_void;
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Status
+{
+	Offline,
+	Starting,
+	Online,
+	Failed
+}
+
+impl Status
+{
+	fn name(&self) -> &'static str
+	{
+		match self
+		{
+			Status::Offline => "offline",
+			Status::Starting => "starting",
+			Status::Online => "online",
+			Status::Failed => "failed"
+		}
+	}
+}
+
+#[derive(Copy, Clone)]
+pub struct Cpu
+{
+	pub status: Status,
+	pub stack_top: usize,
+	tables: *mut CpuTables
+}
+
+// only accessed through the CPUS lock
+unsafe impl Send for Cpu {}
+
+struct Cpus
+{
+	cpus: [Cpu; MAX_CPUS]
+}
+
+// "nosmp" leaves the application processors halted
+crate::boot_param!(NOSMP: bool = false, "nosmp");
+
+static CPUS: IrqSpinlock<Cpus> = IrqSpinlock::new(Cpus
+{
+	cpus: [Cpu
+	{
+		status: Status::Offline,
+		stack_top: 0,
+		tables: core::ptr::null_mut()
+	}; MAX_CPUS]
+});
+
+// read by the timer interrupt on every cpu, without the lock
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+static BSP_APIC_ID: AtomicU8 = AtomicU8::new(0);
+static APIC_IDS: [AtomicU8; MAX_CPUS] = [const { AtomicU8::new(0) }; MAX_CPUS];
+static TICKS: [AtomicU64; MAX_CPUS] = [const { AtomicU64::new(0) }; MAX_CPUS];
+
+// index of the running cpu in the CPUS table
+pub fn current() -> usize
+{
+	if !apic::is_enabled()
+	{
+		return 0;
+	}
+	let apic_id = apic::id();
+	APIC_IDS[..COUNT.load(Ordering::Acquire)].iter().position(|id| id.load(Ordering::Relaxed) == apic_id).unwrap_or(0)
+}
+
+pub fn is_bsp() -> bool
+{
+	!apic::is_enabled() || apic::id() == BSP_APIC_ID.load(Ordering::Relaxed)
+}
+
+pub fn count() -> usize
+{
+	COUNT.load(Ordering::Acquire).max(1)
+}
+
+pub fn tick()
+{
+	TICKS[current()].fetch_add(1, Ordering::Relaxed);
+}
+
+fn set_status(index: usize, status: Status)
+{
+	CPUS.lock().cpus[index].status = status;
+}
+
+fn status(index: usize) -> Status
+{
+	CPUS.lock().cpus[index].status
+}
+
+unsafe fn trampoline_variable(symbol: &c_void) -> *mut u32
+{
+	let offset = symbol as *const _ as usize - &_ap_trampoline_start as *const _ as usize;
+	(TRAMPOLINE_BASE + offset) as *mut u32
+}
+
+unsafe fn copy_trampoline()
+{
+	let start = &_ap_trampoline_start as *const _ as usize;
+	let end = &_ap_trampoline_end as *const _ as usize;
+
+	libc::memcpy(TRAMPOLINE_BASE as *mut c_void, start as *const c_void, end - start);
+	// the shared GDT at 0x800 is reachable from real mode
+	let (limit, base) = gdt::shared_descriptor();
+	let descriptor = trampoline_variable(&_ap_gdt_descriptor) as *mut u16;
+	core::ptr::write_unaligned(descriptor, limit);
+	core::ptr::write_unaligned(descriptor.add(1) as *mut u32, base);
+	*trampoline_variable(&_ap_cr3) = instructions::read_cr3();
+	*trampoline_variable(&_ap_cr4) = instructions::read_cr4();
+}
+
+// the bootstrap processor also gets its own GDT and TSS
+unsafe fn init_bsp() -> bool
+{
+	let stack_top = crate::get_reg!("esp") as usize;
+	let double_fault_stack_top = match memory::allocate_stack(DOUBLE_FAULT_STACK_SIZE)
+	{
+		Some(stack_top) => stack_top,
+		None => return false
+	};
+	let tables = Box::leak(Box::new(CpuTables::new(stack_top as u32, double_fault_stack_top as u32)));
+
+	{
+		let mut cpus = CPUS.lock();
+		let bsp_apic_id = if apic::is_enabled() { apic::id() } else { 0 };
+
+		BSP_APIC_ID.store(bsp_apic_id, Ordering::Relaxed);
+		APIC_IDS[0].store(bsp_apic_id, Ordering::Relaxed);
+		COUNT.store(1, Ordering::Release);
+		cpus.cpus[0].status = Status::Online;
+		cpus.cpus[0].stack_top = stack_top;
+		cpus.cpus[0].tables = tables;
+	}
+	tables.load();
+	// the IDT is shared, every cpu has its own double fault TSS behind the selector
+	interrupts::idt::set_task_gate(DOUBLE_FAULT_VECTOR, gdt::DOUBLE_FAULT_TSS_SELECTOR);
+	true
+}
+
+unsafe fn start_ap(index: usize, apic_id: u8) -> bool
+{
+	let (stack_top, double_fault_stack_top) = match (memory::allocate_stack(AP_STACK_SIZE), memory::allocate_stack(DOUBLE_FAULT_STACK_SIZE))
+	{
+		(Some(stack_top), Some(double_fault_stack_top)) => (stack_top, double_fault_stack_top),
+		_ => return false
+	};
+	let tables = Box::leak(Box::new(CpuTables::new(stack_top as u32, double_fault_stack_top as u32)));
+
+	{
+		let mut cpus = CPUS.lock();
+		cpus.cpus[index] = Cpu
+		{
+			status: Status::Starting,
+			stack_top,
+			tables
+		};
+		APIC_IDS[index].store(apic_id, Ordering::Relaxed);
+		COUNT.store(index + 1, Ordering::Release);
+	}
+	*trampoline_variable(&_ap_stack) = stack_top as u32;
+	*trampoline_variable(&_ap_cpu) = index as u32;
+
+	// INIT, then two STARTUP IPIs as recommended by the Intel MP specification
+	apic::send_ipi(apic_id, ICR_INIT | ICR_LEVEL_ASSERT);
+	apic::wait_ipi_delivery();
+	pit::wait_ms(10);
+	for _ in 0..2
+	{
+		apic::send_ipi(apic_id, ICR_STARTUP | ICR_LEVEL_ASSERT | (TRAMPOLINE_BASE >> 12) as u32);
+		apic::wait_ipi_delivery();
+		pit::wait_ms(1);
+		if status(index) == Status::Online
+		{
+			return true;
+		}
+	}
+	for _ in 0..100
+	{
+		if status(index) == Status::Online
+		{
+			return true;
+		}
+		pit::wait_ms(1);
+	}
+	set_status(index, Status::Failed);
+	false
+}
+
+pub unsafe fn init()
+{
+	crate::logln!("[{}] loaded the cpu tables and the double fault task", ok_fail(init_bsp()));
+	if !apic::is_enabled() || NOSMP.get()
+	{
+		return;
+	}
+
+	copy_trampoline();
+	let bsp_apic_id = apic::id();
+	let mut index = 1;
+	for cpu in madt::get().cpus()
+	{
+		if cpu.apic_id == bsp_apic_id || index >= MAX_CPUS
+		{
+			continue;
+		}
+		let apic_id = cpu.apic_id;
+		crate::logln!("[{}] started cpu {} (APIC {})", ok_fail(start_ap(index, apic_id)), index, apic_id);
+		index += 1;
+	}
+	let online = CPUS.lock().cpus.iter().filter(|cpu| cpu.status == Status::Online).count();
+	crate::log_info!("smp", "{} cpu(s) online", online);
+}
+
+// entry point of the application processors, called by the trampoline
+#[no_mangle]
+pub unsafe extern "C" fn ap_main(index: usize) -> !
+{
+	let tables = CPUS.lock().cpus[index].tables;
+	(*tables).load();
+	interrupts::idt::load();
+	super::fpu::init();
+	apic::init_local();
+	apic::start_timer(crate::time::HZ);
+	set_status(index, Status::Online);
+	interrupts::enable();
+	loop
+	{
+		super::halt();
+	}
+}
+
+crate::shell_command!("cpus", &[], "show the status of each cpu", |_| print_cpus());
+
+// the table is copied, printing with the lock held would deadlock
+pub fn print_cpus()
+{
+	let cpus = CPUS.lock().cpus;
+	let bsp_apic_id = BSP_APIC_ID.load(Ordering::Relaxed);
+
+	crate::println!("cpu  apic  status    stack       ticks");
+	for (index, cpu) in cpus[..count()].iter().enumerate()
+	{
+		let apic_id = APIC_IDS[index].load(Ordering::Relaxed);
+		crate::println!("{:3}  {:4}  {:8}  {:#010x}  {}{}", index, apic_id, cpu.status.name(), cpu.stack_top,
+						TICKS[index].load(Ordering::Relaxed), if apic_id == bsp_apic_id { "  (bsp)" } else { "" });
+	}
+}
@@ -0,0 +1,74 @@
+; real mode entry point of the application processors, copied at
+; TRAMPOLINE_BASE by the bootstrap processor before sending the SIPIs
+TRAMPOLINE_BASE equ 0x8000
+
+%define RELOCATED(label) (TRAMPOLINE_BASE + (label - _ap_trampoline_start))
+
+section .text
+global _ap_trampoline_start
+global _ap_trampoline_end
+global _ap_gdt_descriptor
+global _ap_cr3
+global _ap_cr4
+global _ap_stack
+global _ap_cpu
+
+bits 16
+_ap_trampoline_start:
+	cli
+	cld
+	xor ax, ax
+	mov ds, ax
+
+	lgdt [RELOCATED(_ap_gdt_descriptor)]
+	mov eax, cr0
+	or eax, 1
+	mov cr0, eax
+
+	jmp dword 0x08:RELOCATED(ap_protected_mode)
+
+bits 32
+ap_protected_mode:
+	mov ax, 0x10
+	mov ds, ax
+	mov es, ax
+	mov fs, ax
+	mov gs, ax
+	mov ss, ax
+
+	; same paging extensions and page directory as the bootstrap processor
+	mov eax, [RELOCATED(_ap_cr4)]
+	mov cr4, eax
+	mov eax, [RELOCATED(_ap_cr3)]
+	mov cr3, eax
+	mov eax, cr0
+	or eax, 0x80000000
+	mov cr0, eax
+
+	mov esp, [RELOCATED(_ap_stack)]
+	xor ebp, ebp
+
+	push dword [RELOCATED(_ap_cpu)]
+	extern ap_main
+	mov eax, ap_main
+	call eax
+
+.hang:
+	cli
+	hlt
+	jmp .hang
+
+; filled by the bootstrap processor for each application processor
+align 8
+_ap_gdt_descriptor:
+	dw 0
+	dd 0
+_ap_cr3:
+	dd 0
+_ap_cr4:
+	dd 0
+_ap_stack:
+	dd 0
+_ap_cpu:
+	dd 0
+_ap_trampoline_end:
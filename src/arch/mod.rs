@@ -6,9 +6,13 @@ mod i686;
 #[cfg(target_arch = "x86")]
 pub use i686::
 {
+	cpuid,
+	fpu,
 	halt,
+	instructions,
 	interrupts,
 	port,
 	rand,
+	smp,
 	syscall
 };
@@ -0,0 +1,317 @@
+use core::cell::UnsafeCell;
+use core::ffi::c_void;
+use core::fmt;
+use core::slice;
+use crate::sync::Once;
+
+// the multiboot information may be given away later, the command line is
+// copied here and the string parameters point into this copy
+const CMDLINE_SIZE: usize = 256;
+
+struct Cmdline
+{
+	bytes: [u8; CMDLINE_SIZE],
+	len: usize
+}
+
+static CMDLINE: Once<Cmdline> = Once::new();
+
+extern "C"
+{
+	static _boot_params_start: c_void;
+	static _boot_params_end: c_void;
+}
+
+// declares a typed boot parameter, given on the command line as "name=value",
+// or as "name" alone for the flags:
+//     boot_param!(pub LOGLEVEL: Level = Level::Info, "loglevel");
+// the parameters are gathered in the .boot_params section by the linker
+#[macro_export]
+macro_rules! boot_param
+{
+	($vis:vis $param:ident: $type:ty = $default:expr, $name:literal) =>
+	{
+		$vis static $param: $crate::cmdline::BootParam<$type> = $crate::cmdline::BootParam::new($name, $default);
+		const _: () =
+		{
+			#[used]
+			#[link_section = ".boot_params"]
+			static PARAM: &'static dyn $crate::cmdline::Param = &$param;
+		};
+	};
+}
+
+// a type a boot parameter can have, the value is None for a name given alone
+pub trait FromArg: Copy + Send + Sync + 'static
+{
+	fn from_arg(value: Option<&'static str>) -> Option<Self>;
+	fn fmt_arg(&self, f: &mut fmt::Formatter) -> fmt::Result;
+}
+
+pub trait Param: Sync
+{
+	fn name(&self) -> &'static str;
+	fn set(&self, value: Option<&'static str>) -> bool;
+	fn is_set(&self) -> bool;
+	fn fmt_value(&self, f: &mut fmt::Formatter) -> fmt::Result;
+}
+
+pub struct BootParam<T>
+{
+	name: &'static str,
+	default: T,
+	value: UnsafeCell<Option<T>>
+}
+
+// only written while parsing the command line, on the boot cpu
+unsafe impl<T: Sync> Sync for BootParam<T> {}
+
+impl<T: FromArg> BootParam<T>
+{
+	pub const fn new(name: &'static str, default: T) -> BootParam<T>
+	{
+		BootParam
+		{
+			name,
+			default,
+			value: UnsafeCell::new(None)
+		}
+	}
+
+	pub fn get(&self) -> T
+	{
+		unsafe
+		{
+			(*self.value.get()).unwrap_or(self.default)
+		}
+	}
+}
+
+impl<T: FromArg> Param for BootParam<T>
+{
+	fn name(&self) -> &'static str
+	{
+		self.name
+	}
+
+	// the last occurence of a parameter wins
+	fn set(&self, value: Option<&'static str>) -> bool
+	{
+		match T::from_arg(value)
+		{
+			Some(value) =>
+			{
+				unsafe
+				{
+					*self.value.get() = Some(value);
+				}
+				true
+			}
+			None => false
+		}
+	}
+
+	fn is_set(&self) -> bool
+	{
+		unsafe
+		{
+			(*self.value.get()).is_some()
+		}
+	}
+
+	fn fmt_value(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		self.get().fmt_arg(f)
+	}
+}
+
+struct Value<'a>(&'a dyn Param);
+
+impl fmt::Display for Value<'_>
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		self.0.fmt_value(f)
+	}
+}
+
+impl FromArg for bool
+{
+	fn from_arg(value: Option<&'static str>) -> Option<bool>
+	{
+		match value
+		{
+			None | Some("1") | Some("y") | Some("yes") | Some("on") | Some("true") => Some(true),
+			Some("0") | Some("n") | Some("no") | Some("off") | Some("false") => Some(false),
+			_ => None
+		}
+	}
+
+	fn fmt_arg(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		write!(f, "{}", if *self { "on" } else { "off" })
+	}
+}
+
+// decimal or 0x prefixed hexadecimal, with an optional K, M or G suffix
+impl FromArg for usize
+{
+	fn from_arg(value: Option<&'static str>) -> Option<usize>
+	{
+		let value = value?;
+		let (digits, shift) = match value.as_bytes().last()?
+		{
+			b'k' | b'K' => (&value[..value.len() - 1], 10),
+			b'm' | b'M' => (&value[..value.len() - 1], 20),
+			b'g' | b'G' => (&value[..value.len() - 1], 30),
+			_ => (value, 0)
+		};
+		let number = match digits.strip_prefix("0x")
+		{
+			Some(hex) => usize::from_str_radix(hex, 16).ok()?,
+			None => digits.parse::<usize>().ok()?
+		};
+		number.checked_mul(1 << shift)
+	}
+
+	fn fmt_arg(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		write!(f, "{}", self)
+	}
+}
+
+impl FromArg for &'static str
+{
+	fn from_arg(value: Option<&'static str>) -> Option<&'static str>
+	{
+		value
+	}
+
+	fn fmt_arg(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		write!(f, "\"{}\"", self)
+	}
+}
+
+// a parameter without a default value
+impl<T: FromArg> FromArg for Option<T>
+{
+	fn from_arg(value: Option<&'static str>) -> Option<Option<T>>
+	{
+		T::from_arg(value).map(Some)
+	}
+
+	fn fmt_arg(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match self
+		{
+			Some(value) => value.fmt_arg(f),
+			None => write!(f, "none")
+		}
+	}
+}
+
+fn params() -> &'static [&'static dyn Param]
+{
+	unsafe
+	{
+		let start = &_boot_params_start as *const _ as *const &'static dyn Param;
+		let end = &_boot_params_end as *const _ as *const &'static dyn Param;
+		slice::from_raw_parts(start, end.offset_from(start) as usize)
+	}
+}
+
+fn find(name: &str) -> Option<&'static dyn Param>
+{
+	params().iter().find(|param| param.name() == name).copied()
+}
+
+fn cmdline() -> &'static str
+{
+	CMDLINE.get().and_then(|cmdline| core::str::from_utf8(&cmdline.bytes[..cmdline.len]).ok()).unwrap_or("")
+}
+
+// the arguments are separated by spaces, except between double quotes
+struct Args(&'static str);
+
+impl Iterator for Args
+{
+	type Item = &'static str;
+
+	fn next(&mut self) -> Option<&'static str>
+	{
+		let rest = self.0.trim_start_matches(' ');
+		let mut quoted = false;
+		let mut end = rest.len();
+
+		for (i, c) in rest.char_indices()
+		{
+			match c
+			{
+				'"' => quoted = !quoted,
+				' ' if !quoted =>
+				{
+					end = i;
+					break;
+				}
+				_ => {}
+			}
+		}
+		self.0 = &rest[end..];
+		if end == 0 { None } else { Some(&rest[..end]) }
+	}
+}
+
+// "name=value", "name=\"value with spaces\"" or "name"
+fn split_arg(arg: &'static str) -> (&'static str, Option<&'static str>)
+{
+	match arg.split_once('=')
+	{
+		Some((name, value)) =>
+		{
+			let value = value.strip_prefix('"').map_or(value, |value| value.strip_suffix('"').unwrap_or(value));
+			(name, Some(value))
+		}
+		None => (arg, None)
+	}
+}
+
+pub fn parse(args: &[u8])
+{
+	CMDLINE.call_once(||
+	{
+		let len = args.len().min(CMDLINE_SIZE);
+		let mut bytes = [0; CMDLINE_SIZE];
+
+		bytes[..len].copy_from_slice(&args[..len]);
+		Cmdline { bytes, len }
+	});
+
+	for arg in Args(cmdline())
+	{
+		crate::log_debug!("cmdline", "parsing arg {}", arg);
+
+		let (name, value) = split_arg(arg);
+		match find(name)
+		{
+			Some(param) if param.set(value) => {}
+			Some(_) => crate::log_warn!("cmdline", "invalid value for {}: {}", name, value.unwrap_or("")),
+			None => crate::log_warn!("cmdline", "unknown boot parameter {}", name)
+		}
+	}
+}
+
+crate::shell_command!("cmdline", &[], "show the boot parameters", |_| print());
+
+pub fn print()
+{
+	crate::println!("{}", cmdline());
+	for param in params()
+	{
+		crate::println!("  {:10} {}{}", param.name(), Value(*param), if param.is_set() { "" } else { " (default)" });
+	}
+	for arg in Args(cmdline()).filter(|arg| find(split_arg(arg).0).is_none())
+	{
+		crate::println!("  {:10} unknown", arg);
+	}
+}
@@ -0,0 +1,65 @@
+use core::fmt;
+use crate::memory;
+use super::symbols::{self, Demangle};
+
+const MAX_FRAMES: usize = 16;
+
+pub struct Frame
+{
+	pub address: usize,
+	// a return address points after the call, which may be the next function
+	pub is_return: bool
+}
+
+impl fmt::Display for Frame
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		let lookup = if self.is_return { self.address - 1 } else { self.address };
+
+		match symbols::resolve(lookup)
+		{
+			Some(symbol) => write!(f, "{:08x}  {}+{:#x}", self.address, Demangle(symbol.name), self.address - symbol.address),
+			None => write!(f, "{:08x}  ???", self.address)
+		}
+	}
+}
+
+// follow the saved ebp chain: each frame starts with the ebp of the caller,
+// followed by the return address. the chain ends with the null ebp set at boot
+pub fn walk(mut ebp: usize, mut callback: impl FnMut(Frame))
+{
+	for _ in 0..MAX_FRAMES
+	{
+		if ebp == 0 || !ebp.is_multiple_of(4) || !memory::is_mapped(ebp) || !memory::is_mapped(ebp + 4)
+		{
+			break;
+		}
+		let (next, address) = unsafe
+		{
+			(*(ebp as *const usize), *((ebp + 4) as *const usize))
+		};
+		if address == 0
+		{
+			break;
+		}
+		callback(Frame { address, is_return: true });
+		// the stack grows down, callers are always higher
+		if next <= ebp
+		{
+			break;
+		}
+		ebp = next;
+	}
+}
+
+pub fn print()
+{
+	let ebp = unsafe
+	{
+		crate::get_reg!("ebp") as usize
+	};
+
+	crate::println!("backtrace:");
+	walk(ebp, |frame| crate::println!("  {}", frame));
+}
@@ -0,0 +1,98 @@
+use super::is_accessible;
+
+const MAX_BREAKPOINTS: usize = 32;
+const INT3: u8 = 0xcc;
+
+#[derive(Copy, Clone)]
+struct Breakpoint
+{
+	address: usize,
+	original: u8
+}
+
+// software breakpoints, the patched byte is kept to restore it on removal
+pub struct Breakpoints
+{
+	slots: [Option<Breakpoint>; MAX_BREAKPOINTS]
+}
+
+impl Breakpoints
+{
+	pub const fn new() -> Breakpoints
+	{
+		Breakpoints
+		{
+			slots: [None; MAX_BREAKPOINTS]
+		}
+	}
+
+	fn find(&self, address: usize) -> Option<usize>
+	{
+		self.slots.iter().position(|slot| slot.is_some_and(|breakpoint| breakpoint.address == address))
+	}
+
+	pub fn contains(&self, address: usize) -> bool
+	{
+		self.find(address).is_some()
+	}
+
+	pub fn insert(&mut self, address: usize) -> bool
+	{
+		if self.contains(address)
+		{
+			return true;
+		}
+		let slot = match self.slots.iter().position(|slot| slot.is_none())
+		{
+			Some(slot) => slot,
+			None => return false
+		};
+		if !is_accessible(address, 1)
+		{
+			return false;
+		}
+		unsafe
+		{
+			let code = address as *mut u8;
+			self.slots[slot] = Some(Breakpoint { address, original: *code });
+			*code = INT3;
+		}
+		true
+	}
+
+	pub fn remove(&mut self, address: usize) -> bool
+	{
+		let slot = match self.find(address)
+		{
+			Some(slot) => slot,
+			None => return false
+		};
+		if let Some(breakpoint) = self.slots[slot].take()
+		{
+			unsafe
+			{
+				*(breakpoint.address as *mut u8) = breakpoint.original;
+			}
+		}
+		true
+	}
+
+	pub fn remove_all(&mut self)
+	{
+		for slot in self.slots.iter_mut()
+		{
+			if let Some(breakpoint) = slot.take()
+			{
+				unsafe
+				{
+					*(breakpoint.address as *mut u8) = breakpoint.original;
+				}
+			}
+		}
+	}
+
+	pub fn addresses(&self) -> impl Iterator<Item = usize> + '_
+	{
+		self.slots.iter().filter_map(|slot| slot.map(|breakpoint| breakpoint.address))
+	}
+}
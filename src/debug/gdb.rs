@@ -0,0 +1,490 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::arch::instructions;
+use crate::arch::interrupts::State;
+use crate::serial::{self, COM2};
+use crate::sync::Spinlock;
+use super::breakpoint::Breakpoints;
+use super::{interrupted_stack, is_accessible, BREAKPOINT_VECTOR, TRAP_FLAG};
+
+// gdb remote serial protocol stub, see "Remote Protocol" in the gdb manual.
+// it runs inside the #DB and #BP handlers with the interrupts disabled, the
+// other cpus keep running while the debugged one is stopped
+const PORT: u16 = COM2;
+const PACKET_SIZE: usize = 0x400;
+
+const CTRL_C: u8 = 0x03;
+
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+// eax, ecx, edx, ebx, esp, ebp, esi, edi, eip, eflags, cs, ss, ds, es, fs, gs
+const REGISTERS: usize = 16;
+
+struct Packet
+{
+	buffer: [u8; PACKET_SIZE],
+	len: usize
+}
+
+impl Packet
+{
+	fn clear(&mut self)
+	{
+		self.len = 0;
+	}
+
+	fn push(&mut self, byte: u8)
+	{
+		if self.len < PACKET_SIZE
+		{
+			self.buffer[self.len] = byte;
+			self.len += 1;
+		}
+	}
+
+	fn push_str(&mut self, s: &str)
+	{
+		for byte in s.bytes()
+		{
+			self.push(byte);
+		}
+	}
+
+	fn push_hex(&mut self, byte: u8)
+	{
+		self.push(hex_digit(byte >> 4));
+		self.push(hex_digit(byte & 0xf));
+	}
+
+	// registers are sent in the target byte order
+	fn push_u32(&mut self, value: u32)
+	{
+		for byte in value.to_le_bytes()
+		{
+			self.push_hex(byte);
+		}
+	}
+
+	fn as_str(&self) -> &str
+	{
+		core::str::from_utf8(&self.buffer[..self.len]).unwrap_or("")
+	}
+}
+
+struct Stub
+{
+	breakpoints: Breakpoints,
+	reply: Packet
+}
+
+struct Gdb
+{
+	packet: Packet,
+	stub: Stub
+}
+
+// "gdb" waits for the debugger at boot
+crate::boot_param!(pub ATTACH: bool = false, "gdb");
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// set by the serial interrupt when gdb asks to stop the kernel
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+static GDB: Spinlock<Gdb> = Spinlock::new(Gdb
+{
+	packet: Packet
+	{
+		buffer: [0; PACKET_SIZE],
+		len: 0
+	},
+	stub: Stub
+	{
+		breakpoints: Breakpoints::new(),
+		reply: Packet
+		{
+			buffer: [0; PACKET_SIZE],
+			len: 0
+		}
+	}
+});
+
+fn hex_digit(value: u8) -> u8
+{
+	b"0123456789abcdef"[(value & 0xf) as usize]
+}
+
+fn hex_value(digit: u8) -> Option<u8>
+{
+	(digit as char).to_digit(16).map(|value| value as u8)
+}
+
+fn parse_hex(s: &str) -> Option<usize>
+{
+	usize::from_str_radix(s, 16).ok()
+}
+
+fn parse_bytes(s: &str) -> impl Iterator<Item = Option<u8>> + '_
+{
+	s.as_bytes().chunks(2).map(|pair| Some(hex_value(pair[0])? << 4 | hex_value(*pair.get(1)?)?))
+}
+
+fn parse_u32(s: &str) -> Option<u32>
+{
+	let mut bytes = [0; 4];
+
+	if s.len() != 8
+	{
+		return None;
+	}
+	for (byte, value) in bytes.iter_mut().zip(parse_bytes(s))
+	{
+		*byte = value?;
+	}
+	Some(u32::from_le_bytes(bytes))
+}
+
+// "addr,len" as used by the memory and breakpoint packets
+fn parse_range(s: &str) -> Option<(usize, usize)>
+{
+	let (address, len) = s.split_once(',')?;
+	Some((parse_hex(address)?, parse_hex(len)?))
+}
+
+fn receive(packet: &mut Packet)
+{
+	loop
+	{
+		while serial::read(PORT) != b'$' {}
+
+		let mut checksum: u8 = 0;
+		packet.clear();
+		loop
+		{
+			let byte = serial::read(PORT);
+			if byte == b'#'
+			{
+				break;
+			}
+			checksum = checksum.wrapping_add(byte);
+			packet.push(byte);
+		}
+		let high = hex_value(serial::read(PORT));
+		let low = hex_value(serial::read(PORT));
+		if let (Some(high), Some(low)) = (high, low)
+		{
+			if high << 4 | low == checksum && packet.len < PACKET_SIZE
+			{
+				serial::write(b'+', PORT);
+				return;
+			}
+		}
+		serial::write(b'-', PORT);
+	}
+}
+
+fn send(packet: &Packet)
+{
+	loop
+	{
+		let checksum = packet.buffer[..packet.len].iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+
+		serial::write(b'$', PORT);
+		for byte in &packet.buffer[..packet.len]
+		{
+			serial::write(*byte, PORT);
+		}
+		serial::write(b'#', PORT);
+		serial::write(hex_digit(checksum >> 4), PORT);
+		serial::write(hex_digit(checksum & 0xf), PORT);
+		loop
+		{
+			match serial::read(PORT)
+			{
+				b'+' => return,
+				b'-' => break,
+				_ => {}
+			}
+		}
+	}
+}
+
+fn read_register(state: &State, index: usize) -> u32
+{
+	match index
+	{
+		0 => state.eax,
+		1 => state.ecx,
+		2 => state.edx,
+		3 => state.ebx,
+		4 => interrupted_stack(state).0,
+		5 => state.ebp,
+		6 => state.esi,
+		7 => state.edi,
+		8 => state.eip,
+		9 => state.eflags,
+		10 => state.cs,
+		11 => interrupted_stack(state).1,
+		12..=15 => state.ds,
+		_ => 0
+	}
+}
+
+// the stack pointer and the segments cannot be changed from here
+fn write_register(state: &mut State, index: usize, value: u32)
+{
+	match index
+	{
+		0 => state.eax = value,
+		1 => state.ecx = value,
+		2 => state.edx = value,
+		3 => state.ebx = value,
+		5 => state.ebp = value,
+		6 => state.esi = value,
+		7 => state.edi = value,
+		8 => state.eip = value,
+		9 => state.eflags = value,
+		_ => {}
+	}
+}
+
+impl Stub
+{
+	fn read_memory(&mut self, args: &str)
+	{
+		match parse_range(args)
+		{
+			Some((address, len)) if len <= (PACKET_SIZE - 1) / 2 && is_accessible(address, len) =>
+			{
+				for offset in 0..len
+				{
+					let byte = unsafe
+					{
+						*((address + offset) as *const u8)
+					};
+					self.reply.push_hex(byte);
+				}
+			}
+			_ => self.reply.push_str("E14")
+		}
+	}
+
+	fn write_memory(&mut self, args: &str)
+	{
+		let (range, data) = match args.split_once(':')
+		{
+			Some(split) => split,
+			None => return self.reply.push_str("E01")
+		};
+		match parse_range(range)
+		{
+			Some((address, len)) if data.len() == len * 2 && is_accessible(address, len) =>
+			{
+				for (offset, byte) in parse_bytes(data).enumerate()
+				{
+					match byte
+					{
+						Some(byte) => unsafe
+						{
+							*((address + offset) as *mut u8) = byte;
+						},
+						None => return self.reply.push_str("E01")
+					}
+				}
+				self.reply.push_str("OK");
+			}
+			_ => self.reply.push_str("E14")
+		}
+	}
+
+	// "type,addr,kind", only the software breakpoints are supported
+	fn breakpoint_packet(&mut self, args: &str, insert: bool)
+	{
+		let (breakpoint_type, range) = match args.split_once(',')
+		{
+			Some(split) => split,
+			None => return self.reply.push_str("E01")
+		};
+		if breakpoint_type != "0"
+		{
+			return;
+		}
+		let ok = match parse_range(range)
+		{
+			Some((address, _)) if insert => self.breakpoints.insert(address),
+			Some((address, _)) => self.breakpoints.remove(address),
+			None => false
+		};
+		self.reply.push_str(if ok { "OK" } else { "E0e" });
+	}
+
+	fn query(&mut self, args: &str)
+	{
+		if args.starts_with("Supported")
+		{
+			self.reply.push_str("PacketSize=");
+			for byte in (PACKET_SIZE as u32).to_be_bytes().iter().skip_while(|byte| **byte == 0)
+			{
+				self.reply.push_hex(*byte);
+			}
+			self.reply.push_str(";swbreak+");
+		}
+		else if args == "Attached"
+		{
+			self.reply.push_str("1");
+		}
+	}
+
+	fn stop_reply(&mut self, signal: u8)
+	{
+		self.reply.push(b'S');
+		self.reply.push_hex(signal);
+	}
+
+	// returns false when the packet resumes the execution
+	fn handle_packet(&mut self, state: &mut State, packet: &str, signal: u8) -> bool
+	{
+		let (command, args) = match packet.chars().next()
+		{
+			Some(command) => (command, &packet[1..]),
+			None => return true
+		};
+
+		self.reply.clear();
+		match command
+		{
+			'?' => self.stop_reply(signal),
+			'g' =>
+			{
+				for index in 0..REGISTERS
+				{
+					self.reply.push_u32(read_register(state, index));
+				}
+			}
+			'G' =>
+			{
+				for index in 0..REGISTERS
+				{
+					if let Some(value) = args.get(index * 8..index * 8 + 8).and_then(parse_u32)
+					{
+						write_register(state, index, value);
+					}
+				}
+				self.reply.push_str("OK");
+			}
+			'p' =>
+			{
+				match parse_hex(args)
+				{
+					Some(index) if index < REGISTERS => self.reply.push_u32(read_register(state, index)),
+					_ => self.reply.push_str("E00")
+				}
+			}
+			'P' =>
+			{
+				let register = args.split_once('=').and_then(|(index, value)| Some((parse_hex(index)?, parse_u32(value)?)));
+				match register
+				{
+					Some((index, value)) if index < REGISTERS =>
+					{
+						write_register(state, index, value);
+						self.reply.push_str("OK");
+					}
+					_ => self.reply.push_str("E00")
+				}
+			}
+			'm' => self.read_memory(args),
+			'M' => self.write_memory(args),
+			'Z' => self.breakpoint_packet(args, true),
+			'z' => self.breakpoint_packet(args, false),
+			'q' => self.query(args),
+			'H' => self.reply.push_str("OK"),
+			'c' | 's' =>
+			{
+				if let Some(address) = parse_hex(args)
+				{
+					state.eip = address as u32;
+				}
+				if command == 's'
+				{
+					state.eflags |= TRAP_FLAG;
+				}
+				return false;
+			}
+			'D' | 'k' =>
+			{
+				self.breakpoints.remove_all();
+				if command == 'D'
+				{
+					self.reply.push_str("OK");
+					send(&self.reply);
+				}
+				return false;
+			}
+			_ => {}
+		}
+		send(&self.reply);
+		true
+	}
+}
+
+pub fn init() -> bool
+{
+	if !serial::init(PORT, &serial::config(1))
+	{
+		return false;
+	}
+	serial::enable_receive_interrupt(PORT);
+	ENABLED.store(true, Ordering::Release);
+	true
+}
+
+pub fn is_enabled() -> bool
+{
+	ENABLED.load(Ordering::Acquire)
+}
+
+// stop and hand the control to gdb
+pub fn breakpoint()
+{
+	instructions::int3();
+}
+
+// gdb sends ^C to stop the kernel while it is running
+pub fn serial_interrupt()
+{
+	while let Some(byte) = serial::try_read(PORT)
+	{
+		if byte == CTRL_C && is_enabled()
+		{
+			INTERRUPTED.store(true, Ordering::Release);
+			breakpoint();
+		}
+	}
+}
+
+// #DB and #BP, talk with gdb until it resumes the execution
+pub fn handle_exception(state: &mut State)
+{
+	let mut gdb = GDB.lock();
+	let Gdb { packet, stub } = &mut *gdb;
+	let signal = if INTERRUPTED.swap(false, Ordering::AcqRel) { SIGINT } else { SIGTRAP };
+
+	// #BP is a trap, eip is after the int3 that was hit
+	if state.interrupt == BREAKPOINT_VECTOR && stub.breakpoints.contains((state.eip as usize).wrapping_sub(1))
+	{
+		state.eip -= 1;
+	}
+	state.eflags &= !TRAP_FLAG;
+
+	stub.reply.clear();
+	stub.stop_reply(signal);
+	send(&stub.reply);
+	loop
+	{
+		receive(packet);
+		if !stub.handle_packet(state, packet.as_str(), signal)
+		{
+			break;
+		}
+	}
+}
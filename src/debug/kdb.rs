@@ -0,0 +1,484 @@
+use core::fmt::{self, Write};
+use crate::arch::instructions;
+use crate::arch::interrupts::State;
+use crate::ferramenta;
+use crate::keyboard;
+use crate::memory;
+use crate::serial;
+use crate::sync::Spinlock;
+use crate::tty;
+use crate::vga;
+use super::backtrace::{self, Frame};
+use super::breakpoint::Breakpoints;
+use super::{interrupted_stack, is_accessible, BREAKPOINT_VECTOR, DEBUG_VECTOR, TRAP_FLAG};
+
+// interactive kernel debugger on the VGA console and the serial console,
+// entered on panic, on #BP and with ctrl+F12. it polls the keyboard, the interrupts stay disabled
+const LINE_SIZE: usize = 80;
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+
+// the scancode handled before the layout translation
+const BACKSPACE_KEY: u8 = 0x0e;
+
+const DEFAULT_DUMP_SIZE: usize = 64;
+const DIS_BEFORE: usize = 16;
+const DIS_AFTER: usize = 32;
+const BITMAP_ROW: usize = 64;
+const DEFAULT_BITMAP_PAGES: usize = 512;
+
+const PAGE_FLAGS: [(u32, &str); 9] =
+[
+	(1 << 0, "P"),
+	(1 << 1, "RW"),
+	(1 << 2, "US"),
+	(1 << 3, "PWT"),
+	(1 << 4, "PCD"),
+	(1 << 5, "A"),
+	(1 << 6, "D"),
+	(1 << 7, "PS"),
+	(1 << 8, "G")
+];
+
+// VGA and serial at the same time, bypassing the ttys
+struct Console;
+
+impl fmt::Write for Console
+{
+	fn write_str(&mut self, s: &str) -> fmt::Result
+	{
+		vga::W.lock().write_string(s);
+		crate::serial_print!("{}", s);
+		Ok(())
+	}
+}
+
+macro_rules! kdb_print
+{
+	($($arg:tt)*) => (Console.write_fmt(format_args!($($arg)*)).unwrap());
+}
+
+macro_rules! kdb_println
+{
+	() => (kdb_print!("\n"));
+	($($arg:tt)*) => (kdb_print!("{}\n", format_args!($($arg)*)));
+}
+
+#[derive(PartialEq)]
+enum Resume
+{
+	Continue,
+	Step
+}
+
+// registers of the stopped code, with its stack pointer
+struct Stopped
+{
+	state: State,
+	esp: u32,
+	ss: u32
+}
+
+struct Kdb
+{
+	breakpoints: Breakpoints,
+	// breakpoint removed to execute its original instruction, put back on #DB
+	stepping_over: Option<usize>,
+	single_step: bool
+}
+
+crate::boot_param!(NOKDB: bool = false, "nokdb");
+
+static KDB: Spinlock<Kdb> = Spinlock::new(Kdb
+{
+	breakpoints: Breakpoints::new(),
+	stepping_over: None,
+	single_step: false
+});
+
+pub fn is_enabled() -> bool
+{
+	!NOKDB.get()
+}
+
+pub fn breakpoint()
+{
+	instructions::int3();
+}
+
+fn read_byte() -> u8
+{
+	loop
+	{
+		if let Some(input) = keyboard::poll()
+		{
+			if !input.released && !input.extended && input.scancode == BACKSPACE_KEY
+			{
+				return BACKSPACE;
+			}
+			if let Some(key) = keyboard::char_from_scancode(&input)
+			{
+				return key as u8;
+			}
+		}
+		let has_serial = unsafe
+		{
+			crate::SETTINGS.has_serial
+		};
+		if has_serial
+		{
+			match serial::try_read(serial::console_port())
+			{
+				Some(b'\r') => return b'\n',
+				Some(DELETE) => return BACKSPACE,
+				Some(byte) => return byte,
+				None => {}
+			}
+		}
+	}
+}
+
+fn read_line(line: &mut [u8; LINE_SIZE]) -> &str
+{
+	let mut len = 0;
+
+	loop
+	{
+		match read_byte()
+		{
+			b'\n' => break,
+			BACKSPACE =>
+			{
+				if len > 0
+				{
+					len -= 1;
+					vga::W.lock().backspace();
+					crate::serial_print!("\x08 \x08");
+				}
+			}
+			byte if (0x20..0x7f).contains(&byte) && len < LINE_SIZE =>
+			{
+				line[len] = byte;
+				len += 1;
+				kdb_print!("{}", byte as char);
+			}
+			_ => {}
+		}
+	}
+	kdb_println!();
+	core::str::from_utf8(&line[..len]).unwrap_or("")
+}
+
+fn parse_address(arg: Option<&str>) -> Option<usize>
+{
+	let arg = arg?;
+	usize::from_str_radix(arg.trim_start_matches("0x"), 16).ok()
+}
+
+fn help()
+{
+	kdb_println!("regs                   registers of the interrupted code");
+	kdb_println!("bt                     backtrace");
+	kdb_println!("ps                     stack dump and backtrace");
+	kdb_println!("dis [addr]             bytes around eip or addr");
+	kdb_println!("pm <addr> [len]        memory dump");
+	kdb_println!("pt <addr>              page table walk");
+	kdb_println!("bitmap [page] [count]  page frame bitmap");
+	kdb_println!("break | delete <addr>  set or remove a breakpoint");
+	kdb_println!("breaks                 list the breakpoints");
+	kdb_println!("step | s               execute one instruction");
+	kdb_println!("continue | c           leave the debugger");
+	kdb_println!("reboot");
+}
+
+fn print_registers(stopped: &Stopped)
+{
+	let state = &stopped.state;
+	let (esp, ss) = (stopped.esp, stopped.ss);
+	let (interrupt, error) = (state.interrupt, state.error);
+	let (eip, eflags, cs, ds) = (state.eip, state.eflags, state.cs, state.ds);
+	let (eax, ebx, ecx, edx) = (state.eax, state.ebx, state.ecx, state.edx);
+	let (esi, edi, ebp) = (state.esi, state.edi, state.ebp);
+
+	kdb_println!("eip: {:08x}   eflags: {:08x}   interrupt: {:02x}   error: {:08x}", eip, eflags, interrupt, error);
+	kdb_println!("eax: {:08x}   ebx: {:08x}   ecx: {:08x}   edx: {:08x}", eax, ebx, ecx, edx);
+	kdb_println!("esi: {:08x}   edi: {:08x}   esp: {:08x}   ebp: {:08x}", esi, edi, esp, ebp);
+	kdb_println!(" cs:     {:04x}    ds:     {:04x}    ss:     {:04x}", cs, ds, ss);
+	kdb_println!("cr0: {:08x}   cr2: {:08x}   cr3: {:08x}   cr4: {:08x}", instructions::read_cr0(), instructions::read_cr2(),
+				 instructions::read_cr3(), instructions::read_cr4());
+}
+
+fn print_backtrace(state: &State)
+{
+	kdb_println!("  {}", Frame { address: state.eip as usize, is_return: false });
+	backtrace::walk(state.ebp as usize, |frame| kdb_println!("  {}", frame));
+}
+
+fn dump(address: usize, len: usize)
+{
+	if !is_accessible(address, len)
+	{
+		kdb_println!("{:#010x}: not mapped", address);
+		return;
+	}
+	unsafe
+	{
+		ferramenta::write_memory(&mut Console, address as *const u8, len).unwrap();
+	}
+}
+
+fn print_stack(stopped: &Stopped)
+{
+	dump(stopped.esp as usize, 10 * 16);
+	print_backtrace(&stopped.state);
+}
+
+// there is no disassembler, the bytes are aligned so eip starts a row
+fn disassemble(address: usize)
+{
+	kdb_println!("{}", Frame { address, is_return: false });
+	dump(address.saturating_sub(DIS_BEFORE), DIS_BEFORE + DIS_AFTER);
+}
+
+fn print_page_entry(name: &str, index: usize, value: u32)
+{
+	kdb_print!("{}[{:4}] = {:08x}  addr {:08x} ", name, index, value, value & 0xffff_f000);
+	for (bit, flag) in PAGE_FLAGS.iter()
+	{
+		if value & bit != 0
+		{
+			kdb_print!(" {}", flag);
+		}
+	}
+	kdb_println!();
+}
+
+fn walk_page_tables(address: usize)
+{
+	let (pdi, pti) = memory::page_map_indexer(address);
+
+	match memory::page_table_entries(address)
+	{
+		None => kdb_println!("paging is disabled"),
+		Some((directory_entry, table_entry)) =>
+		{
+			print_page_entry("pde", pdi, directory_entry);
+			match table_entry
+			{
+				Some(table_entry) =>
+				{
+					print_page_entry("pte", pti, table_entry);
+					if table_entry & 1 != 0
+					{
+						kdb_println!("{:08x} -> {:08x}", address, (table_entry & 0xffff_f000) as usize | (address & 0xfff));
+					}
+				}
+				None if directory_entry & 1 != 0 =>
+				{
+					kdb_println!("{:08x} -> {:08x} (4 MiB page)", address, (directory_entry & 0xffc0_0000) as usize | (address & 0x3f_ffff));
+				}
+				None => kdb_println!("{:08x} is not mapped", address)
+			}
+		}
+	}
+}
+
+fn print_bitmap(start: usize, count: usize)
+{
+	let ((free, locked, reserved), frames) = match (memory::page_frame_usage(), memory::page_frame_count())
+	{
+		(Some(usage), Some(frames)) => (usage, frames),
+		_ =>
+		{
+			kdb_println!("the page frame allocator is busy");
+			return;
+		}
+	};
+	if start >= frames
+	{
+		kdb_println!("bitmap: the first page frame is past the last one ({})", frames);
+		return;
+	}
+	let end = start.saturating_add(count).min(frames);
+
+	kdb_println!("free {}KiB - used {}KiB - reserved {}KiB - {} page frames", free / 1024, locked / 1024, reserved / 1024, frames);
+	for row in (start..end).step_by(BITMAP_ROW)
+	{
+		kdb_print!("{:6}: ", row);
+		for page in row..(row + BITMAP_ROW).min(end)
+		{
+			kdb_print!("{}", match memory::is_page_frame_used(page)
+			{
+				Some(true) => '#',
+				Some(false) => '.',
+				None => '?'
+			});
+		}
+		kdb_println!();
+	}
+}
+
+impl Kdb
+{
+	fn list_breakpoints(&self)
+	{
+		for address in self.breakpoints.addresses()
+		{
+			kdb_println!("  {}", Frame { address, is_return: false });
+		}
+	}
+
+	// without a stopped state, the panic did not come from an exception
+	fn run(&mut self, stopped: Option<&Stopped>, resumable: bool, reason: &str) -> Resume
+	{
+		let mut line = [0u8; LINE_SIZE];
+
+		kdb_println!();
+		kdb_println!("kdb: stopped on {}, type help for the commands", reason);
+		if let Some(stopped) = stopped
+		{
+			kdb_println!("{}", Frame { address: stopped.state.eip as usize, is_return: false });
+		}
+		loop
+		{
+			kdb_print!("kdb> ");
+			let command = read_line(&mut line);
+			let mut args = command.split_whitespace();
+			let name = match args.next()
+			{
+				Some(name) => name,
+				None => continue
+			};
+			let first = parse_address(args.next());
+			let second = parse_address(args.next());
+
+			match name
+			{
+				"help" => help(),
+				"regs" | "ps" if stopped.is_none() => kdb_println!("{}: no saved registers", name),
+				"regs" => print_registers(stopped.unwrap()),
+				"ps" => print_stack(stopped.unwrap()),
+				"bt" =>
+				{
+					match stopped
+					{
+						Some(stopped) => print_backtrace(&stopped.state),
+						None => backtrace::walk(unsafe { crate::get_reg!("ebp") } as usize, |frame| kdb_println!("  {}", frame))
+					}
+				}
+				"dis" =>
+				{
+					match first.or(stopped.map(|stopped| stopped.state.eip as usize))
+					{
+						Some(address) => disassemble(address),
+						None => kdb_println!("dis: missing address")
+					}
+				}
+				"pm" | "pt" | "break" | "delete" if first.is_none() => kdb_println!("{}: missing address", name),
+				"pm" => dump(first.unwrap(), second.unwrap_or(DEFAULT_DUMP_SIZE)),
+				"pt" => walk_page_tables(first.unwrap()),
+				"bitmap" => print_bitmap(first.unwrap_or(0), second.unwrap_or(DEFAULT_BITMAP_PAGES)),
+				"break" =>
+				{
+					if !self.breakpoints.insert(first.unwrap())
+					{
+						kdb_println!("break: cannot set a breakpoint at {:#x}", first.unwrap());
+					}
+				}
+				"delete" =>
+				{
+					if !self.breakpoints.remove(first.unwrap())
+					{
+						kdb_println!("delete: no breakpoint at {:#x}", first.unwrap());
+					}
+				}
+				"breaks" => self.list_breakpoints(),
+				"step" | "s" | "continue" | "c" if !resumable => kdb_println!("{}: cannot resume after a panic", name),
+				"step" | "s" => return Resume::Step,
+				"continue" | "c" => return Resume::Continue,
+				"reboot" =>
+				{
+					crate::acpi::reboot();
+					kdb_println!("reboot: failed");
+				}
+				_ => kdb_println!("{}: unknown command", name)
+			}
+		}
+	}
+}
+
+// #DB and #BP
+pub fn handle_exception(state: &mut State)
+{
+	let mut kdb = KDB.lock();
+
+	if state.interrupt == DEBUG_VECTOR
+	{
+		if let Some(address) = kdb.stepping_over.take()
+		{
+			kdb.breakpoints.insert(address);
+			if !kdb.single_step
+			{
+				state.eflags &= !TRAP_FLAG;
+				return;
+			}
+		}
+	}
+	// #BP is a trap, eip is after the int3 that was hit
+	if state.interrupt == BREAKPOINT_VECTOR && kdb.breakpoints.contains((state.eip as usize).wrapping_sub(1))
+	{
+		state.eip -= 1;
+	}
+	state.eflags &= !TRAP_FLAG;
+
+	let reason = if state.interrupt == DEBUG_VECTOR { "single step" } else { "breakpoint" };
+	let (esp, ss) = interrupted_stack(state);
+	let stopped = Stopped
+	{
+		state: *state,
+		esp,
+		ss
+	};
+	let resume = kdb.run(Some(&stopped), true, reason);
+
+	kdb.single_step = resume == Resume::Step;
+	if kdb.single_step
+	{
+		state.eflags |= TRAP_FLAG;
+	}
+	// the int3 cannot stay in place, the original instruction runs first
+	let eip = state.eip as usize;
+	if kdb.breakpoints.contains(eip)
+	{
+		kdb.breakpoints.remove(eip);
+		kdb.stepping_over = Some(eip);
+		state.eflags |= TRAP_FLAG;
+	}
+	drop(kdb);
+	keyboard::reset_state();
+	tty::refresh();
+}
+
+// the panic handler already printed the state, only the inspection is left
+pub fn enter_panic()
+{
+	// the panic may come from the debugger itself, and the keyboard irq
+	// must not take the scancodes
+	unsafe
+	{
+		KDB.force_unlock();
+		crate::arch::interrupts::disable();
+	}
+	let mut kdb = KDB.lock();
+	// saved by the exception handlers, esp is the one of the handler
+	let state = *crate::INTERRUPT_STATE.lock();
+	let stopped = Stopped
+	{
+		state,
+		esp: state.esp,
+		ss: 0x10
+	};
+
+	kdb.breakpoints.remove_all();
+	kdb.run(if state.eip != 0 { Some(&stopped) } else { None }, false, "panic");
+}
@@ -0,0 +1,40 @@
+use core::mem::size_of;
+use crate::arch::interrupts::State;
+use crate::memory;
+
+pub mod backtrace;
+pub mod breakpoint;
+pub mod gdb;
+pub mod kdb;
+pub mod symbols;
+
+pub const DEBUG_VECTOR: u32 = 0x01;
+pub const BREAKPOINT_VECTOR: u32 = 0x03;
+pub const TRAP_FLAG: u32 = 1 << 8;
+
+const KERNEL_DATA_SELECTOR: u32 = 0x10;
+
+pub fn is_accessible(address: usize, len: usize) -> bool
+{
+	match address.checked_add(len)
+	{
+		Some(end) => (address..end).all(memory::is_mapped),
+		None => false
+	}
+}
+
+// the cpu only pushes esp and ss when the interrupt changed the privilege
+// level, otherwise the interrupted stack starts right after the frame
+pub fn interrupted_stack(state: &State) -> (u32, u32)
+{
+	let frame_end = state as *const State as usize + size_of::<State>();
+
+	if state.cs & 0x3 == 0
+	{
+		return (frame_end as u32, KERNEL_DATA_SELECTOR);
+	}
+	unsafe
+	{
+		(*(frame_end as *const u32), *((frame_end + 4) as *const u32))
+	}
+}
@@ -0,0 +1,221 @@
+use core::fmt;
+use core::mem::size_of;
+use core::slice;
+use crate::multiboot::MultibootTagElfSections;
+use crate::sync::Once;
+
+const SHT_SYMTAB: u32 = 2;
+const STT_FUNC: u8 = 2;
+
+// length of the "h" + 16 hex digits hash ending the legacy rust symbols
+const HASH_LEN: usize = 17;
+
+#[repr(C)]
+struct ElfSymbol
+{
+	name: u32,
+	value: u32,
+	size: u32,
+	info: u8,
+	other: u8,
+	shndx: u16
+}
+
+struct SymbolTable
+{
+	symbols: &'static [ElfSymbol],
+	strings: &'static [u8],
+	end: usize
+}
+
+impl SymbolTable
+{
+	fn name(&self, offset: u32) -> &'static str
+	{
+		let strings = self.strings.get(offset as usize..).unwrap_or(&[]);
+		let len = strings.iter().position(|c| *c == 0).unwrap_or(strings.len());
+		core::str::from_utf8(&strings[..len]).unwrap_or("???")
+	}
+}
+
+pub struct Symbol
+{
+	pub name: &'static str,
+	pub address: usize,
+	pub offset: usize
+}
+
+// the ELF symbol table of the kernel, loaded by GRUB after the kernel image
+static SYMBOLS: Once<SymbolTable> = Once::new();
+
+pub fn init(tag: &MultibootTagElfSections) -> bool
+{
+	let sections = tag.sections();
+	let symtab = match sections.iter().find(|section| section.section_type == SHT_SYMTAB)
+	{
+		Some(symtab) => symtab,
+		None => return false
+	};
+	let strtab = match sections.get(symtab.link as usize)
+	{
+		Some(strtab) => strtab,
+		None => return false
+	};
+	if symtab.addr == 0 || strtab.addr == 0
+	{
+		return false;
+	}
+
+	SYMBOLS.call_once(||
+	{
+		unsafe
+		{
+			SymbolTable
+			{
+				symbols: slice::from_raw_parts(symtab.addr as *const ElfSymbol, symtab.size as usize / size_of::<ElfSymbol>()),
+				strings: slice::from_raw_parts(strtab.addr as *const u8, strtab.size as usize),
+				end: (symtab.addr + symtab.size).max(strtab.addr + strtab.size) as usize
+			}
+		}
+	});
+	true
+}
+
+// end of the memory used by the symbol table, it must not be given away
+pub fn end() -> usize
+{
+	SYMBOLS.get().map_or(0, |table| table.end)
+}
+
+// the function containing the address, or the closest one before it
+pub fn resolve(address: usize) -> Option<Symbol>
+{
+	let table = SYMBOLS.get()?;
+	let mut best: Option<&ElfSymbol> = None;
+
+	for symbol in table.symbols.iter().filter(|symbol| symbol.info & 0xf == STT_FUNC && symbol.value as usize <= address)
+	{
+		if (address - symbol.value as usize) < symbol.size as usize
+		{
+			best = Some(symbol);
+			break;
+		}
+		if best.is_none_or(|best| symbol.value > best.value)
+		{
+			best = Some(symbol);
+		}
+	}
+	best.map(|symbol| Symbol
+	{
+		name: table.name(symbol.name),
+		address: symbol.value as usize,
+		offset: address - symbol.value as usize
+	})
+}
+
+// displays a legacy mangled rust symbol as a path, other symbols are left as is
+pub struct Demangle(pub &'static str);
+
+impl fmt::Display for Demangle
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		let mut rest = match self.0.strip_prefix("_ZN")
+		{
+			Some(rest) if is_mangled(rest) => rest,
+			_ => return f.write_str(self.0)
+		};
+		let mut first = true;
+
+		while let Some((segment, next)) = next_segment(rest)
+		{
+			rest = next;
+			if rest == "E" && is_hash(segment)
+			{
+				break;
+			}
+			if !first
+			{
+				f.write_str("::")?;
+			}
+			write_segment(f, segment)?;
+			first = false;
+		}
+		Ok(())
+	}
+}
+
+// a mangled name is a list of length prefixed segments ending with "E"
+fn next_segment(rest: &str) -> Option<(&str, &str)>
+{
+	let digits = rest.bytes().take_while(|c| c.is_ascii_digit()).count();
+	let len = rest[..digits].parse::<usize>().ok()?;
+	let segment = rest.get(digits..digits + len)?;
+
+	Some((segment, &rest[digits + len..]))
+}
+
+fn is_mangled(mut rest: &str) -> bool
+{
+	while let Some((_, next)) = next_segment(rest)
+	{
+		rest = next;
+	}
+	rest == "E"
+}
+
+fn is_hash(segment: &str) -> bool
+{
+	segment.len() == HASH_LEN && segment.starts_with('h') && segment[1..].bytes().all(|c| c.is_ascii_hexdigit())
+}
+
+fn write_segment(f: &mut fmt::Formatter, segment: &str) -> fmt::Result
+{
+	// a leading "_" only protects a segment starting with an escape
+	let mut rest = if segment.starts_with("_$") { &segment[1..] } else { segment };
+
+	while !rest.is_empty()
+	{
+		if let Some(escape) = rest.strip_prefix('$')
+		{
+			if let Some(end) = escape.find('$')
+			{
+				let replacement = match &escape[..end]
+				{
+					"SP" => "@",
+					"BP" => "*",
+					"RF" => "&",
+					"LT" => "<",
+					"GT" => ">",
+					"LP" => "(",
+					"RP" => ")",
+					"C" => ",",
+					"u20" => " ",
+					"u22" => "\"",
+					"u27" => "'",
+					"u2b" => "+",
+					"u3b" => ";",
+					"u5b" => "[",
+					"u5d" => "]",
+					"u7b" => "{",
+					"u7d" => "}",
+					"u7e" => "~",
+					_ => &rest[..end + 2]
+				};
+				f.write_str(replacement)?;
+				rest = &escape[end + 1..];
+				continue;
+			}
+		}
+		if let Some(path) = rest.strip_prefix("..")
+		{
+			f.write_str("::")?;
+			rest = path;
+			continue;
+		}
+		let len = rest[1..].find(['$', '.']).map_or(rest.len(), |len| len + 1);
+		f.write_str(&rest[..len])?;
+		rest = &rest[len..];
+	}
+	Ok(())
+}
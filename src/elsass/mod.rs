@@ -1,3 +1,5 @@
+crate::shell_command!("elsass", &[], "the flag of Alsace, on the serial port", |_| flag());
+
 pub fn flag()
 {
 		crate::serial_println!("[107;40m[38;5;015m@[38;5;015m@[38;5;015m@[38;5;015m@[38;5;015m@[38;5;015m@[38;5;015m@[38;5;009m/[38;5;009m/[38;5;009m/[38;5;015m@[38;5;015m@[38;5;015m@[38;5;015m@[38;5;015m@[38;5;015m@[38;5;015m@[38;5;015m@[38;5;009m/[38;5;015m@[38;5;015m@[38;5;015m@[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/[38;5;009m/");
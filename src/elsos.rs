@@ -10,17 +10,22 @@ extern crate alloc;
 
 use core::panic::PanicInfo;
 use crate::multiboot::{MULTIBOOT_MMAP, MULTIBOOT_MMAP_ENTRIES};
+use crate::sync::IrqSpinlock;
 
-use alloc::string::String;
-
+mod acpi;
 mod arch;
+mod cmdline;
+mod debug;
 mod elsass;
 mod ferramenta;
 mod keyboard;
+mod klog;
 mod libc;
 mod memory;
 mod multiboot;
+mod random;
 mod serial;
+mod sync;
 mod syscall;
 mod time;
 mod tty;
@@ -43,6 +48,9 @@ pub static mut SETTINGS: Settings = Settings
 	layout: 0
 };
 
+// the first user program, nothing starts it until there is a userspace
+boot_param!(INIT: &'static str = "/sbin/init", "init");
+
 #[no_mangle]
 pub extern "C" fn kernel_main(magic: u32, address: u32)
 {
@@ -51,8 +59,12 @@ pub extern "C" fn kernel_main(magic: u32, address: u32)
 	vga::cursor::Cursor::init(0, 15);
 	if multiboot::check_magic(magic) && multiboot::parse(address)
 	{
+		keyboard::init();
 		init_serial();
 		init_memory();
+		init_framebuffer();
+		acpi::init();
+		random::init();
 		logln!("\n");
 		logln!("        :::      ::::::::    __       __       __ _  ____  ____  ");
 		logln!("      :+:      :+:    :+:  .'  `'._.'`  '.    (  / )(  __)/ ___) ");
@@ -65,10 +77,14 @@ pub extern "C" fn kernel_main(magic: u32, address: u32)
 		tty::prompt();
 		unsafe
 		{
-			keyboard::BUFFER.as_mut_ptr().write(String::new());
 			arch::interrupts::init();
+			logln!("[{}] initialized the FPU{}", ok_fail(arch::fpu::init()), if arch::fpu::is_sse_enabled() { " and SSE" } else { "" });
+			arch::smp::init();
 			arch::interrupts::enable();
 		}
+		init_gdb();
+		init_serial_tty();
+		tty::script::run_init();
 		//tests();
 		loop
 		{
@@ -99,9 +115,50 @@ fn init_vga()
 	vga::Buffer::clear();
 }
 
+// the output printed so far is drawn again on the new console
+fn init_framebuffer()
+{
+	if vga::framebuffer::init()
+	{
+		tty::refresh();
+		log_info!("vga", "framebuffer console of {}x{} characters", vga::width(), vga::height());
+	}
+}
+
 fn init_serial()
 {
-	crate::println!("[{}] initialized serial", ok_fail(serial::init(serial::COM1)));
+	let serial_ok = serial::init_console();
+	unsafe
+	{
+		SETTINGS.has_serial = serial_ok;
+	}
+	crate::println!("[{}] initialized serial", ok_fail(serial_ok));
+}
+
+fn init_gdb()
+{
+	if !debug::gdb::ATTACH.get()
+	{
+		return;
+	}
+	let gdb_ok = debug::gdb::init();
+	logln!("[{}] initialized the gdb stub on COM2", ok_fail(gdb_ok));
+	if gdb_ok
+	{
+		log_info!("gdb", "waiting for gdb to attach");
+		debug::gdb::breakpoint();
+	}
+}
+
+// the shell is also reachable from COM1, with "-serial stdio" in qemu
+fn init_serial_tty()
+{
+	let tty_ok = tty::serial::init();
+	logln!("[{}] started a shell on ttyS0", ok_fail(tty_ok));
+	if tty_ok
+	{
+		tty::serial::prompt();
+	}
 }
 
 fn init_memory()
@@ -111,6 +168,7 @@ fn init_memory()
 		crate::memory::init(MULTIBOOT_MMAP, MULTIBOOT_MMAP_ENTRIES);
 		crate::println!("[{}] initilized memory", ok_fail(true));
 	}
+	crate::println!("[{}] allocated the scrollback of the ttys", ok_fail(tty::init_scrollback()));
 }
 
 pub fn ok_fail(value: bool) -> &'static str
@@ -122,7 +180,8 @@ pub fn ok_fail(value: bool) -> &'static str
 	}
 }
 
-static mut INTERRUPT_STATE: arch::interrupts::State = arch::interrupts::State
+// the registers of the last exception, printed by the panic handler
+static INTERRUPT_STATE: IrqSpinlock<arch::interrupts::State> = IrqSpinlock::new(arch::interrupts::State
 {
 	eax: 0,
 	ebx: 0,
@@ -142,17 +201,29 @@ static mut INTERRUPT_STATE: arch::interrupts::State = arch::interrupts::State
 
 	eip: 0,
 	eflags: 0,
-};
+});
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> !
 {
+	// we may have panicked while printing, nobody will release these
+	unsafe
+	{
+		serial::W.force_unlock();
+		serial::force_unlock();
+		vga::W.force_unlock();
+		tty::force_unlock();
+	}
 	vga::panic();
 	logln!("\n\x1B[31;49m{}\x1B[39;49m\n", info);
 
 	print_panic_state(false);
 
 	logln!("");
+	if debug::kdb::is_enabled()
+	{
+		debug::kdb::enter_panic();
+	}
 	unsafe
 	{
 		clear_reg!("eax");
@@ -170,39 +241,12 @@ fn panic(info: &PanicInfo) -> !
 
 fn print_panic_state(serial_only: bool)
 {
-	let eip: u32;
-	let eflags: u32;
-
-	let eax: u32;
-	let ebx: u32;
-	let ecx: u32;
-	let edx: u32;
-
-	let esi: u32;
-	let edi: u32;
-	let esp: u32;
-	let ebp: u32;
-
-	let cs: u32;
-	let ds: u32;
-
-	unsafe
-	{
-		eip = INTERRUPT_STATE.eip;
-		eflags = INTERRUPT_STATE.eflags;
-		eax = INTERRUPT_STATE.eax;
-		ebx = INTERRUPT_STATE.ebx;
-		ecx = INTERRUPT_STATE.ecx;
-		edx = INTERRUPT_STATE.edx;
-
-		esi = INTERRUPT_STATE.esi;
-		edi = INTERRUPT_STATE.edi;
-		esp = INTERRUPT_STATE.esp;
-		ebp = INTERRUPT_STATE.ebp;
-
-		cs = INTERRUPT_STATE.cs;
-		ds = INTERRUPT_STATE.ds;
-	}
+	// copied out, packed fields cannot be borrowed by the formatting
+	let state = *INTERRUPT_STATE.lock();
+	let (eip, eflags) = (state.eip, state.eflags);
+	let (eax, ebx, ecx, edx) = (state.eax, state.ebx, state.ecx, state.edx);
+	let (esi, edi, esp, ebp) = (state.esi, state.edi, state.esp, state.ebp);
+	let (cs, ds) = (state.cs, state.ds);
 
 	if !serial_only
 	{
@@ -216,11 +260,24 @@ fn print_panic_state(serial_only: bool)
 	crate::serial_println!("esi: {:08x}   edi: {:08x}   esp: {:08x}   ebp: {:08x}", esi, edi, esp, ebp);
 	crate::serial_println!(" cs:     {:04x}    ds:     {:04x}", cs, ds);
 
+	print_backtrace(eip as usize, ebp as usize, serial_only);
+
 	if !serial_only
 	{
 		crate::vga_print!("\nstack: ");
 	}
 	crate::serial_print!("\nstack: ");
+	// after a stack overflow esp may point to a guard page
+	if !memory::is_mapped(esp as usize) || !memory::is_mapped(esp as usize + 24 * 16)
+	{
+		if !serial_only
+		{
+			crate::vga_print!("not mapped");
+		}
+		crate::serial_print!("not mapped");
+		log!("\n\nALLES KAPUT !!!");
+		return;
+	}
 	for i in 0..24
 	{
 		unsafe
@@ -243,6 +300,39 @@ fn print_panic_state(serial_only: bool)
 	log!("\n\nALLES KAPUT !!!");
 }
 
+fn print_backtrace(eip: usize, ebp: usize, serial_only: bool)
+{
+	let mut print_frame = |frame: debug::backtrace::Frame|
+	{
+		if !serial_only
+		{
+			crate::vga_println!("  {}", frame);
+		}
+		crate::serial_println!("  {}", frame);
+	};
+
+	if !serial_only
+	{
+		crate::vga_println!("\nbacktrace:");
+	}
+	crate::serial_println!("\nbacktrace:");
+	// after an exception, start from the faulting instruction rather than
+	// from the frames of the panic handler
+	if eip != 0
+	{
+		print_frame(debug::backtrace::Frame { address: eip, is_return: false });
+		debug::backtrace::walk(ebp, &mut print_frame);
+	}
+	else
+	{
+		let ebp = unsafe
+		{
+			crate::get_reg!("ebp") as usize
+		};
+		debug::backtrace::walk(ebp, &mut print_frame);
+	}
+}
+
 #[macro_export]
 macro_rules! oops
 {
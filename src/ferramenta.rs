@@ -3,6 +3,7 @@ use crate::syscall;
 
 use alloc::string::String;
 
+use core::fmt;
 use core::slice;
 
 pub fn shutdown_qemu()
@@ -38,38 +39,43 @@ pub unsafe fn from_c_str(ptr: *const u8) -> &'static [u8]
 
 pub unsafe fn print_memory(ptr: *const u8, n: usize)
 {
-	let mut i: usize = 0;
-
 	if !memory::is_range_mapped(ptr, n)
 	{
 		crate::oops!("cannot print unmapped memory from {:#08x} to {:#08x}", ptr as usize, ptr as usize + n);
 		return;
 	}
+	write_memory(&mut crate::serial::Output, ptr, n).unwrap();
+}
+
+// the caller checks that the memory is mapped
+pub unsafe fn write_memory(out: &mut dyn fmt::Write, ptr: *const u8, n: usize) -> fmt::Result
+{
+	let mut i: usize = 0;
+
 	while i < n
 	{
 		if i % 16 == 0
 		{
-			crate::serial_print!("{:08x}: ", ptr.add(i) as u32);
+			write!(out, "{:08x}: ", ptr.add(i) as u32)?;
 		}
-		crate::serial_print!("{:02x?} ", *ptr.add(i));
+		write!(out, "{:02x?} ", *ptr.add(i))?;
 		i += 1;
 		if i % 16 == 0
 		{
-			crate::serial_print!(" |");
+			write!(out, " |")?;
 			for i in i - 16..i
 			{
 				let chr = *ptr.add(i);
-				crate::serial_print!("{}", if chr > 0x1f && chr < 0x7f {chr as char } else { '.' });
+				write!(out, "{}", if chr > 0x1f && chr < 0x7f {chr as char } else { '.' })?;
 			}
-			crate::serial_print!("|");
-			crate::serial_println!();
+			writeln!(out, "|")?;
 		}
 		else if i % 8 == 0
 		{
-			crate::serial_print!("  ");
+			write!(out, "  ")?;
 		}
 	}
-	crate::serial_println!();
+	writeln!(out)
 }
 
 pub unsafe fn print_memory_bin(ptr: *const u8, n: usize)
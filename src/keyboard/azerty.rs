@@ -119,3 +119,22 @@ pub fn char_from_input(keyboard_input: &KeyboardInput) -> Option<char>
 		};
 	}
 }
+
+// the symbols printed on the right of the keys
+pub fn altgr_char(scancode: u8) -> Option<char>
+{
+	match scancode
+	{
+		0x02 => Some('|'),
+		0x03 => Some('@'),
+		0x04 => Some('#'),
+		0x07 => Some('^'),
+		0x0a => Some('{'),
+		0x0b => Some('}'),
+		0x1A => Some('['),
+		0x1B => Some(']'),
+		0x29 => Some('\\'),
+		0x35 => Some('~'),
+		_ => None,
+	}
+}
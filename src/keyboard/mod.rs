@@ -1,15 +1,13 @@
-use crate::arch;
+use core::sync::atomic::{AtomicU8, Ordering};
+use crate::sync::IrqSpinlock;
 use crate::tty;
-
-use core::mem::MaybeUninit;
-use alloc::string::String;
+use crate::tty::command::{self, Arg, Args, Kind};
+use scancode::{Decoder, Key, Set};
 
 mod azerty;
+mod ps2;
 mod qwerty;
-
-const KEYBOARD_DATA: u16 = 0x60;
-const KEYBOARD_READ_STATUS: u32 = 0x64;
-const KEYBOARD_WRITE_COMMAND: u32 = 0x64;
+mod scancode;
 
 pub enum Arrow
 {
@@ -19,69 +17,422 @@ pub enum Arrow
 	Down
 }
 
+// the modifiers and the locks are kept by the driver, the other keys are
+// given to the tty pressed and released
+#[derive(Clone, Copy)]
 pub struct KeyboardInput
 {
 	pub state: KeyboardState,
 	pub scancode: u8,
+	// after 0xE0: the arrows, home, end, delete, right ctrl, the keypad enter...
+	// and the keypad moving without num lock
+	pub extended: bool,
+	pub released: bool
 }
 
+#[derive(Clone, Copy)]
 pub struct KeyboardState
 {
-    pub shift: bool,
-    pub ctrl: bool,
+	pub shift: bool,
+	pub ctrl: bool,
+	pub alt: bool,
+	pub altgr: bool,
+	pub caps_lock: bool,
+	pub num_lock: bool
+}
+
+// the bits of SET_LEDS
+const SCROLL_LOCK: u8 = 1 << 0;
+const NUM_LOCK: u8 = 1 << 1;
+const CAPS_LOCK: u8 = 1 << 2;
+
+struct Modifiers
+{
+	left_shift: bool,
+	right_shift: bool,
+	left_ctrl: bool,
+	right_ctrl: bool,
+	alt: bool,
+	altgr: bool,
+	locks: u8,
+	// the locks toggle once per press, not on the repeats
+	held_locks: u8
+}
+
+impl Modifiers
+{
+	fn state(&self) -> KeyboardState
+	{
+		KeyboardState
+		{
+			shift: self.left_shift || self.right_shift,
+			ctrl: self.left_ctrl || self.right_ctrl,
+			alt: self.alt,
+			altgr: self.altgr,
+			caps_lock: self.locks & CAPS_LOCK != 0,
+			num_lock: self.locks & NUM_LOCK != 0
+		}
+	}
+
+	fn toggle(&mut self, lock: u8, pressed: bool) -> bool
+	{
+		let toggled = pressed && self.held_locks & lock == 0;
+
+		if toggled
+		{
+			self.locks ^= lock;
+		}
+		if pressed
+		{
+			self.held_locks |= lock;
+		}
+		else
+		{
+			self.held_locks &= !lock;
+		}
+		toggled
+	}
+
+	// false for the keys that are not modifiers or locks
+	fn update(&mut self, key: &Key) -> bool
+	{
+		let pressed = !key.released;
+		let mut toggled = false;
+
+		match (key.extended, key.scancode)
+		{
+			(false, 0x2A) => self.left_shift = pressed,
+			(false, 0x36) => self.right_shift = pressed,
+			(false, 0x1D) => self.left_ctrl = pressed,
+			(true, 0x1D) => self.right_ctrl = pressed,
+			(false, 0x38) => self.alt = pressed,
+			(true, 0x38) => self.altgr = pressed,
+			(false, 0x3A) => toggled = self.toggle(CAPS_LOCK, pressed),
+			(false, 0x45) => toggled = self.toggle(NUM_LOCK, pressed),
+			(false, 0x46) => toggled = self.toggle(SCROLL_LOCK, pressed),
+			_ => return false
+		}
+		if toggled
+		{
+			set_leds(self.locks);
+		}
+		true
+	}
+
+	// the releases may have been read by someone else
+	fn release_all(&mut self)
+	{
+		self.left_shift = false;
+		self.right_shift = false;
+		self.left_ctrl = false;
+		self.right_ctrl = false;
+		self.alt = false;
+		self.altgr = false;
+		self.held_locks = 0;
+	}
+}
+
+struct Keyboard
+{
+	decoder: Decoder,
+	modifiers: Modifiers
 }
 
-static mut KEYBOARD_STATE: KeyboardState = KeyboardState
+// the interrupt handler and kdb, which polls, decode with the same state
+static KEYBOARD: IrqSpinlock<Keyboard> = IrqSpinlock::new(Keyboard
 {
-    shift: false,
-    ctrl: false,
-};
+	decoder: Decoder::new(),
+	modifiers: Modifiers
+	{
+		left_shift: false,
+		right_shift: false,
+		left_ctrl: false,
+		right_ctrl: false,
+		alt: false,
+		altgr: false,
+		locks: 0,
+		held_locks: 0
+	}
+});
 
+crate::boot_param!(KEYMAP: &'static str = "fr", "keymap");
+// same as keymap=us
+crate::boot_param!(QWERTY: bool = false, "qwerty");
+// the key repeat, in characters per second and ms before the first repeat
+crate::boot_param!(KBDRATE: usize = 30, "kbdrate");
+crate::boot_param!(KBDDELAY: usize = 250, "kbddelay");
 
-pub static mut BUFFER: MaybeUninit<String> = MaybeUninit::uninit();
+// the last byte sent by SET_TYPEMATIC
+static TYPEMATIC: AtomicU8 = AtomicU8::new(0);
+
+// the value of SETTINGS.layout for a keymap name
+pub fn layout(name: &str) -> Option<u8>
+{
+	match name
+	{
+		"fr" => Some(0),
+		"us" => Some(1),
+		_ => None
+	}
+}
 
-pub fn char_from_input(keyboard_input: &KeyboardInput) -> Option<char>
+pub fn init()
 {
+	let layout = if QWERTY.get() { Some(1) } else { layout(KEYMAP.get()) };
+	if layout.is_none()
+	{
+		crate::log_warn!("keyboard", "unknown keymap {}, using fr", KEYMAP.get());
+	}
 	unsafe
 	{
-		let c = if crate::SETTINGS.layout == 1
+		crate::SETTINGS.layout = layout.unwrap_or(0);
+	}
+
+	// without the translation, the keyboard is told to use set 2
+	if !ps2::is_translated()
+	{
+		KEYBOARD.lock().decoder.set = Set::Two;
+		ps2::send(&[ps2::SCANCODE_SET, 2]);
+	}
+	let typematic = set_typematic(KBDRATE.get(), KBDDELAY.get());
+	set_leds(0);
+	match typematic
+	{
+		Some((rate, delay)) => crate::log_info!("keyboard", "scancode set {}, repeating at {}.{} cps after {} ms",
+												scancode_set(), rate / 10, rate % 10, delay),
+		None => crate::log_warn!("keyboard", "no answer to the typematic command")
+	}
+}
+
+fn scancode_set() -> u8
+{
+	match KEYBOARD.lock().decoder.set
+	{
+		Set::One => 1,
+		Set::Two => 2
+	}
+}
+
+// the rates of the typematic byte in tenths of characters per second,
+// 240 / ((8 + A) * 2^B) with A in the bits 0-2 and B in the bits 3-4
+fn rate_of(code: u8) -> usize
+{
+	2400 / ((8 + (code & 7) as usize) << (code >> 3))
+}
+
+fn delay_of(code: u8) -> usize
+{
+	(code as usize + 1) * 250
+}
+
+// the nearest the keyboard can do, the rate and delay set are returned
+fn set_typematic(rate: usize, delay: usize) -> Option<(usize, usize)>
+{
+	let rate_code = (0..32).min_by_key(|code| rate_of(*code).abs_diff(rate.saturating_mul(10))).unwrap_or(0);
+	let delay_code = ((delay.clamp(250, 1000) + 125) / 250 - 1) as u8;
+	let typematic = delay_code << 5 | rate_code;
+
+	if !ps2::send(&[ps2::SET_TYPEMATIC, typematic])
+	{
+		return None;
+	}
+	TYPEMATIC.store(typematic, Ordering::Relaxed);
+	Some((rate_of(rate_code), delay_of(delay_code)))
+}
+
+fn set_leds(locks: u8)
+{
+	ps2::send(&[ps2::SET_LEDS, locks]);
+}
+
+crate::shell_command!("loadkeys", &[Arg::required("keymap", Kind::Str)], "change the keymap, fr or us", loadkeys);
+
+fn loadkeys(args: &Args)
+{
+	let name = args.str(0).unwrap_or("");
+	let layout = match layout(name)
+	{
+		Some(layout) => layout,
+		None =>
+		{
+			crate::println!("loadkeys: unknown keymap {}", name);
+			command::fail();
+			return;
+		}
+	};
+	unsafe
+	{
+		crate::SETTINGS.layout = layout;
+	}
+}
+
+crate::shell_command!("kbdrate", &[Arg::optional("rate", Kind::Int), Arg::optional("delay", Kind::Int)],
+					"show or set the key repeat rate in cps and its delay in ms", kbdrate);
+
+fn kbdrate(args: &Args)
+{
+	let typematic = TYPEMATIC.load(Ordering::Relaxed);
+	let (rate, delay) = match args.number(0)
+	{
+		Some(rate) =>
+		{
+			let delay = args.number(1).unwrap_or(delay_of(typematic >> 5));
+			match set_typematic(rate, delay)
+			{
+				Some(typematic) => typematic,
+				None =>
+				{
+					crate::println!("kbdrate: the keyboard did not answer");
+					command::fail();
+					return;
+				}
+			}
+		}
+		None => (rate_of(typematic & 0x1f), delay_of(typematic >> 5))
+	};
+	crate::println!("typematic rate is {}.{} cps (delay = {} ms)", rate / 10, rate % 10, delay);
+}
+
+fn char_from_layout(keyboard_input: &KeyboardInput) -> Option<char>
+{
+	unsafe
+	{
+		if crate::SETTINGS.layout == 1
 		{
 			qwerty::char_from_input(keyboard_input)
 		}
 		else
 		{
 			azerty::char_from_input(keyboard_input)
-		};
-		if let Some(key) = c
+		}
+	}
+}
+
+fn altgr_char(scancode: u8) -> Option<char>
+{
+	unsafe
+	{
+		if crate::SETTINGS.layout == 1
+		{
+			qwerty::altgr_char(scancode)
+		}
+		else
 		{
-			let buf = BUFFER.assume_init_mut();
-			buf.push(key);
+			azerty::altgr_char(scancode)
 		}
-		c
 	}
 }
 
-pub fn get_scancode()
+// the keypad with num lock, the same with every layout
+fn keypad_char(scancode: u8) -> Option<char>
 {
-	let scancode = arch::port::inb(KEYBOARD_DATA);
+	match scancode
+	{
+		0x37 => Some('*'),
+		0x4A => Some('-'),
+		0x4E => Some('+'),
+		0x47 => Some('7'),
+		0x48 => Some('8'),
+		0x49 => Some('9'),
+		0x4B => Some('4'),
+		0x4C => Some('5'),
+		0x4D => Some('6'),
+		0x4F => Some('1'),
+		0x50 => Some('2'),
+		0x51 => Some('3'),
+		0x52 => Some('0'),
+		0x53 => Some('.'),
+		_ => None
+	}
+}
 
-	unsafe
+pub fn char_from_scancode(keyboard_input: &KeyboardInput) -> Option<char>
+{
+	if keyboard_input.released
 	{
-		match scancode
+		return None;
+	}
+	if keyboard_input.extended
+	{
+		// the enter and the slash of the keypad
+		return match keyboard_input.scancode
 		{
-			0x2A => KEYBOARD_STATE.shift = true,
-			0xAA => KEYBOARD_STATE.shift = false,
-			0x1D => KEYBOARD_STATE.ctrl = true,
-			0x9D => KEYBOARD_STATE.ctrl = false,
-			_ => {
-				tty::input(&KeyboardInput {
-					state: KeyboardState {
-						shift: KEYBOARD_STATE.shift,
-						ctrl: KEYBOARD_STATE.ctrl
-					},
-					scancode,
-				});
-			}
+			0x1C => Some('\n'),
+			0x35 => Some('/'),
+			_ => None
 		};
 	}
+	if let Some(key) = keypad_char(keyboard_input.scancode)
+	{
+		return Some(key);
+	}
+	if keyboard_input.state.altgr
+	{
+		return altgr_char(keyboard_input.scancode);
+	}
+
+	// caps lock only changes the letters
+	let mut input = *keyboard_input;
+	input.state.shift = false;
+	let is_letter = char_from_layout(&input).is_some_and(|key| key.is_ascii_alphabetic());
+	input.state.shift = keyboard_input.state.shift ^ (keyboard_input.state.caps_lock && is_letter);
+	char_from_layout(&input)
+}
+
+// without num lock the keypad moves the cursor like the grey keys
+fn is_keypad_move(key: &Key) -> bool
+{
+	!key.extended && matches!(key.scancode, 0x47..=0x49 | 0x4B..=0x4D | 0x4F..=0x53)
+}
+
+// None for the prefixes, the modifiers and the locks
+fn decode(byte: u8) -> Option<KeyboardInput>
+{
+	let mut keyboard = KEYBOARD.lock();
+	let key = keyboard.decoder.feed(byte)?;
+
+	if keyboard.modifiers.update(&key)
+	{
+		return None;
+	}
+	let state = keyboard.modifiers.state();
+
+	Some(KeyboardInput
+	{
+		state,
+		scancode: key.scancode,
+		extended: key.extended || (is_keypad_move(&key) && !state.num_lock),
+		released: key.released
+	})
+}
+
+// for the code running with the interrupts disabled, the keys are decoded
+// and the modifiers kept as with the interrupts
+pub fn poll() -> Option<KeyboardInput>
+{
+	decode(ps2::poll()?)
+}
+
+pub fn reset_state()
+{
+	let mut keyboard = KEYBOARD.lock();
+
+	keyboard.decoder.reset();
+	keyboard.modifiers.release_all();
+}
+
+pub fn get_scancode()
+{
+	let byte = match ps2::poll()
+	{
+		Some(byte) => byte,
+		// the answer to a command, already read
+		None => return
+	};
+	crate::random::add_input_randomness(byte);
+
+	if let Some(input) = decode(byte)
+	{
+		tty::input(&input);
+	}
 }
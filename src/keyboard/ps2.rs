@@ -0,0 +1,92 @@
+use crate::arch::port::{inb, outb};
+use crate::sync::IrqSpinlock;
+
+const DATA: u16 = 0x60;
+const STATUS: u16 = 0x64;
+const COMMAND: u16 = 0x64;
+
+// status register
+const OUTPUT_BUFFER_FULL: u8 = 1 << 0;
+const INPUT_BUFFER_FULL: u8 = 1 << 1;
+
+// controller commands and configuration byte
+const READ_CONFIG: u8 = 0x20;
+const TRANSLATION: u8 = 1 << 6;
+
+// keyboard commands
+pub const SET_LEDS: u8 = 0xED;
+pub const SCANCODE_SET: u8 = 0xF0;
+pub const SET_TYPEMATIC: u8 = 0xF3;
+
+const ACK: u8 = 0xFA;
+const RESEND: u8 = 0xFE;
+
+// the loops give up after this many reads of the status, a few ms
+const TIMEOUT: usize = 100_000;
+const RETRIES: usize = 3;
+
+// the answers of the keyboard are polled with the interrupts disabled, the
+// irq that follows finds nothing to read
+static CONTROLLER: IrqSpinlock<()> = IrqSpinlock::new(());
+
+// for the code running with the interrupts disabled
+pub fn poll() -> Option<u8>
+{
+	if inb(STATUS) & OUTPUT_BUFFER_FULL == 0
+	{
+		return None;
+	}
+	Some(inb(DATA))
+}
+
+fn wait_read() -> Option<u8>
+{
+	(0..TIMEOUT).find_map(|_| poll())
+}
+
+fn wait_write() -> bool
+{
+	(0..TIMEOUT).any(|_| inb(STATUS) & INPUT_BUFFER_FULL == 0)
+}
+
+// sent again while the keyboard asks for it
+fn send_byte(byte: u8) -> bool
+{
+	for _ in 0..RETRIES
+	{
+		if !wait_write()
+		{
+			return false;
+		}
+		outb(DATA, byte);
+		match wait_read()
+		{
+			Some(ACK) => return true,
+			Some(RESEND) => continue,
+			_ => return false
+		}
+	}
+	false
+}
+
+// a keyboard command and its arguments, each byte is acknowledged
+pub fn send(bytes: &[u8]) -> bool
+{
+	let _controller = CONTROLLER.lock();
+
+	bytes.iter().all(|byte| send_byte(*byte))
+}
+
+// the controller turns set 2 into set 1 unless told not to, assumed when
+// it does not answer
+pub fn is_translated() -> bool
+{
+	let _controller = CONTROLLER.lock();
+
+	if !wait_write()
+	{
+		return true;
+	}
+	outb(COMMAND, READ_CONFIG);
+	wait_read().is_none_or(|config| config & TRANSLATION != 0)
+}
@@ -119,3 +119,9 @@ pub fn char_from_input(keyboard_input: &KeyboardInput) -> Option<char>
 		};
 	}
 }
+
+// nothing more on a us keyboard
+pub fn altgr_char(_scancode: u8) -> Option<char>
+{
+	None
+}
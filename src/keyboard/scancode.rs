@@ -0,0 +1,135 @@
+// the bytes of the keyboard decoded to the keys of set 1, the extended ones
+// come after 0xE0 in both sets
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Set
+{
+	// translated by the controller
+	One,
+	// as sent by the keyboard, releases after 0xF0
+	Two
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Key
+{
+	// without the release bit
+	pub scancode: u8,
+	pub extended: bool,
+	pub released: bool
+}
+
+// pause has no release, the rest of its sequence is skipped
+const PAUSE: Key = Key { scancode: 0x45, extended: true, released: false };
+
+// the fake shifts sent around the extended keys, for the old keyboards
+const FAKE_LEFT_SHIFT: u8 = 0x2A;
+const FAKE_RIGHT_SHIFT: u8 = 0x36;
+
+// set 2 to set 1, the translation table of the 8042
+const TRANSLATE: [u8; 128] =
+[
+	0xff, 0x43, 0x41, 0x3f, 0x3d, 0x3b, 0x3c, 0x58, 0x64, 0x44, 0x42, 0x40, 0x3e, 0x0f, 0x29, 0x59,
+	0x65, 0x38, 0x2a, 0x70, 0x1d, 0x10, 0x02, 0x5a, 0x66, 0x71, 0x2c, 0x1f, 0x1e, 0x11, 0x03, 0x5b,
+	0x67, 0x2e, 0x2d, 0x20, 0x12, 0x05, 0x04, 0x5c, 0x68, 0x39, 0x2f, 0x21, 0x14, 0x13, 0x06, 0x5d,
+	0x69, 0x31, 0x30, 0x23, 0x22, 0x15, 0x07, 0x5e, 0x6a, 0x72, 0x32, 0x24, 0x16, 0x08, 0x09, 0x5f,
+	0x6b, 0x33, 0x25, 0x17, 0x18, 0x0b, 0x0a, 0x60, 0x6c, 0x34, 0x35, 0x26, 0x27, 0x19, 0x0c, 0x61,
+	0x6d, 0x73, 0x28, 0x74, 0x1a, 0x0d, 0x62, 0x6e, 0x3a, 0x36, 0x1c, 0x1b, 0x75, 0x2b, 0x63, 0x76,
+	0x55, 0x56, 0x77, 0x78, 0x79, 0x7a, 0x0e, 0x7b, 0x7c, 0x4f, 0x7d, 0x4b, 0x47, 0x7e, 0x7f, 0x6f,
+	0x52, 0x53, 0x50, 0x4c, 0x4d, 0x48, 0x01, 0x45, 0x57, 0x4e, 0x51, 0x4a, 0x37, 0x49, 0x46, 0x54
+];
+
+fn translate(byte: u8) -> Option<u8>
+{
+	match byte
+	{
+		0x00..=0x7f if TRANSLATE[byte as usize] != 0xff => Some(TRANSLATE[byte as usize]),
+		// F7 and alt+print screen
+		0x83 => Some(0x41),
+		0x84 => Some(0x54),
+		_ => None
+	}
+}
+
+pub struct Decoder
+{
+	pub set: Set,
+	extended: bool,
+	released: bool,
+	skip: usize
+}
+
+impl Decoder
+{
+	pub const fn new() -> Decoder
+	{
+		Decoder
+		{
+			set: Set::One,
+			extended: false,
+			released: false,
+			skip: 0
+		}
+	}
+
+	// None for the prefixes and the bytes that are not keys
+	pub fn feed(&mut self, byte: u8) -> Option<Key>
+	{
+		if self.skip > 0
+		{
+			self.skip -= 1;
+			return None;
+		}
+		match (self.set, byte)
+		{
+			(_, 0xE0) =>
+			{
+				self.extended = true;
+				return None;
+			}
+			(Set::One, 0xE1) =>
+			{
+				self.skip = 5;
+				return Some(PAUSE);
+			}
+			(Set::Two, 0xE1) =>
+			{
+				self.skip = 7;
+				return Some(PAUSE);
+			}
+			(Set::Two, 0xF0) =>
+			{
+				self.released = true;
+				return None;
+			}
+			// acknowledges, resends, echoes and errors
+			(_, 0x00 | 0xEE | 0xFA | 0xFE | 0xFF) => return None,
+			_ => {}
+		}
+
+		let extended = core::mem::replace(&mut self.extended, false);
+		let (scancode, released) = match self.set
+		{
+			Set::One => (byte & 0x7f, byte & 0x80 != 0),
+			Set::Two =>
+			{
+				let released = core::mem::replace(&mut self.released, false);
+				(translate(byte)?, released)
+			}
+		};
+
+		if extended && (scancode == FAKE_LEFT_SHIFT || scancode == FAKE_RIGHT_SHIFT)
+		{
+			return None;
+		}
+		Some(Key { scancode, extended, released })
+	}
+
+	// after someone else read a part of a sequence
+	pub fn reset(&mut self)
+	{
+		self.extended = false;
+		self.released = false;
+		self.skip = 0;
+	}
+}
@@ -0,0 +1,213 @@
+use core::fmt::{self, Write};
+use crate::cmdline::FromArg;
+use crate::sync::IrqSpinlock;
+use crate::time;
+use crate::tty::command::{self, Arg, Args, Kind};
+
+// the last records are kept in memory, dmesg shows them
+const LOG_RECORDS: usize = 256;
+const MESSAGE_SIZE: usize = 120;
+
+#[macro_export]
+macro_rules! log_error
+{
+	($subsystem:expr, $($arg:tt)*) => ($crate::klog::record($crate::klog::Level::Error, $subsystem, format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! log_warn
+{
+	($subsystem:expr, $($arg:tt)*) => ($crate::klog::record($crate::klog::Level::Warn, $subsystem, format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! log_info
+{
+	($subsystem:expr, $($arg:tt)*) => ($crate::klog::record($crate::klog::Level::Info, $subsystem, format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! log_debug
+{
+	($subsystem:expr, $($arg:tt)*) => ($crate::klog::record($crate::klog::Level::Debug, $subsystem, format_args!($($arg)*)));
+}
+
+// the records above this level are kept but not shown on the console
+crate::boot_param!(LOGLEVEL: Level = Level::Info, "loglevel");
+
+#[derive(Copy, Clone, PartialEq, PartialOrd)]
+pub enum Level
+{
+	Error,
+	Warn,
+	Info,
+	Debug
+}
+
+impl Level
+{
+	pub fn from_name(name: &str) -> Option<Level>
+	{
+		match name
+		{
+			"error" | "0" => Some(Level::Error),
+			"warn" | "1" => Some(Level::Warn),
+			"info" | "2" => Some(Level::Info),
+			"debug" | "3" => Some(Level::Debug),
+			_ => None
+		}
+	}
+
+	fn name(&self) -> &'static str
+	{
+		match self
+		{
+			Level::Error => "error",
+			Level::Warn => "warn",
+			Level::Info => "info",
+			Level::Debug => "debug"
+		}
+	}
+
+	// what the console showed before the levels existed
+	fn prefix(&self) -> &'static str
+	{
+		match self
+		{
+			Level::Error => "[\x1B[31mERROR\x1B[39m] ",
+			Level::Warn => "[\x1B[33mWARN\x1B[39m] ",
+			Level::Info => "[INFO] ",
+			Level::Debug => "[DEBUG] "
+		}
+	}
+}
+
+impl FromArg for Level
+{
+	fn from_arg(value: Option<&'static str>) -> Option<Level>
+	{
+		Level::from_name(value?)
+	}
+
+	fn fmt_arg(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		write!(f, "{}", self.name())
+	}
+}
+
+#[derive(Copy, Clone)]
+struct Record
+{
+	level: Level,
+	subsystem: &'static str,
+	jiffies: u64,
+	len: usize,
+	message: [u8; MESSAGE_SIZE]
+}
+
+impl Record
+{
+	fn message(&self) -> &str
+	{
+		core::str::from_utf8(&self.message[..self.len]).unwrap_or("")
+	}
+}
+
+// long messages are cut, on a character boundary
+impl fmt::Write for Record
+{
+	fn write_str(&mut self, s: &str) -> fmt::Result
+	{
+		for c in s.chars()
+		{
+			if self.len + c.len_utf8() > MESSAGE_SIZE
+			{
+				break;
+			}
+			c.encode_utf8(&mut self.message[self.len..]);
+			self.len += c.len_utf8();
+		}
+		Ok(())
+	}
+}
+
+struct Log
+{
+	records: [Record; LOG_RECORDS],
+	// index of the next record to write, the oldest one once the buffer is full
+	next: usize,
+	count: usize
+}
+
+static LOG: IrqSpinlock<Log> = IrqSpinlock::new(Log
+{
+	records: [Record
+	{
+		level: Level::Info,
+		subsystem: "",
+		jiffies: 0,
+		len: 0,
+		message: [0; MESSAGE_SIZE]
+	}; LOG_RECORDS],
+	next: 0,
+	count: 0
+});
+
+pub fn record(level: Level, subsystem: &'static str, args: fmt::Arguments)
+{
+	let mut log = LOG.lock();
+	let next = log.next;
+	let record = &mut log.records[next];
+
+	record.level = level;
+	record.subsystem = subsystem;
+	record.jiffies = time::jiffies();
+	record.len = 0;
+	let _ = record.write_fmt(args);
+
+	log.next = (next + 1) % LOG_RECORDS;
+	log.count = (log.count + 1).min(LOG_RECORDS);
+	drop(log);
+
+	if level <= LOGLEVEL.get()
+	{
+		crate::logln!("{}{}", level.prefix(), args);
+	}
+}
+
+crate::shell_command!("dmesg", &[Arg::optional("level", Kind::Str)], "show the kernel log, up to error, warn, info or debug", dmesg);
+
+fn dmesg(args: &Args)
+{
+	match args.str(0).map(Level::from_name)
+	{
+		None => print(Level::Debug),
+		Some(Some(level)) => print(level),
+		Some(None) =>
+		{
+			crate::println!("dmesg: invalid level {}", args.str(0).unwrap_or(""));
+			command::fail();
+		}
+	}
+}
+
+// the records are copied one by one, nothing is printed with the lock held
+pub fn print(max_level: Level)
+{
+	let (oldest, count) =
+	{
+		let log = LOG.lock();
+		((log.next + LOG_RECORDS - log.count) % LOG_RECORDS, log.count)
+	};
+
+	for i in 0..count
+	{
+		let record = LOG.lock().records[(oldest + i) % LOG_RECORDS];
+		if record.level <= max_level
+		{
+			let milliseconds = record.jiffies * 1000 / time::HZ as u64;
+			crate::println!("[{:5}.{:03}] {:5} {}: {}", milliseconds / 1000, milliseconds % 1000, record.level.name(),
+							record.subsystem, record.message());
+		}
+	}
+}
@@ -23,13 +23,10 @@ pub fn kmalloc(size: usize) -> *mut c_void
 	}
 
 	let size = ferramenta::align(size, 0x10);
-	let pt_manager: &mut pagetable::Manager = unsafe
-	{
-		&mut PT_MANAGER
-	};
+	let mut pt_manager = PT_MANAGER.lock();
 
 	let start = pt_manager.memory_start + PAGE_SIZE * (pt_manager.page_count / 1024 + 2);
-	let address = next_available_space(start, size, MemorySpace::Kernel);
+	let address = next_available_space(&mut pt_manager, start, size, MemorySpace::Kernel);
 
 	if address == 0
 	{
@@ -74,16 +71,14 @@ pub fn vmalloc(size: usize) -> *mut c_void
 	}
 
 	let size = ferramenta::align(size, 0x10);
-	let pt_manager: &mut pagetable::Manager = unsafe
-	{
-		&mut PT_MANAGER
-	};
+	let mut pt_manager = PT_MANAGER.lock();
 
-	let mut address = next_available_space(pt_manager.heap_start, size, MemorySpace::User);
+	let heap_start = pt_manager.heap_start;
+	let mut address = next_available_space(&mut pt_manager, heap_start, size, MemorySpace::User);
 
 	if address == 0
 	{
-		if let Some(new_address) = expand_heap(200)
+		if let Some(new_address) = expand_heap(&mut pt_manager, 200)
 		{
 			address = new_address as usize;
 		}
@@ -136,12 +131,8 @@ fn create_header(address: usize, size: usize)
 	}
 }
 
-fn next_available_space(address: usize, size: usize, memory_space: MemorySpace) -> usize
+fn next_available_space(pt_manager: &mut pagetable::Manager, address: usize, size: usize, memory_space: MemorySpace) -> usize
 {
-	let pt_manager: &mut pagetable::Manager = unsafe
-	{
-		&mut PT_MANAGER
-	};
 	let total_size = size + core::mem::size_of::<AllocHeader>();
 	let limit = match memory_space
 	{
@@ -156,7 +147,7 @@ fn next_available_space(address: usize, size: usize, memory_space: MemorySpace)
 		{
 			return 0;
 		}
-		else if !expand_heap_if_needed(start, total_size, limit)
+		else if !expand_heap_if_needed(pt_manager, start, total_size, limit)
 		{
 			return 0;
 		}
@@ -167,7 +158,7 @@ fn next_available_space(address: usize, size: usize, memory_space: MemorySpace)
 			{
 				if (*header).freed
 				{
-					if use_freed_block(address + start, size, memory_space)
+					if use_freed_block(pt_manager, address + start, size, memory_space)
 					{
 						return header as usize;
 					}
@@ -182,18 +173,14 @@ fn next_available_space(address: usize, size: usize, memory_space: MemorySpace)
 	}
 }
 
-fn expand_heap_if_needed(start: usize, size: usize, heap_size: usize) -> bool
+fn expand_heap_if_needed(pt_manager: &mut pagetable::Manager, start: usize, size: usize, heap_size: usize) -> bool
 {
 	let mut heap_size = heap_size;
-	let pt_manager: &mut pagetable::Manager = unsafe
-	{
-		&mut PT_MANAGER
-	};
 
 	while start + size > heap_size
 	{
 		let pages = max(size / PAGE_SIZE, 1);
-		if let Some(_) = expand_heap(pages)
+		if expand_heap(pt_manager, pages).is_some()
 		{
 			heap_size = pt_manager.heap_size();
 		}
@@ -205,17 +192,17 @@ fn expand_heap_if_needed(start: usize, size: usize, heap_size: usize) -> bool
 	true
 }
 
-fn use_freed_block(address: usize, size: usize, memory_space: MemorySpace) -> bool
+fn use_freed_block(pt_manager: &pagetable::Manager, address: usize, size: usize, memory_space: MemorySpace) -> bool
 {
 	unsafe
 	{
 		let header: *mut AllocHeader = address as *mut _;
-		merge_next_blocks(get_block_for(&*header), memory_space);
+		merge_next_blocks(pt_manager, get_block_for(&*header), memory_space);
 
 		if (*header).size > size
 		{
 			let new_address = address + core::mem::size_of::<AllocHeader>();
-			let could_break = break_block(new_address as *mut c_void, size, memory_space);
+			let could_break = break_block(pt_manager, new_address as *mut c_void, size, memory_space);
 
 			could_break
 		}
@@ -234,7 +221,9 @@ fn free(address: *mut c_void, memory_space: MemorySpace)
 {
 	if address != core::ptr::null_mut::<c_void>()
 	{
-		if let Some(header) = get_header_for(address, memory_space)
+		let pt_manager = PT_MANAGER.lock();
+
+		if let Some(header) = get_header_for(&pt_manager, address, memory_space)
 		{
 			if header.freed
 			{
@@ -243,7 +232,7 @@ fn free(address: *mut c_void, memory_space: MemorySpace)
 			else
 			{
 				header.freed = true;
-				merge_next_blocks(address, memory_space);
+				merge_next_blocks(&pt_manager, address, memory_space);
 			}
 		}
 	}
@@ -251,7 +240,9 @@ fn free(address: *mut c_void, memory_space: MemorySpace)
 
 fn size_of(address: *mut c_void, memory_space: MemorySpace) -> usize
 {
-	if let Some(header) = get_header_for(address, memory_space)
+	let pt_manager = PT_MANAGER.lock();
+
+	if let Some(header) = get_header_for(&pt_manager, address, memory_space)
 	{
 		if header.freed
 		{
@@ -269,12 +260,8 @@ fn size_of(address: *mut c_void, memory_space: MemorySpace) -> usize
 	}
 }
 
-fn get_header_for(address: *mut c_void, memory_space: MemorySpace) -> Option<&'static mut AllocHeader>
+fn get_header_for(pt_manager: &pagetable::Manager, address: *mut c_void, memory_space: MemorySpace) -> Option<&'static mut AllocHeader>
 {
-	let pt_manager: &mut pagetable::Manager = unsafe
-	{
-		&mut PT_MANAGER
-	};
 	let lowest_address = match memory_space
 	{
 		MemorySpace::Kernel => pt_manager.memory_start + core::mem::size_of::<AllocHeader>(),
@@ -314,14 +301,9 @@ fn get_block_for(header_address: &AllocHeader) -> *mut c_void
 	(header_address as *const _ as usize + core::mem::size_of::<AllocHeader>()) as *mut c_void
 }
 
-fn expand_heap(pages: usize) -> Option<*mut c_void>
+fn expand_heap(pt_manager: &mut pagetable::Manager, pages: usize) -> Option<*mut c_void>
 {
-	let pt_manager = unsafe
-	{
-		&mut PT_MANAGER
-	};
-	let alloc = pageframe::Allocator::shared();
-	let new_page = alloc.request_free_pages(max(pages, 1), MemorySpace::User);
+	let new_page = pageframe::Allocator::shared().request_free_pages(max(pages, 1), MemorySpace::User);
 
 	if new_page != 0
 	{
@@ -338,9 +320,9 @@ fn expand_heap(pages: usize) -> Option<*mut c_void>
 	None
 }
 
-fn break_block(address: *mut c_void, new_size: usize, memory_space: MemorySpace) -> bool
+fn break_block(pt_manager: &pagetable::Manager, address: *mut c_void, new_size: usize, memory_space: MemorySpace) -> bool
 {
-	if let Some(header) = get_header_for(address, memory_space)
+	if let Some(header) = get_header_for(pt_manager, address, memory_space)
 	{
 		let old_size = header.size;
 
@@ -362,9 +344,9 @@ fn break_block(address: *mut c_void, new_size: usize, memory_space: MemorySpace)
 	false
 }
 
-fn merge_next_blocks(address: *mut c_void, memory_space: MemorySpace)
+fn merge_next_blocks(pt_manager: &pagetable::Manager, address: *mut c_void, memory_space: MemorySpace)
 {
-	if let Some(header) = get_header_for(address, memory_space)
+	if let Some(header) = get_header_for(pt_manager, address, memory_space)
 	{
 		loop
 		{
@@ -1,6 +1,10 @@
+use core::ffi::c_void;
+use crate::arch::cpuid::{self, Feature};
+use crate::arch::instructions;
 use crate::ferramenta;
 use crate::libc;
 use crate::multiboot::MultibootTagMmap;
+use crate::sync::{IrqSpinlock, Once};
 use pagetable::flags::*;
 pub use malloc::*;
 
@@ -10,12 +14,19 @@ mod page;
 mod pageframe;
 mod pagetable;
 
+pub use pagetable::flags;
+
 // In pages, * PAGE_SIZE to get memory sizes
 const KERNEL_SPACE_START: usize = 0x0000_0000;
 const KERNEL_SPACE_RANGE: usize = 0x0000_2000;
 
 static PAGE_SIZE: usize = 4096;
-static mut PT_MANAGER: pagetable::Manager = pagetable::Manager::uninitialized();
+
+const CR0_PG: u32 = 1 << 31;
+// "mem=" limits the memory used, the rest is left reserved
+crate::boot_param!(MEM: Option<usize> = None, "mem");
+
+static PT_MANAGER: IrqSpinlock<pagetable::Manager> = IrqSpinlock::new(pagetable::Manager::uninitialized());
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum MemorySpace
@@ -26,49 +37,57 @@ pub enum MemorySpace
 
 pub fn get_mem_size(mmap: *const MultibootTagMmap, mmap_size: usize) -> usize
 {
-	static mut MEM_SIZE_BYTES: u64 = 0;
-	unsafe
+	static MEM_SIZE: Once<usize> = Once::new();
+
+	*MEM_SIZE.call_once(||
 	{
-		if MEM_SIZE_BYTES > 0
+		let mut mem_size_bytes: u64 = 0;
+		unsafe
 		{
-			return MEM_SIZE_BYTES as usize;
+			for mmap_entry in (*mmap).entries(mmap_size)
+			{
+				mem_size_bytes += mmap_entry.len as u64;
+			}
 		}
-		for mmap_entry in (*mmap).entries(mmap_size)
+		if let Some(limit) = MEM.get()
 		{
-			MEM_SIZE_BYTES += mmap_entry.len as u64;
+			mem_size_bytes = mem_size_bytes.min(limit as u64);
 		}
-		let installed = (MEM_SIZE_BYTES / 1024) / 1024;
+		let installed = (mem_size_bytes / 1024) / 1024;
 		let mini = KERNEL_SPACE_RANGE * PAGE_SIZE + 0x100000;
-		if MEM_SIZE_BYTES > usize::MAX as u64
+		if mem_size_bytes > usize::MAX as u64
 		{
 			panic!("This version of elsOS is in 32 bit, it only supports {}MiB of RAM,\nyou have {}MiB installed", (usize::MAX / 1024) / 1024, installed);
 		}
-		else if MEM_SIZE_BYTES < mini as u64
+		else if mem_size_bytes < mini as u64
 		{
 			panic!("This version of elsOS needs at least {}MiB of RAM,\nyou have {}MiB installed", mini / 1024 / 1024, installed);
 		}
-		return MEM_SIZE_BYTES as usize;
-	}
+		mem_size_bytes as usize
+	})
 }
 
 pub fn init(mmap: *const MultibootTagMmap, mmap_size: usize)
 {
-	let alloc: &mut pageframe::Allocator = pageframe::Allocator::shared();
-	alloc.read_grub_mmap(mmap, mmap_size);
-
-	let page_directory_addr = alloc.request_free_page(MemorySpace::Kernel);
+	let (page_directory_addr, page_count) =
+	{
+		let mut alloc = pageframe::Allocator::shared();
+		alloc.read_grub_mmap(mmap, mmap_size);
+		(alloc.request_free_page(MemorySpace::Kernel), alloc.bitmap.size)
+	};
 	let mut pt_manager = pagetable::Manager::new(page_directory_addr, PDE_RW);
 
-	pt_manager.page_count = alloc.bitmap.size;
-	id_map(&mut pt_manager);
-	alloc.print_memusage(1);
+	pt_manager.page_count = page_count;
+	let (large_pages, global_pages) = enable_paging_extensions();
+	id_map(&mut pt_manager, large_pages, global_pages);
+	pageframe::Allocator::shared().print_memusage(1);
 	unsafe
 	{
 		load_page_directory(page_directory_addr as *const page::DirectoryEntry);
 		enable_paging();
 		pt_manager.enable_paging();
 	}
-	let page = alloc.request_free_page(MemorySpace::User);
+	let page = pageframe::Allocator::shared().request_free_page(MemorySpace::User);
 	let virtual_page = PAGE_SIZE * (KERNEL_SPACE_START + KERNEL_SPACE_RANGE);
 	pt_manager.memory_map(virtual_page, page);
 	unsafe
@@ -76,27 +95,46 @@ pub fn init(mmap: *const MultibootTagMmap, mmap_size: usize)
 		libc::memset(virtual_page as *mut _, 0, PAGE_SIZE);
 	}
 
+	*PT_MANAGER.lock() = pt_manager;
 	unsafe
 	{
-		PT_MANAGER = pt_manager;
+		set_guard_page(&stack_guard as *const _ as usize);
 	}
+	crate::log_info!("memory", "kernel space mapped with {} pages{}", if large_pages { "4 MiB" } else { "4 KiB" },
+				  if global_pages { ", global" } else { "" });
 }
 
-fn id_map(pt_manager: &mut pagetable::Manager)
+fn id_map(pt_manager: &mut pagetable::Manager, large_pages: bool, global_pages: bool)
 {
-	let alloc: &mut pageframe::Allocator = pageframe::Allocator::shared();
-	let mut memory_start = alloc.bitmap.buffer as *const _ as *const usize as usize;
-	memory_start += alloc.bitmap.buffer.len();
+	let memory_start =
+	{
+		let alloc = pageframe::Allocator::shared();
+		alloc.bitmap.buffer.as_ptr() as usize + alloc.bitmap.buffer.len()
+	};
 
 	pt_manager.memory_start = ferramenta::align(memory_start, PAGE_SIZE);
-	for i in 0..memory_start / PAGE_SIZE
+	// the kernel space never changes, keep it in the TLB across CR3 reloads
+	let flags = if global_pages { PTE_RW | PTE_GLOBAL } else { PTE_RW };
+	if large_pages
 	{
-		pt_manager.memory_map(i * PAGE_SIZE, i * PAGE_SIZE);
-		alloc.lock_page(i);
+		let start = KERNEL_SPACE_START * PAGE_SIZE;
+		let end = (KERNEL_SPACE_START + KERNEL_SPACE_RANGE) * PAGE_SIZE;
+		for address in (start..end).step_by(pagetable::LARGE_PAGE_SIZE)
+		{
+			pt_manager.identity_map_large(address, flags);
+		}
 	}
-	for i in memory_start / PAGE_SIZE..KERNEL_SPACE_START + KERNEL_SPACE_RANGE
+	else
 	{
-		pt_manager.memory_map(i * PAGE_SIZE, i * PAGE_SIZE);
+		for i in KERNEL_SPACE_START..KERNEL_SPACE_START + KERNEL_SPACE_RANGE
+		{
+			pt_manager.identity_map(i * PAGE_SIZE, PTE_PRESENT | flags);
+		}
+	}
+	let mut alloc = pageframe::Allocator::shared();
+	for i in 0..memory_start / PAGE_SIZE
+	{
+		alloc.lock_page(i);
 	}
 	for i in pt_manager.page_count / 1024 + 2 + memory_start / PAGE_SIZE..KERNEL_SPACE_START + KERNEL_SPACE_RANGE
 	{
@@ -104,10 +142,39 @@ fn id_map(pt_manager: &mut pagetable::Manager)
 	}
 }
 
+// CR4 bits
+const CR4_PSE: u32 = 1 << 4;
+const CR4_PGE: u32 = 1 << 7;
+
+// 4 MiB and global pages have to be enabled before loading a page directory
+// that uses them
+fn enable_paging_extensions() -> (bool, bool)
+{
+	let large_pages = cpuid::has(Feature::Pse);
+	let global_pages = cpuid::has(Feature::Pge);
+	let mut cr4 = instructions::read_cr4();
+
+	if large_pages
+	{
+		cr4 |= CR4_PSE;
+	}
+	if global_pages
+	{
+		cr4 |= CR4_PGE;
+	}
+	unsafe
+	{
+		instructions::write_cr4(cr4);
+	}
+	(large_pages, global_pages)
+}
+
 extern "C"
 {
 	fn load_page_directory(address: *const page::DirectoryEntry);
 	fn enable_paging();
+	// page right below the boot stack
+	static stack_guard: c_void;
 }
 
 pub fn page_map_indexer(v_addr: usize) -> (usize, usize)
@@ -120,16 +187,132 @@ pub fn page_map_indexer(v_addr: usize) -> (usize, usize)
 
 pub fn is_range_mapped(ptr: *const u8, n: usize) -> bool
 {
-	let pt_manager = unsafe
+	let end = match (ptr as usize).checked_add(n)
 	{
-		&PT_MANAGER
+		Some(end) => end,
+		None => return false
 	};
-	if ptr as usize >= pt_manager.last_mapped + PAGE_SIZE || ptr as usize + n > pt_manager.last_mapped + PAGE_SIZE
+	let limit = PT_MANAGER.lock().last_mapped + PAGE_SIZE;
+
+	(ptr as usize) < limit && end <= limit
+}
+
+// the last page directory entry is used to map the page directory itself
+const RECURSIVE_MAPPING_START: usize = 0xffc0_0000;
+
+pub fn identity_map(address: usize, size: usize, flags: usize) -> bool
+{
+	let mut pt_manager = PT_MANAGER.lock();
+	let start = address & !(PAGE_SIZE - 1);
+	let end = match address.checked_add(size).and_then(|end| end.checked_add(PAGE_SIZE - 1))
 	{
-		false
+		Some(end) => end & !(PAGE_SIZE - 1),
+		None => return false
+	};
+
+	if size == 0 || end > RECURSIVE_MAPPING_START
+	{
+		return false;
 	}
-	else
+	for page in (start..end).step_by(PAGE_SIZE)
+	{
+		pt_manager.identity_map(page, PTE_PRESENT | flags);
+	}
+	true
+}
+
+pub fn map_mmio(address: usize, size: usize) -> bool
+{
+	identity_map(address, size, PTE_RW | PTE_PCD | PTE_PWT)
+}
+
+// walk the page tables through the recursive mapping without taking the
+// manager lock, so the double fault handler can use it
+pub fn is_mapped(address: usize) -> bool
+{
+	let (pdi, pti) = page_map_indexer(address);
+
+	// without paging every physical address can be read
+	if instructions::read_cr0() & CR0_PG == 0
+	{
+		return true;
+	}
+	unsafe
+	{
+		let page_directory_entry = &*((RECURSIVE_MAPPING_START + 1023 * PAGE_SIZE + pdi * 4) as *const page::DirectoryEntry);
+		if !page_directory_entry.get_present()
+		{
+			return false;
+		}
+		if page_directory_entry.get_ps()
+		{
+			return true;
+		}
+		let page_table_entry = &*((RECURSIVE_MAPPING_START + pdi * PAGE_SIZE + pti * 4) as *const page::TableEntry);
+		page_table_entry.get_present()
+	}
+}
+
+// raw page directory and page table entries of an address, there is no page
+// table behind an absent or a 4 MiB page directory entry
+pub fn page_table_entries(address: usize) -> Option<(u32, Option<u32>)>
+{
+	let (pdi, pti) = page_map_indexer(address);
+
+	if instructions::read_cr0() & CR0_PG == 0
+	{
+		return None;
+	}
+	unsafe
+	{
+		let page_directory_entry = &*((RECURSIVE_MAPPING_START + 1023 * PAGE_SIZE + pdi * 4) as *const page::DirectoryEntry);
+		if !page_directory_entry.get_present() || page_directory_entry.get_ps()
+		{
+			return Some((page_directory_entry.value, None));
+		}
+		let page_table_entry = &*((RECURSIVE_MAPPING_START + pdi * PAGE_SIZE + pti * 4) as *const page::TableEntry);
+		Some((page_directory_entry.value, Some(page_table_entry.value)))
+	}
+}
+
+// the page frame helpers are used by kdb, they give up with None instead
+// of waiting for the allocator held by a stopped cpu
+
+pub fn page_frame_count() -> Option<usize>
+{
+	Some(pageframe::Allocator::try_shared()?.bitmap.size)
+}
+
+pub fn is_page_frame_used(index: usize) -> Option<bool>
+{
+	let alloc = pageframe::Allocator::try_shared()?;
+	Some(index < alloc.bitmap.size && alloc.bitmap.get(index))
+}
+
+// free, locked and reserved memory in bytes
+pub fn page_frame_usage() -> Option<(usize, usize, usize)>
+{
+	let alloc = pageframe::Allocator::try_shared()?;
+	Some((alloc.free_mem, alloc.locked_mem, alloc.reserved_mem))
+}
+
+pub fn set_guard_page(address: usize)
+{
+	PT_MANAGER.lock().unmap(address & !(PAGE_SIZE - 1));
+}
+
+// kernel stack with an unmapped guard page right below it, an overflow
+// faults instead of corrupting the memory under it. returns the stack top
+pub fn allocate_stack(size: usize) -> Option<usize>
+{
+	let size = ferramenta::align(size, PAGE_SIZE);
+	let block = kmalloc(size + 2 * PAGE_SIZE);
+
+	if block.is_null()
 	{
-		true
+		return None;
 	}
+	let guard = ferramenta::align(block as usize, PAGE_SIZE);
+	set_guard_page(guard);
+	Some(guard + PAGE_SIZE + size)
 }
@@ -54,7 +54,8 @@ impl DirectoryEntry
 	// PS, or 'Page Size' stores t-he page size for that specific entry.
 	// If the bit is set, then the PDE maps to a page that is 4 MiB in size.
 	// Otherwise, it maps to a 4 KiB page table. Please note that 4-MiB pages
-	// require PSE to be enabled. In our case, it will be 0.
+	// require PSE to be enabled. We only use them for the kernel space, when
+	// the CPU supports PSE.
 	pub fn get_ps(&self) -> bool
 	{
 		ferramenta::get_bit_at(self.value, 7)
@@ -3,6 +3,7 @@ use crate::ferramenta;
 use crate::multiboot::MultibootTagMmap;
 use crate::memory::get_mem_size;
 use crate::page_index;
+use crate::sync::{IrqSpinlock, IrqSpinlockGuard};
 use super::{KERNEL_SPACE_START, KERNEL_SPACE_RANGE, PAGE_SIZE, MemorySpace};
 
 extern "C"
@@ -21,23 +22,28 @@ pub struct Allocator
 	pub bitmap: ferramenta::Bitmap,
 }
 
+static ALLOC: IrqSpinlock<Allocator> = IrqSpinlock::new(Allocator
+{
+	free_mem: 0,
+	locked_mem: 0,
+	reserved_mem: 0,
+	unusable_mem: 0,
+	initialized: false,
+	bitmap: ferramenta::Bitmap {buffer: &mut[] as &'static mut[u8], size: 0},
+});
+
 impl Allocator
 {
-	pub fn shared() -> &'static mut Allocator
+	// taken after the page table manager, never held while mapping pages
+	pub fn shared() -> IrqSpinlockGuard<'static, Allocator>
 	{
-		static mut ALLOC: Allocator = Allocator
-		{
-			free_mem: 0,
-			locked_mem: 0,
-			reserved_mem: 0,
-			unusable_mem: 0,
-			initialized: false,
-			bitmap: ferramenta::Bitmap {buffer: &mut[] as &'static mut[u8], size: 0},
-		};
-		unsafe
-		{
-			&mut ALLOC
-		}
+		ALLOC.lock()
+	}
+
+	// for the debugger, which may have stopped the cpu holding it
+	pub fn try_shared() -> Option<IrqSpinlockGuard<'static, Allocator>>
+	{
+		ALLOC.try_lock()
 	}
 
 	pub fn read_grub_mmap(&mut self, mmap: *const MultibootTagMmap, mmap_size: usize)
@@ -51,40 +57,46 @@ impl Allocator
 		}
 		self.initialized = true;
 
-		crate::logln!("[INFO] initializing memory map...");
+		crate::log_info!("memory", "initializing memory map...");
 		unsafe
 		{
 			kernel_start = &_kernel_start as *const _ as usize;
 			kernel_end =  &_kernel_end as *const _ as usize;
 		}
+		// GRUB loaded the symbol table and the modules right after the kernel,
+		// keep them with it
+		let kernel_end = kernel_end.max(crate::debug::symbols::end()).max(crate::multiboot::modules_end());
 		crate::logln!("KERNEL START {:#08x} END {:#08x}", kernel_start, kernel_end);
 
 		self.reserved_mem = crate::memory::get_mem_size(mmap, mmap_size);
-		crate::logln!("[INFO] found {}KiB of memory", self.reserved_mem / 1024);
+		crate::log_info!("memory", "found {}KiB of memory", self.reserved_mem / 1024);
 		// initialise the bitmap according to mem size, and set every page as reserved
 		self.init_bitmap(ferramenta::align(kernel_end, 0x1000));
-		crate::logln!("[INFO] assigned {} pages to bitmap", self.bitmap.size);
-		crate::logln!("[INFO] bitmap end : {:#08x}", &self.bitmap as *const _ as usize + self.bitmap.buffer.len());
+		crate::log_info!("memory", "assigned {} pages to bitmap", self.bitmap.size);
+		crate::log_info!("memory", "bitmap end : {:#08x}", &self.bitmap as *const _ as usize + self.bitmap.buffer.len());
 		unsafe
 		{
 			for entry in (*mmap).entries(mmap_size)
 			{
-				if (entry.tag_type == 1 && entry.addr < get_mem_size(mmap, mmap_size) as u32) && entry.addr != 0
+				let mem_size = get_mem_size(mmap, mmap_size);
+				if (entry.tag_type == 1 && entry.addr < mem_size as u32) && entry.addr != 0
 				{
-					// unreserve grub memmap entries marked as free except lower memory
-					self.unreserve_mem(page_index!(entry.addr as usize), page_index!(entry.len as usize));
+					// unreserve grub memmap entries marked as free except lower memory,
+					// up to the memory size which may be limited by mem=
+					let len = (entry.len as usize).min(mem_size - entry.addr as usize);
+					self.unreserve_mem(page_index!(entry.addr as usize), page_index!(len));
 				}
 			}
 		}
 		// reserve kernel space
 		self.reserve_mem(page_index!(kernel_start), page_index!(kernel_end - kernel_start));
-		crate::logln!("[INFO] reserved {} pages for kernel", page_index!(kernel_end - kernel_start));
-		crate::logln!("[INFO] kernel {:#08x} - {:#08x}", kernel_start, kernel_end);
-		crate::logln!("[INFO] kernel space memory start {:#08x}", (self.bitmap.buffer as *const _ as *const usize as usize) + self.bitmap.buffer.len());
+		crate::log_info!("memory", "reserved {} pages for kernel", page_index!(kernel_end - kernel_start));
+		crate::log_info!("memory", "kernel {:#08x} - {:#08x}", kernel_start, kernel_end);
+		crate::log_info!("memory", "kernel space memory start {:#08x}", (self.bitmap.buffer as *const _ as *const usize as usize) + self.bitmap.buffer.len());
 		// reserve bitmap
-		crate::logln!("[INFO] reserving {} pages for bitmap", page_index!(self.bitmap.size / 8));
+		crate::log_info!("memory", "reserving {} pages for bitmap", page_index!(self.bitmap.size / 8));
 		self.reserve_mem(page_index!(kernel_end),  page_index!(self.bitmap.size / 8));
-		crate::logln!("[INFO] kernel space : {:#08x} - {:#08x}", PAGE_SIZE * KERNEL_SPACE_START, PAGE_SIZE * (KERNEL_SPACE_START + KERNEL_SPACE_RANGE));
+		crate::log_info!("memory", "kernel space : {:#08x} - {:#08x}", PAGE_SIZE * KERNEL_SPACE_START, PAGE_SIZE * (KERNEL_SPACE_START + KERNEL_SPACE_RANGE));
 	}
 
 	pub fn request_free_pages(&mut self, n: usize, memory_space: MemorySpace) -> usize
@@ -154,8 +166,8 @@ impl Allocator
 		}
 		if level >= 2
 		{
-			crate::logln!("[INFO] reserved pages: {} pages", self.reserved_mem / PAGE_SIZE);
-			crate::logln!("[INFO] used pages: {} pages", self.locked_mem / PAGE_SIZE);
+			crate::log_info!("memory", "reserved pages: {} pages", self.reserved_mem / PAGE_SIZE);
+			crate::log_info!("memory", "used pages: {} pages", self.locked_mem / PAGE_SIZE);
 		}
 		if level >= 3
 		{
@@ -229,7 +241,7 @@ impl Allocator
 	fn init_bitmap(&mut self, b: usize)
 	{
 		let bitmap_size = self.reserved_mem / PAGE_SIZE;
-		crate::logln!("[INFO] bitmap location: {:#x}", b);
+		crate::log_info!("memory", "bitmap location: {:#x}", b);
 
 		unsafe
 		{
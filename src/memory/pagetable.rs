@@ -1,8 +1,12 @@
 use core::ffi::c_void;
+use crate::arch::instructions;
 use crate::libc;
 use crate::memory::{page, pageframe, page_map_indexer};
 use crate::memory::PAGE_SIZE;
 use super::MemorySpace;
+use flags::*;
+
+pub const LARGE_PAGE_SIZE: usize = 0x40_0000;
 
 pub mod flags
 {
@@ -92,9 +96,8 @@ impl Manager
 
 		if !page_directory_entry.get_present()
 		{
-			let alloc = pageframe::Allocator::shared();
 			page_directory_entry.reset();
-			page_directory_entry.set_addr(alloc.request_free_page(MemorySpace::Kernel) as u32);
+			page_directory_entry.set_addr(pageframe::Allocator::shared().request_free_page(MemorySpace::Kernel) as u32);
 			page_directory_entry.value |= self.flags as u32 & 0xFFF;
 			page_directory_entry.set_present(true);
 			unsafe
@@ -104,8 +107,13 @@ impl Manager
 		}
 	}
 
-	fn create_page_table_entry(&mut self, page_directory_index: usize, page_table_index: usize, physical_address: usize)
+	fn create_page_table_entry(&mut self, page_directory_index: usize, page_table_index: usize, physical_address: usize, flags: usize)
 	{
+		// already mapped by a 4 MiB page, there is no page table behind it
+		if self.page_directory[page_directory_index].get_ps()
+		{
+			return;
+		}
 		let page_table = unsafe
 		{
 			core::slice::from_raw_parts_mut(self.address(page_directory_index) as *mut page::TableEntry, 1024)
@@ -115,7 +123,7 @@ impl Manager
 		{
 			page_table_entry.reset();
 			page_table_entry.set_addr(physical_address as u32);
-			page_table_entry.value |= self.flags as u32 & 0xFFF;
+			page_table_entry.value |= flags as u32 & 0xFFF;
 			page_table_entry.set_present(true);
 		}
 	}
@@ -124,7 +132,7 @@ impl Manager
 	{
 		let (pdi, pti): (usize, usize) = page_map_indexer(v_addr);
 		self.create_page_directory_entry(pdi);
-		self.create_page_table_entry(pdi, pti, phys_addr);
+		self.create_page_table_entry(pdi, pti, phys_addr, self.flags);
 		if v_addr != phys_addr
 		{
 			if self.heap_start == 0
@@ -136,6 +144,89 @@ impl Manager
 		}
 	}
 
+	// identity map a physical page outside of the kernel space (ACPI tables,
+	// memory mapped registers...), without touching the heap bookkeeping
+	pub fn identity_map(&mut self, phys_addr: usize, flags: usize)
+	{
+		let (pdi, pti): (usize, usize) = page_map_indexer(phys_addr);
+		self.create_page_directory_entry(pdi);
+		self.create_page_table_entry(pdi, pti, phys_addr, flags);
+	}
+
+	// identity map a 4 MiB page straight from the page directory, PSE has to
+	// be enabled in CR4 before loading it
+	pub fn identity_map_large(&mut self, phys_addr: usize, flags: usize)
+	{
+		let (pdi, _): (usize, usize) = page_map_indexer(phys_addr);
+		let page_directory_entry = &mut self.page_directory[pdi];
+
+		if !page_directory_entry.get_present()
+		{
+			page_directory_entry.reset();
+			page_directory_entry.set_addr((phys_addr & !(LARGE_PAGE_SIZE - 1)) as u32);
+			page_directory_entry.value |= flags as u32 & 0xFFF;
+			page_directory_entry.set_ps(true);
+			page_directory_entry.set_present(true);
+		}
+	}
+
+	// unmap a page, used for the guard pages below the kernel stacks. 4 MiB
+	// pages are split in 4 KiB pages first
+	pub fn unmap(&mut self, v_addr: usize)
+	{
+		let (pdi, pti): (usize, usize) = page_map_indexer(v_addr);
+
+		if !self.page_directory[pdi].get_present()
+		{
+			return;
+		}
+		if self.page_directory[pdi].get_ps()
+		{
+			self.split_large_page(pdi);
+		}
+		let page_table = unsafe
+		{
+			core::slice::from_raw_parts_mut(self.address(pdi) as *mut page::TableEntry, 1024)
+		};
+		page_table[pti].reset();
+		if self.paging_enabled
+		{
+			unsafe
+			{
+				instructions::invlpg(v_addr);
+			}
+		}
+	}
+
+	fn split_large_page(&mut self, page_directory_index: usize)
+	{
+		let table_address = pageframe::Allocator::shared().request_free_page(MemorySpace::Kernel);
+		let page_directory_entry = &mut self.page_directory[page_directory_index];
+		let base = page_directory_entry.get_addr() as usize;
+		// the same bits mean the same thing in a 4 MiB PDE and in a PTE
+		let flags = page_directory_entry.value & (PTE_RW | PTE_US | PTE_PWT | PTE_PCD | PTE_GLOBAL) as u32;
+
+		// frames of the kernel space are identity mapped, the table can be
+		// filled before it replaces the 4 MiB page
+		let page_table = unsafe
+		{
+			core::slice::from_raw_parts_mut(table_address as *mut page::TableEntry, 1024)
+		};
+		for (i, page_table_entry) in page_table.iter_mut().enumerate()
+		{
+			page_table_entry.value = (base + i * PAGE_SIZE) as u32 | flags | PTE_PRESENT as u32;
+		}
+		page_directory_entry.value = table_address as u32 | (flags & !(PTE_GLOBAL as u32)) | PDE_PRESENT as u32;
+		if self.paging_enabled
+		{
+			unsafe
+			{
+				instructions::invlpg(base);
+				instructions::invlpg(self.address(page_directory_index) as usize);
+			}
+		}
+	}
+
 	fn address(&self, page_directory_index: usize) -> u32
 	{
 		if self.paging_enabled
@@ -1,9 +1,9 @@
 use core::slice;
 use core::mem::size_of;
+use core::sync::atomic::Ordering;
 use crate::ferramenta;
-use crate::log;
-use crate::logln;
-use crate::ok_fail;
+use crate::sync::Once;
+use crate::{log_debug, log_error, log_info, log_warn};
 
 pub static mut MULTIBOOT_MMAP: *const MultibootTagMmap = core::ptr::null();
 pub static mut MULTIBOOT_MMAP_ENTRIES: usize = 0;
@@ -33,6 +33,16 @@ const MULTIBOOT_TAG_TYPE_EFI32_IH: u32			= 19;
 const MULTIBOOT_TAG_TYPE_EFI64_IH: u32			= 20;
 const MULTIBOOT_TAG_TYPE_LOAD_BASE_ADDR: u32	= 21;
 
+// the multiboot information is not reserved, BootInfo keeps a copy of it
+const CMDLINE_SIZE: usize = 256;
+const NAME_SIZE: usize = 64;
+const MAX_MODULES: usize = 8;
+const MAX_MEMORY_REGIONS: usize = 32;
+
+const FRAMEBUFFER_TYPE_INDEXED: u8 = 0;
+const FRAMEBUFFER_TYPE_RGB: u8 = 1;
+const FRAMEBUFFER_TYPE_EGA_TEXT: u8 = 2;
+
 #[repr(C)]
 struct MultibootInfoHeader
 {
@@ -66,6 +76,7 @@ impl MultibootTagString
 	}
 }
 
+#[repr(C)]
 struct MultibootTagBasicMeminfo
 {
 	tag_type: u32,
@@ -74,6 +85,137 @@ struct MultibootTagBasicMeminfo
 	mem_upper: u32
 }
 
+#[repr(C)]
+struct MultibootTagBootdev
+{
+	tag_type: u32,
+	size: u32,
+	biosdev: u32,
+	slice: u32,
+	part: u32
+}
+
+#[repr(C)]
+struct MultibootTagModule
+{
+	tag_type: u32,
+	size: u32,
+	mod_start: u32,
+	mod_end: u32,
+	cmdline_ptr: &'static [u8]
+}
+
+impl MultibootTagModule
+{
+	fn cmdline(&self) -> &'static [u8]
+	{
+		unsafe
+		{
+			ferramenta::from_c_str((&self.cmdline_ptr as *const _) as *const u8)
+		}
+	}
+}
+
+#[repr(C)]
+struct MultibootTagFramebuffer
+{
+	tag_type: u32,
+	size: u32,
+	framebuffer_addr: u64,
+	framebuffer_pitch: u32,
+	framebuffer_width: u32,
+	framebuffer_height: u32,
+	framebuffer_bpp: u8,
+	framebuffer_type: u8,
+	reserved: u16,
+	// the number of palette colors for the indexed type, the position and
+	// the size of each color for the rgb type
+	color_info: [u8; 6]
+}
+
+#[repr(C)]
+struct MultibootTagVbe
+{
+	tag_type: u32,
+	size: u32,
+	vbe_mode: u16,
+	vbe_interface_seg: u16,
+	vbe_interface_off: u16,
+	vbe_interface_len: u16
+}
+
+#[repr(C)]
+struct MultibootTagApm
+{
+	tag_type: u32,
+	size: u32,
+	version: u16,
+	cseg: u16,
+	offset: u32,
+	cseg_16: u16,
+	dseg: u16,
+	flags: u16,
+	cseg_len: u16,
+	cseg_16_len: u16,
+	dseg_len: u16
+}
+
+#[repr(C)]
+struct MultibootTagSmbios
+{
+	tag_type: u32,
+	size: u32,
+	major: u8,
+	minor: u8,
+	reserved: [u8; 6]
+}
+
+// the tags holding a 32 or 64 bits pointer: EFI system tables and image handles
+#[repr(C)]
+struct MultibootTagPointer32
+{
+	tag_type: u32,
+	size: u32,
+	pointer: u32
+}
+
+#[repr(C)]
+struct MultibootTagPointer64
+{
+	tag_type: u32,
+	size: u32,
+	pointer: u64
+}
+
+#[repr(C)]
+struct MultibootTagEfiMmap
+{
+	tag_type: u32,
+	size: u32,
+	descr_size: u32,
+	descr_vers: u32
+}
+
+#[repr(C)]
+struct MultibootTagLoadBaseAddr
+{
+	tag_type: u32,
+	size: u32,
+	load_base_addr: u32
+}
+
+// the RSDP copied in the ACPI_OLD and ACPI_NEW tags
+#[repr(C)]
+struct MultibootTagRsdp
+{
+	tag_type: u32,
+	size: u32,
+	signature: [u8; 8],
+	checksum: u8,
+	oem_id: [u8; 6],
+	revision: u8
+}
+
 // constants for the type inside MultiBootMmapEntry struct
 const MULTIBOOT_MEMORY_AVAILABLE: u32 = 1;
 const MULTIBOOT_MEMORY_RESERVED: u32 = 2;
@@ -115,16 +257,630 @@ impl MultibootTagMmap
 	}
 }
 
+#[repr(C)]
+pub struct MultibootTagElfSections
+{
+	tag_type: u32,
+	size: u32,
+	num: u32,
+	entsize: u32,
+	shndx: u32,
+	// the headers follow the tag
+	sections: [ElfSectionHeader; 0]
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ElfSectionHeader
+{
+	pub name: u32,
+	pub section_type: u32,
+	pub flags: u32,
+	pub addr: u32,
+	pub offset: u32,
+	pub size: u32,
+	pub link: u32,
+	pub info: u32,
+	pub addralign: u32,
+	pub entsize: u32
+}
+
+impl MultibootTagElfSections
+{
+	pub fn sections(&self) -> &'static [ElfSectionHeader]
+	{
+		if self.entsize as usize != size_of::<ElfSectionHeader>()
+		{
+			return &[];
+		}
+		unsafe
+		{
+			slice::from_raw_parts(self.sections.as_ptr(), self.num as usize)
+		}
+	}
+}
+
+// a string copied from the multiboot information, cut if it is too long
+#[derive(Copy, Clone)]
+pub struct BootString<const N: usize>
+{
+	buffer: [u8; N],
+	len: usize
+}
+
+impl<const N: usize> BootString<N>
+{
+	const fn new() -> BootString<N>
+	{
+		BootString
+		{
+			buffer: [0; N],
+			len: 0
+		}
+	}
+
+	fn from_bytes(bytes: &[u8]) -> BootString<N>
+	{
+		let mut string = BootString::new();
+		let len = bytes.iter().position(|c| *c == b'\0').unwrap_or(bytes.len()).min(N);
+
+		string.buffer[..len].copy_from_slice(&bytes[..len]);
+		string.len = len;
+		string
+	}
+
+	pub fn as_str(&self) -> &str
+	{
+		core::str::from_utf8(&self.buffer[..self.len]).unwrap_or("???")
+	}
+}
+
+#[derive(Copy, Clone)]
+pub struct BasicMeminfo
+{
+	// in KiB, below 1MiB and above 1MiB up to the first hole
+	pub lower: u32,
+	pub upper: u32
+}
+
+#[derive(Copy, Clone)]
+pub struct BootDevice
+{
+	pub bios_device: u32,
+	pub partition: u32,
+	pub sub_partition: u32
+}
+
+#[derive(Copy, Clone)]
+pub struct Module
+{
+	pub start: u32,
+	pub end: u32,
+	cmdline: BootString<NAME_SIZE>
+}
+
+impl Module
+{
+	const EMPTY: Module = Module
+	{
+		start: 0,
+		end: 0,
+		cmdline: BootString::new()
+	};
+
+	pub fn cmdline(&self) -> &str
+	{
+		self.cmdline.as_str()
+	}
+
+	// the first word of the command line, without its directory
+	pub fn name(&self) -> &str
+	{
+		let path = self.cmdline().split(' ').next().unwrap_or("");
+		path.rsplit('/').next().unwrap_or(path)
+	}
+
+	// kept away from the page frame allocator with the kernel
+	pub fn bytes(&self) -> &'static [u8]
+	{
+		unsafe
+		{
+			slice::from_raw_parts(self.start as *const u8, self.end.saturating_sub(self.start) as usize)
+		}
+	}
+}
+
+#[derive(Copy, Clone)]
+pub struct MemoryRegion
+{
+	pub address: u64,
+	pub len: u64,
+	pub region_type: u32
+}
+
+impl MemoryRegion
+{
+	const EMPTY: MemoryRegion = MemoryRegion
+	{
+		address: 0,
+		len: 0,
+		region_type: 0
+	};
+
+	pub fn type_name(&self) -> &'static str
+	{
+		match self.region_type
+		{
+			MULTIBOOT_MEMORY_AVAILABLE => "available",
+			MULTIBOOT_MEMORY_RESERVED => "reserved",
+			MULTIBOOT_MEMORY_ACPI_RECLAIMABLE => "ACPI reclaimable",
+			MULTIBOOT_MEMORY_NVS => "ACPI NVS",
+			MULTIBOOT_MEMORY_BADRAM => "bad",
+			_ => "unknown"
+		}
+	}
+}
+
+#[derive(Copy, Clone)]
+pub struct ColorField
+{
+	pub position: u8,
+	pub size: u8
+}
+
+#[derive(Copy, Clone)]
+pub enum FramebufferType
+{
+	Indexed
+	{
+		colors: u16
+	},
+	Rgb
+	{
+		red: ColorField,
+		green: ColorField,
+		blue: ColorField
+	},
+	EgaText
+}
+
+#[derive(Copy, Clone)]
+pub struct Framebuffer
+{
+	pub address: u64,
+	pub pitch: u32,
+	pub width: u32,
+	pub height: u32,
+	pub bpp: u8,
+	pub framebuffer_type: FramebufferType
+}
+
+#[derive(Copy, Clone)]
+pub struct Vbe
+{
+	pub mode: u16,
+	pub interface_segment: u16,
+	pub interface_offset: u16,
+	pub interface_len: u16
+}
+
+#[derive(Copy, Clone)]
+pub struct ElfSections
+{
+	pub count: u32,
+	pub entry_size: u32,
+	pub string_table_index: u32
+}
+
+#[derive(Copy, Clone)]
+pub struct Apm
+{
+	pub version: u16,
+	pub code_segment: u16,
+	pub offset: u32,
+	pub code_segment_16: u16,
+	pub data_segment: u16,
+	pub flags: u16,
+	pub code_segment_len: u16,
+	pub code_segment_16_len: u16,
+	pub data_segment_len: u16
+}
+
+#[derive(Copy, Clone)]
+pub struct Smbios
+{
+	pub major: u8,
+	pub minor: u8,
+	pub tables_len: usize
+}
+
+#[derive(Copy, Clone)]
+pub struct Rsdp
+{
+	pub revision: u8,
+	oem_id: [u8; 6]
+}
+
+impl Rsdp
+{
+	pub fn oem_id(&self) -> &str
+	{
+		core::str::from_utf8(&self.oem_id).unwrap_or("??????")
+	}
+}
+
+#[derive(Copy, Clone)]
+pub struct EfiMmap
+{
+	pub descriptor_size: u32,
+	pub descriptor_version: u32,
+	pub descriptors: usize
+}
+
+// everything given by the bootloader, each tag that was not found is None
+pub struct BootInfo
+{
+	cmdline: BootString<CMDLINE_SIZE>,
+	bootloader: BootString<NAME_SIZE>,
+	pub basic_meminfo: Option<BasicMeminfo>,
+	pub boot_device: Option<BootDevice>,
+	modules: [Module; MAX_MODULES],
+	module_count: usize,
+	memory_map: [MemoryRegion; MAX_MEMORY_REGIONS],
+	memory_region_count: usize,
+	pub framebuffer: Option<Framebuffer>,
+	pub vbe: Option<Vbe>,
+	pub elf_sections: Option<ElfSections>,
+	pub apm: Option<Apm>,
+	pub smbios: Option<Smbios>,
+	pub acpi_old: Option<Rsdp>,
+	pub acpi_new: Option<Rsdp>,
+	pub efi32_system_table: Option<u32>,
+	pub efi64_system_table: Option<u64>,
+	pub efi32_image_handle: Option<u32>,
+	pub efi64_image_handle: Option<u64>,
+	pub efi_mmap: Option<EfiMmap>,
+	// the EFI boot services were not terminated by the bootloader
+	pub efi_boot_services: bool,
+	// size of the DHCP ACK packet
+	pub network: Option<usize>,
+	pub load_base_address: Option<u32>
+}
+
+impl BootInfo
+{
+	const fn new() -> BootInfo
+	{
+		BootInfo
+		{
+			cmdline: BootString::new(),
+			bootloader: BootString::new(),
+			basic_meminfo: None,
+			boot_device: None,
+			modules: [Module::EMPTY; MAX_MODULES],
+			module_count: 0,
+			memory_map: [MemoryRegion::EMPTY; MAX_MEMORY_REGIONS],
+			memory_region_count: 0,
+			framebuffer: None,
+			vbe: None,
+			elf_sections: None,
+			apm: None,
+			smbios: None,
+			acpi_old: None,
+			acpi_new: None,
+			efi32_system_table: None,
+			efi64_system_table: None,
+			efi32_image_handle: None,
+			efi64_image_handle: None,
+			efi_mmap: None,
+			efi_boot_services: false,
+			network: None,
+			load_base_address: None
+		}
+	}
+
+	pub fn cmdline(&self) -> &str
+	{
+		self.cmdline.as_str()
+	}
+
+	pub fn bootloader(&self) -> &str
+	{
+		self.bootloader.as_str()
+	}
+
+	pub fn modules(&self) -> &[Module]
+	{
+		&self.modules[..self.module_count]
+	}
+
+	pub fn memory_map(&self) -> &[MemoryRegion]
+	{
+		&self.memory_map[..self.memory_region_count]
+	}
+
+	unsafe fn read_tag(&mut self, tag: *const MultibootTag)
+	{
+		let payload_size = ((*tag).size as usize).saturating_sub(size_of::<MultibootTag>());
+
+		match (*tag).tag_type
+		{
+			MULTIBOOT_TAG_TYPE_CMDLINE => self.cmdline = BootString::from_bytes((*(tag as *const MultibootTagString)).string()),
+			MULTIBOOT_TAG_TYPE_BOOT_LOADER_NAME => self.bootloader = BootString::from_bytes((*(tag as *const MultibootTagString)).string()),
+			MULTIBOOT_TAG_TYPE_MODULE =>
+			{
+				let tag = tag as *const MultibootTagModule;
+				if self.module_count < MAX_MODULES
+				{
+					self.modules[self.module_count] = Module
+					{
+						start: (*tag).mod_start,
+						end: (*tag).mod_end,
+						cmdline: BootString::from_bytes((*tag).cmdline())
+					};
+					self.module_count += 1;
+				}
+			}
+			MULTIBOOT_TAG_TYPE_BASIC_MEMINFO =>
+			{
+				let tag = tag as *const MultibootTagBasicMeminfo;
+				self.basic_meminfo = Some(BasicMeminfo { lower: (*tag).mem_lower, upper: (*tag).mem_upper });
+			}
+			MULTIBOOT_TAG_TYPE_BOOTDEV =>
+			{
+				let tag = tag as *const MultibootTagBootdev;
+				self.boot_device = Some(BootDevice
+				{
+					bios_device: (*tag).biosdev,
+					partition: (*tag).slice,
+					sub_partition: (*tag).part
+				});
+			}
+			MULTIBOOT_TAG_TYPE_MMAP =>
+			{
+				let tag = tag as *const MultibootTagMmap;
+				if (*tag).entry_size as usize >= size_of::<MultibootMmapEntry>()
+				{
+					let count = (((*tag).size as usize).saturating_sub(16) / (*tag).entry_size as usize).min(MAX_MEMORY_REGIONS);
+					for i in 0..count
+					{
+						let entry = &*((&(*tag).entries_ptr as *const _ as usize + i * (*tag).entry_size as usize) as *const MultibootMmapEntry);
+						self.memory_map[i] = MemoryRegion
+						{
+							address: (entry.addr_upper as u64) << 32 | entry.addr as u64,
+							len: (entry.len_upper as u64) << 32 | entry.len as u64,
+							region_type: entry.tag_type
+						};
+					}
+					self.memory_region_count = count;
+				}
+			}
+			MULTIBOOT_TAG_TYPE_VBE =>
+			{
+				let tag = tag as *const MultibootTagVbe;
+				self.vbe = Some(Vbe
+				{
+					mode: (*tag).vbe_mode,
+					interface_segment: (*tag).vbe_interface_seg,
+					interface_offset: (*tag).vbe_interface_off,
+					interface_len: (*tag).vbe_interface_len
+				});
+			}
+			MULTIBOOT_TAG_TYPE_FRAMEBUFFER =>
+			{
+				let tag = tag as *const MultibootTagFramebuffer;
+				let color_info = (*tag).color_info;
+				let framebuffer_type = match (*tag).framebuffer_type
+				{
+					FRAMEBUFFER_TYPE_INDEXED => FramebufferType::Indexed { colors: u16::from_le_bytes([color_info[0], color_info[1]]) },
+					FRAMEBUFFER_TYPE_RGB => FramebufferType::Rgb
+					{
+						red: ColorField { position: color_info[0], size: color_info[1] },
+						green: ColorField { position: color_info[2], size: color_info[3] },
+						blue: ColorField { position: color_info[4], size: color_info[5] }
+					},
+					FRAMEBUFFER_TYPE_EGA_TEXT => FramebufferType::EgaText,
+					_ => return
+				};
+				self.framebuffer = Some(Framebuffer
+				{
+					address: (*tag).framebuffer_addr,
+					pitch: (*tag).framebuffer_pitch,
+					width: (*tag).framebuffer_width,
+					height: (*tag).framebuffer_height,
+					bpp: (*tag).framebuffer_bpp,
+					framebuffer_type
+				});
+			}
+			MULTIBOOT_TAG_TYPE_ELF_SECTIONS =>
+			{
+				let tag = tag as *const MultibootTagElfSections;
+				self.elf_sections = Some(ElfSections
+				{
+					count: (*tag).num,
+					entry_size: (*tag).entsize,
+					string_table_index: (*tag).shndx
+				});
+			}
+			MULTIBOOT_TAG_TYPE_APM =>
+			{
+				let tag = tag as *const MultibootTagApm;
+				self.apm = Some(Apm
+				{
+					version: (*tag).version,
+					code_segment: (*tag).cseg,
+					offset: (*tag).offset,
+					code_segment_16: (*tag).cseg_16,
+					data_segment: (*tag).dseg,
+					flags: (*tag).flags,
+					code_segment_len: (*tag).cseg_len,
+					code_segment_16_len: (*tag).cseg_16_len,
+					data_segment_len: (*tag).dseg_len
+				});
+			}
+			MULTIBOOT_TAG_TYPE_EFI32 => self.efi32_system_table = Some((*(tag as *const MultibootTagPointer32)).pointer),
+			MULTIBOOT_TAG_TYPE_EFI64 => self.efi64_system_table = Some((*(tag as *const MultibootTagPointer64)).pointer),
+			MULTIBOOT_TAG_TYPE_EFI32_IH => self.efi32_image_handle = Some((*(tag as *const MultibootTagPointer32)).pointer),
+			MULTIBOOT_TAG_TYPE_EFI64_IH => self.efi64_image_handle = Some((*(tag as *const MultibootTagPointer64)).pointer),
+			MULTIBOOT_TAG_TYPE_SMBIOS =>
+			{
+				let tag = tag as *const MultibootTagSmbios;
+				self.smbios = Some(Smbios
+				{
+					major: (*tag).major,
+					minor: (*tag).minor,
+					tables_len: payload_size.saturating_sub(8)
+				});
+			}
+			MULTIBOOT_TAG_TYPE_ACPI_OLD | MULTIBOOT_TAG_TYPE_ACPI_NEW =>
+			{
+				let rsdp = tag as *const MultibootTagRsdp;
+				let rsdp = Some(Rsdp { revision: (*rsdp).revision, oem_id: (*rsdp).oem_id });
+				if (*tag).tag_type == MULTIBOOT_TAG_TYPE_ACPI_NEW
+				{
+					self.acpi_new = rsdp;
+				}
+				else
+				{
+					self.acpi_old = rsdp;
+				}
+			}
+			MULTIBOOT_TAG_TYPE_NETWORK => self.network = Some(payload_size),
+			MULTIBOOT_TAG_TYPE_EFI_MMAP =>
+			{
+				let tag = tag as *const MultibootTagEfiMmap;
+				let descriptor_size = (*tag).descr_size;
+				self.efi_mmap = Some(EfiMmap
+				{
+					descriptor_size,
+					descriptor_version: (*tag).descr_vers,
+					descriptors: if descriptor_size == 0 { 0 } else { payload_size.saturating_sub(8) / descriptor_size as usize }
+				});
+			}
+			MULTIBOOT_TAG_TYPE_EFI_BS => self.efi_boot_services = true,
+			MULTIBOOT_TAG_TYPE_LOAD_BASE_ADDR => self.load_base_address = Some((*(tag as *const MultibootTagLoadBaseAddr)).load_base_addr),
+			_ => {}
+		}
+	}
+}
+
+static BOOT_INFO: Once<BootInfo> = Once::new();
+
+// empty until the multiboot information is parsed
+pub fn boot_info() -> &'static BootInfo
+{
+	static EMPTY: BootInfo = BootInfo::new();
+
+	BOOT_INFO.get().unwrap_or(&EMPTY)
+}
+
+pub fn find_module(name: &str) -> Option<&'static Module>
+{
+	boot_info().modules().iter().find(|module| module.name() == name)
+}
+
+// end of the memory used by the modules, it must not be given away
+pub fn modules_end() -> usize
+{
+	boot_info().modules().iter().map(|module| module.end as usize).max().unwrap_or(0)
+}
+
+crate::shell_command!("bootinfo", &[], "show the information given by the bootloader", |_| print_boot_info());
+
+pub fn print_boot_info()
+{
+	let info = boot_info();
+
+	crate::println!("cmdline:      {}", info.cmdline());
+	crate::println!("bootloader:   {}", info.bootloader());
+	if let Some(meminfo) = info.basic_meminfo
+	{
+		crate::println!("memory:       {}KiB lower, {}KiB upper", meminfo.lower, meminfo.upper);
+	}
+	if let Some(device) = info.boot_device
+	{
+		crate::println!("boot device:  {:#x}, partition {:#x}, sub partition {:#x}", device.bios_device, device.partition, device.sub_partition);
+	}
+	if let Some(address) = info.load_base_address
+	{
+		crate::println!("load base:    {:#010x}", address);
+	}
+	for module in info.modules()
+	{
+		crate::println!("module:       {:#010x} - {:#010x} {}", module.start, module.end, module.cmdline());
+	}
+	crate::println!("memory map:   {} regions", info.memory_map().len());
+	for region in info.memory_map()
+	{
+		crate::println!("  {:#018x} - {:#018x} {}", region.address, region.address + region.len, region.type_name());
+	}
+	if let Some(framebuffer) = info.framebuffer
+	{
+		crate::print!("framebuffer:  {}x{}x{} at {:#x}, pitch {}, ", framebuffer.width, framebuffer.height, framebuffer.bpp,
+					  framebuffer.address, framebuffer.pitch);
+		match framebuffer.framebuffer_type
+		{
+			FramebufferType::Indexed { colors } => crate::println!("indexed, {} colors", colors),
+			FramebufferType::Rgb { red, green, blue } => crate::println!("rgb, red {}:{} green {}:{} blue {}:{}",
+																		  red.position, red.size, green.position, green.size, blue.position, blue.size),
+			FramebufferType::EgaText => crate::println!("EGA text")
+		}
+	}
+	if let Some(vbe) = info.vbe
+	{
+		crate::println!("vbe:          mode {:#x}, interface {:#06x}:{:#06x} ({} bytes)", vbe.mode, vbe.interface_segment,
+						vbe.interface_offset, vbe.interface_len);
+	}
+	if let Some(sections) = info.elf_sections
+	{
+		crate::println!("elf sections: {}, {} bytes each, names in {}", sections.count, sections.entry_size, sections.string_table_index);
+	}
+	if let Some(apm) = info.apm
+	{
+		crate::println!("apm:          version {:#x}, entry {:#06x}:{:#010x}, flags {:#x}", apm.version, apm.code_segment, apm.offset, apm.flags);
+	}
+	if let Some(smbios) = info.smbios
+	{
+		crate::println!("smbios:       version {}.{}, {} bytes of tables", smbios.major, smbios.minor, smbios.tables_len);
+	}
+	for rsdp in [info.acpi_old, info.acpi_new].iter().flatten()
+	{
+		crate::println!("acpi:         RSDP revision {}, OEM {}", rsdp.revision, rsdp.oem_id());
+	}
+	if let Some(table) = info.efi32_system_table
+	{
+		crate::println!("efi32:        system table {:#010x}, image handle {:#010x}", table, info.efi32_image_handle.unwrap_or(0));
+	}
+	if let Some(table) = info.efi64_system_table
+	{
+		crate::println!("efi64:        system table {:#018x}, image handle {:#018x}", table, info.efi64_image_handle.unwrap_or(0));
+	}
+	if let Some(mmap) = info.efi_mmap
+	{
+		crate::println!("efi mmap:     {} descriptors of {} bytes, version {}", mmap.descriptors, mmap.descriptor_size, mmap.descriptor_version);
+	}
+	if info.efi_boot_services
+	{
+		crate::println!("efi:          boot services not terminated");
+	}
+	if let Some(len) = info.network
+	{
+		crate::println!("network:      {} bytes of DHCP ACK", len);
+	}
+}
+
 pub fn check_magic(magic: u32) -> bool
 {
 	let magic_ok = magic == BOOTLOADER_MAGIC;
 
-	log!("[{}] multiboot2 magic number", ok_fail(magic_ok));
-	if !magic_ok
+	if magic_ok
+	{
+		log_info!("multiboot", "valid multiboot2 magic number");
+	}
+	else
 	{
-		log!(": expected {:#0x}, got {:#0x}.", BOOTLOADER_MAGIC, magic);
+		log_error!("multiboot", "bad multiboot2 magic number: expected {:#0x}, got {:#0x}", BOOTLOADER_MAGIC, magic);
 	}
-	logln!();
 
 	magic_ok
 }
@@ -159,85 +915,48 @@ fn type_name(tag_type: u32) -> &'static str
 	}
 }
 
-fn handle_qwerty()
+pub fn parse(address: u32) -> bool
 {
-	unsafe
-	{
-		crate::SETTINGS.layout = 1;
-	}
-}
+	let alignment_ok = address & 7 == 0;
 
-fn handle_serial()
-{
-	unsafe
+	if alignment_ok
 	{
-		crate::SETTINGS.has_serial = true;
+		log_info!("multiboot", "multiboot2 structure address aligned");
 	}
-}
-
-fn parse_args(args: &[u8])
-{
-	let mut previous_index: usize = 0;
-	for (i, chr) in args.iter().enumerate()
+	else
 	{
-		let i = if i + 1 == args.len() { i + 1 } else { i };
-
-		if *chr == b' ' || i == args.len()
-		{
-			let arg = core::str::from_utf8(&args[previous_index..i]).unwrap_or_else(|_| { "" });
-
-			logln!("[INFO] parsing arg {}", arg);
-
-			match arg
-			{
-				"qwerty" => handle_qwerty(),
-				"serial" => handle_serial(),
-				_ => {}
-			};
-
-			previous_index = i + 1;
-		}
+		log_error!("multiboot", "multiboot2 structure address {:#x} is not aligned", address);
 	}
-}
-
-pub fn parse(address: u32) -> bool
-{
-	let alignment_ok = address & 7 == 0;
-
-	logln!("[{}] multiboot2 structure address alignment", ok_fail(alignment_ok));
 
 	unsafe
 	{
 		let info_header = address as *const MultibootInfoHeader;
-		logln!("[INFO] multiboot2 information structure total size: {}", (*info_header).total_size);
+		log_info!("multiboot", "multiboot2 information structure total size: {}", (*info_header).total_size);
 
+		let mut info = BootInfo::new();
 		let mut address = address + 8;
 		loop
 		{
 			let tag = address as *const MultibootTag;
 
-			//print!("{:#0x}: ", address);
+			log_debug!("multiboot", "found tag of type {} and size {}", type_name((*tag).tag_type), (*tag).size);
+			info.read_tag(tag);
 			match (*tag).tag_type
 			{
 				MULTIBOOT_TAG_TYPE_CMDLINE | MULTIBOOT_TAG_TYPE_BOOT_LOADER_NAME => {
 					let tag = tag as *const MultibootTagString;
-					let mut i = 0;
-
 					let string = (*tag).string();
-					log!("[INFO] {}: ", if (*tag).tag_type == MULTIBOOT_TAG_TYPE_CMDLINE { "cmd line" } else { "bootloader" });
-					while string[i] != b'\0'
-					{
-						log!("{}", string[i] as char);
-						i += 1;
-					}
-					logln!();
+					let i = string.iter().position(|c| *c == b'\0').unwrap_or(string.len());
+
+					log_info!("multiboot", "{}: {}", if (*tag).tag_type == MULTIBOOT_TAG_TYPE_CMDLINE { "cmd line" } else { "bootloader" },
+							  core::str::from_utf8(&string[..i]).unwrap_or("???"));
 					if (*tag).tag_type == MULTIBOOT_TAG_TYPE_CMDLINE
 					{
-						parse_args(&string[..i]);
+						crate::cmdline::parse(&string[..i]);
 					}
 				},
 				MULTIBOOT_TAG_TYPE_END => {
-					logln!("[INFO] end of multiboot2 information structure");
+					log_debug!("multiboot", "end of multiboot2 information structure");
 					break
 				},
 				MULTIBOOT_TAG_TYPE_MMAP =>
@@ -245,11 +964,30 @@ pub fn parse(address: u32) -> bool
 					MULTIBOOT_MMAP = tag as *const MultibootTagMmap;
 					MULTIBOOT_MMAP_ENTRIES = (*tag).size as usize / size_of::<MultibootMmapEntry>();
 				}
-				_ => {}//crate::println!("found tag of type {} and size {}", type_name((*tag).tag_type), (*tag).size)
+				MULTIBOOT_TAG_TYPE_ELF_SECTIONS =>
+				{
+					let tag = tag as *const MultibootTagElfSections;
+					if crate::debug::symbols::init(&*tag)
+					{
+						log_info!("multiboot", "loaded the kernel symbols");
+					}
+					else
+					{
+						log_warn!("multiboot", "no kernel symbols, the backtraces will not be symbolized");
+					}
+				}
+				// the new RSDP is preferred if both tags are present
+				MULTIBOOT_TAG_TYPE_ACPI_OLD | MULTIBOOT_TAG_TYPE_ACPI_NEW
+					if (*tag).tag_type == MULTIBOOT_TAG_TYPE_ACPI_NEW || crate::acpi::RSDP_ADDRESS.load(Ordering::Relaxed) == 0 =>
+				{
+					crate::acpi::RSDP_ADDRESS.store(address as usize + size_of::<MultibootTag>(), Ordering::Relaxed);
+				}
+				_ => {}
 			};
 
 			address += ferramenta::align((*tag).size as usize, 8) as u32;
 		}
+		BOOT_INFO.call_once(|| info);
 	}
 
 	true
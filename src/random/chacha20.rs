@@ -0,0 +1,55 @@
+// ChaCha20 as described in RFC 8439, with a 32 bit block counter and a
+// 96 bit nonce
+
+// "expand 32-byte k"
+const CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+pub const KEY_WORDS: usize = 8;
+pub const NONCE_WORDS: usize = 3;
+pub const BLOCK_WORDS: usize = 16;
+
+fn quarter_round(state: &mut [u32; BLOCK_WORDS], a: usize, b: usize, c: usize, d: usize)
+{
+	state[a] = state[a].wrapping_add(state[b]);
+	state[d] = (state[d] ^ state[a]).rotate_left(16);
+	state[c] = state[c].wrapping_add(state[d]);
+	state[b] = (state[b] ^ state[c]).rotate_left(12);
+	state[a] = state[a].wrapping_add(state[b]);
+	state[d] = (state[d] ^ state[a]).rotate_left(8);
+	state[c] = state[c].wrapping_add(state[d]);
+	state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+// the 20 rounds without the final addition, also used to stir the entropy pool
+pub fn permute(state: &mut [u32; BLOCK_WORDS])
+{
+	for _ in 0..10
+	{
+		quarter_round(state, 0, 4, 8, 12);
+		quarter_round(state, 1, 5, 9, 13);
+		quarter_round(state, 2, 6, 10, 14);
+		quarter_round(state, 3, 7, 11, 15);
+		quarter_round(state, 0, 5, 10, 15);
+		quarter_round(state, 1, 6, 11, 12);
+		quarter_round(state, 2, 7, 8, 13);
+		quarter_round(state, 3, 4, 9, 14);
+	}
+}
+
+pub fn block(key: &[u32; KEY_WORDS], counter: u32, nonce: &[u32; NONCE_WORDS]) -> [u32; BLOCK_WORDS]
+{
+	let mut state = [0; BLOCK_WORDS];
+
+	state[..4].copy_from_slice(&CONSTANTS);
+	state[4..12].copy_from_slice(key);
+	state[12] = counter;
+	state[13..].copy_from_slice(nonce);
+
+	let mut output = state;
+	permute(&mut output);
+	for (word, initial) in output.iter_mut().zip(state.iter())
+	{
+		*word = word.wrapping_add(*initial);
+	}
+	output
+}
@@ -0,0 +1,250 @@
+use crate::arch::rand::{rdrand, rdseed, rdtsc};
+use crate::ok_fail;
+use crate::sync::IrqSpinlock;
+use chacha20::{BLOCK_WORDS, KEY_WORDS, NONCE_WORDS};
+
+mod chacha20;
+
+// words mixed in the pool before stirring it, the other half is never exposed
+const POOL_RATE: usize = 8;
+const POOL_MAX_ENTROPY: u32 = 512;
+
+// reseed the generator after this much output, if the pool has enough entropy
+const RESEED_BYTES: usize = 1024 * 1024;
+const RESEED_ENTROPY: u32 = 256;
+
+// every interrupt is mixed in, but only credited as 1 bit every 64 of them
+const INTERRUPTS_PER_BIT: u32 = 64;
+const JITTER_SAMPLES: u32 = 2048;
+const JITTER_SAMPLES_PER_BIT: u32 = 8;
+const HARDWARE_WORDS: usize = 8;
+
+struct Pool
+{
+	state: [u32; BLOCK_WORDS],
+	position: usize,
+	entropy: u32,
+	interrupts: u32
+}
+
+impl Pool
+{
+	fn mix(&mut self, value: u32, bits: u32)
+	{
+		self.state[self.position] ^= value;
+		self.position += 1;
+		if self.position == POOL_RATE
+		{
+			chacha20::permute(&mut self.state);
+			self.position = 0;
+		}
+		self.entropy = (self.entropy + bits).min(POOL_MAX_ENTROPY);
+	}
+
+	fn extract(&mut self) -> [u32; KEY_WORDS]
+	{
+		let mut seed = [0; KEY_WORDS];
+
+		chacha20::permute(&mut self.state);
+		seed.copy_from_slice(&self.state[..KEY_WORDS]);
+		// forget what was handed out so the seed cannot be recovered later
+		self.state[..KEY_WORDS].fill(0);
+		chacha20::permute(&mut self.state);
+		self.position = 0;
+		self.entropy = 0;
+		seed
+	}
+}
+
+struct Crng
+{
+	key: [u32; KEY_WORDS],
+	nonce: [u32; NONCE_WORDS],
+	counter: u32,
+	generated: usize,
+	seeded: bool
+}
+
+impl Crng
+{
+	fn reseed(&mut self, seed: &[u32; KEY_WORDS])
+	{
+		for (word, seed) in self.key.iter_mut().zip(seed.iter())
+		{
+			*word ^= *seed;
+		}
+		self.nonce[0] = self.nonce[0].wrapping_add(1);
+		self.counter = 0;
+		self.generated = 0;
+		self.seeded = true;
+	}
+
+	fn next_block(&mut self) -> [u32; BLOCK_WORDS]
+	{
+		let block = chacha20::block(&self.key, self.counter, &self.nonce);
+
+		self.counter = self.counter.wrapping_add(1);
+		if self.counter == 0
+		{
+			self.nonce[1] = self.nonce[1].wrapping_add(1);
+		}
+		block
+	}
+
+	fn fill(&mut self, buffer: &mut [u8])
+	{
+		for chunk in buffer.chunks_mut(BLOCK_WORDS * 4)
+		{
+			let block = self.next_block();
+			for (bytes, word) in chunk.chunks_mut(4).zip(block.iter())
+			{
+				bytes.copy_from_slice(&word.to_le_bytes()[..bytes.len()]);
+			}
+		}
+		// fast key erasure: the key that produced this output is gone
+		let block = self.next_block();
+		self.key.copy_from_slice(&block[..KEY_WORDS]);
+		self.generated += buffer.len();
+	}
+}
+
+struct Random
+{
+	pool: Pool,
+	crng: Crng
+}
+
+impl Random
+{
+	fn add_hardware_randomness(&mut self)
+	{
+		for _ in 0..HARDWARE_WORDS
+		{
+			// RDSEED is a true entropy source, RDRAND is only mixed in
+			if let Some(value) = rdseed()
+			{
+				self.pool.mix(value, 32);
+			}
+			if let Some(value) = rdrand()
+			{
+				self.pool.mix(value, 0);
+			}
+		}
+	}
+
+	fn reseed_if_needed(&mut self)
+	{
+		if !self.crng.seeded || (self.crng.generated >= RESEED_BYTES && self.pool.entropy >= RESEED_ENTROPY)
+		{
+			self.add_hardware_randomness();
+			let seed = self.pool.extract();
+			self.crng.reseed(&seed);
+		}
+	}
+}
+
+static RANDOM: IrqSpinlock<Random> = IrqSpinlock::new(Random
+{
+	pool: Pool
+	{
+		state: [0; BLOCK_WORDS],
+		position: 0,
+		entropy: 0,
+		interrupts: 0
+	},
+	crng: Crng
+	{
+		key: [0; KEY_WORDS],
+		nonce: [0; NONCE_WORDS],
+		counter: 0,
+		generated: 0,
+		seeded: false
+	}
+});
+
+// the time a short loop takes varies with the cache, bus and timer states
+fn collect_jitter(pool: &mut Pool)
+{
+	let mut dummy: u32 = 0;
+
+	for i in 0..JITTER_SAMPLES
+	{
+		let start = rdtsc();
+		for j in 0..(start as u32 & 0xf) + 1
+		{
+			dummy = dummy.wrapping_mul(31).wrapping_add(j);
+			unsafe
+			{
+				core::ptr::write_volatile(&mut dummy, dummy);
+			}
+		}
+		let delta = rdtsc().wrapping_sub(start);
+		let bits = if (i + 1) % JITTER_SAMPLES_PER_BIT == 0 { 1 } else { 0 };
+		pool.mix(delta as u32 ^ (start as u32).rotate_left(16), bits);
+	}
+}
+
+pub fn init()
+{
+	let entropy =
+	{
+		let mut random = RANDOM.lock();
+
+		collect_jitter(&mut random.pool);
+		random.add_hardware_randomness();
+		let entropy = random.pool.entropy;
+		let seed = random.pool.extract();
+		random.crng.reseed(&seed);
+		entropy
+	};
+	crate::logln!("[{}] seeded the random generator with {} bits of entropy", ok_fail(entropy >= RESEED_ENTROPY / 2), entropy);
+}
+
+pub fn add_interrupt_randomness(irq: u8)
+{
+	let mut random = RANDOM.lock();
+
+	random.pool.interrupts += 1;
+	let bits = if random.pool.interrupts.is_multiple_of(INTERRUPTS_PER_BIT) { 1 } else { 0 };
+	random.pool.mix(rdtsc() as u32 ^ (irq as u32) << 24, bits);
+}
+
+// the exact time a key is pressed is hard to guess
+pub fn add_input_randomness(scancode: u8)
+{
+	RANDOM.lock().pool.mix(rdtsc() as u32 ^ (scancode as u32) << 24, 1);
+}
+
+pub fn get_random_bytes(buffer: &mut [u8])
+{
+	let mut random = RANDOM.lock();
+
+	random.reseed_if_needed();
+	random.crng.fill(buffer);
+}
+
+pub fn get_random_u32() -> u32
+{
+	let mut bytes = [0; 4];
+
+	get_random_bytes(&mut bytes);
+	u32::from_le_bytes(bytes)
+}
+
+pub fn entropy_available() -> u32
+{
+	RANDOM.lock().pool.entropy
+}
+
+crate::shell_command!("rand", &[], "print a random number", |_| rand());
+crate::shell_command!("entropy", &[], "show the entropy available", |_| entropy());
+
+fn rand()
+{
+	crate::logln!("{}", get_random_u32());
+}
+
+fn entropy()
+{
+	crate::println!("{} bits of entropy available", entropy_available());
+}
@@ -0,0 +1,191 @@
+use core::fmt;
+use crate::cmdline::FromArg;
+use crate::sync::IrqSpinlock;
+
+mod uart;
+
+pub use uart::*;
+
+#[macro_export]
+macro_rules! serial_print
+{
+	($($arg:tt)*) => ($crate::serial::_print(format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! serial_println
+{
+	() => ($crate::serial_print!("\n"));
+	($($arg:tt)*) => ($crate::serial_print!("{}\n", format_args!($($arg)*)));
+}
+
+crate::boot_param!(CONSOLE: Console = Console::Vga, "console");
+// the kernel log is also written to COM1
+crate::boot_param!(SERIAL: bool = false, "serial");
+
+// "tty0" for the screen, or "ttyS<n>[,<config>]" for a serial port, the
+// configuration is then the one of ttyS<n>= otherwise
+#[derive(Copy, Clone)]
+pub enum Console
+{
+	Vga,
+	Serial
+	{
+		index: usize,
+		config: Option<Config>
+	}
+}
+
+impl FromArg for Console
+{
+	fn from_arg(value: Option<&'static str>) -> Option<Console>
+	{
+		let value = value?;
+		if value == "tty0"
+		{
+			return Some(Console::Vga);
+		}
+		let (device, config) = match value.split_once(',')
+		{
+			Some((device, config)) => (device, Some(Config::from_arg(Some(config))?)),
+			None => (value, None)
+		};
+		let index = device.strip_prefix("ttyS")?.parse::<usize>().ok()?;
+		if index >= PORTS.len()
+		{
+			return None;
+		}
+		Some(Console::Serial { index, config })
+	}
+
+	fn fmt_arg(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match self
+		{
+			Console::Vga => write!(f, "tty0"),
+			Console::Serial { index, config: Some(config) } =>
+			{
+				write!(f, "ttyS{},", index)?;
+				config.fmt_arg(f)
+			}
+			Console::Serial { index, config: None } => write!(f, "ttyS{}", index)
+		}
+	}
+}
+
+// the configuration given with console=ttyS<index>,<config>
+fn console_config(index: usize) -> Option<Config>
+{
+	match CONSOLE.get()
+	{
+		Console::Serial { index: console, config } if console == index => config,
+		_ => None
+	}
+}
+
+pub struct Writer
+{
+	index: usize,
+	pos_x: usize
+}
+
+impl Writer // base stuff
+{
+	pub fn write_byte(&mut self, byte: u8)
+	{
+		match byte
+		{
+			b'\n' =>
+			{
+				self.new_line();
+			},
+			_ =>
+			{
+				send(self.index, &[byte]);
+				self.pos_x += 1;
+			},
+		}
+	}
+
+	pub fn write_string(&mut self, s: &str)
+	{
+		for byte in s.bytes()
+		{
+			match byte
+			{
+				0x00..0xfd => self.write_byte(byte),
+				_ => self.write_byte(0xfe),
+			}
+		}
+	}
+
+	fn new_line(&mut self)
+	{
+		send(self.index, b"\n");
+		self.pos_x = 0;
+	}
+}
+
+impl fmt::Write for Writer
+{
+	fn write_str(&mut self, s: &str) -> fmt::Result
+	{
+		self.write_string(s);
+		Ok(())
+	}
+}
+
+// the port the kernel log is written to, and read from by kdb
+pub fn console_port() -> u16
+{
+	PORTS[W.lock().index]
+}
+
+// "console=ttyS<n>" or "serial" send the kernel log to a serial port
+pub fn init_console() -> bool
+{
+	let index = match CONSOLE.get()
+	{
+		Console::Serial { index, .. } => index,
+		Console::Vga if SERIAL.get() => 0,
+		Console::Vga => return false
+	};
+	if !open(index)
+	{
+		return false;
+	}
+	W.lock().index = index;
+	true
+}
+
+pub static W: IrqSpinlock<Writer> = IrqSpinlock::new(Writer
+{
+	index: 0,
+	pos_x: 0
+});
+
+// serial_print! as a fmt::Write, for the code writing to a given output
+pub struct Output;
+
+impl fmt::Write for Output
+{
+	fn write_str(&mut self, s: &str) -> fmt::Result
+	{
+		_print(format_args!("{}", s));
+		Ok(())
+	}
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments)
+{
+	unsafe
+	{
+		if !crate::SETTINGS.has_serial
+		{
+			return;
+		}
+	}
+	use core::fmt::Write;
+	W.lock().write_fmt(args).unwrap();
+}
@@ -0,0 +1,490 @@
+use core::fmt;
+use crate::arch;
+use crate::arch::port::{inb, outb};
+use crate::cmdline::FromArg;
+use crate::sync::IrqSpinlock;
+
+pub const COM1: u16 = 0x3f8;
+pub const COM2: u16 = 0x2f8;
+pub const COM3: u16 = 0x3e8;
+pub const COM4: u16 = 0x2e8;
+
+// ttyS0 to ttyS3
+pub const PORTS: [u16; 4] = [COM1, COM2, COM3, COM4];
+
+// the clock of the UART, divided to get the baud rate
+pub const UART_CLOCK: u32 = 115200;
+pub const DEFAULT_BAUD: u32 = 38400;
+
+// registers, as offsets from the base port
+const DATA: u16 = 0;
+const INTERRUPT_ENABLE: u16 = 1;
+const INTERRUPT_ID: u16 = 2; // FIFO control when written
+const LINE_CONTROL: u16 = 3;
+const MODEM_CONTROL: u16 = 4;
+const LINE_STATUS: u16 = 5;
+const MODEM_STATUS: u16 = 6;
+
+const IER_RECEIVE: u8 = 0x01;
+const IER_TRANSMIT: u8 = 0x02;
+
+const IIR_NO_INTERRUPT: u8 = 0x01;
+const IIR_ID_MASK: u8 = 0x0e;
+const IIR_TRANSMIT_EMPTY: u8 = 0x02;
+const IIR_RECEIVE: u8 = 0x04;
+const IIR_LINE_STATUS: u8 = 0x06;
+const IIR_RECEIVE_TIMEOUT: u8 = 0x0c;
+const IIR_FIFO_ENABLED: u8 = 0xc0;
+
+const LCR_DLAB: u8 = 0x80;
+const LSR_DATA_READY: u8 = 0x01;
+const LSR_TRANSMIT_EMPTY: u8 = 0x20;
+
+// the transmit FIFO of a 16550A, a single byte without it
+const FIFO_SIZE: usize = 16;
+
+const TX_SIZE: usize = 1024;
+const RX_SIZE: usize = 256;
+
+crate::boot_param!(TTYS0: Config = Config::default(), "ttyS0");
+crate::boot_param!(TTYS1: Config = Config::default(), "ttyS1");
+crate::boot_param!(TTYS2: Config = Config::default(), "ttyS2");
+crate::boot_param!(TTYS3: Config = Config::default(), "ttyS3");
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Parity
+{
+	None,
+	Odd,
+	Even,
+	Mark,
+	Space
+}
+
+// "<baud>[<parity>[<bits>[<stop>]]][,<fifo trigger>]", as in "9600n8" or "115200e71,4"
+#[derive(Copy, Clone)]
+pub struct Config
+{
+	pub baud: u32,
+	pub data_bits: u8,
+	pub parity: Parity,
+	pub stop_bits: u8,
+	pub fifo_trigger: u8
+}
+
+impl Config
+{
+	pub const fn default() -> Config
+	{
+		Config
+		{
+			baud: DEFAULT_BAUD,
+			data_bits: 8,
+			parity: Parity::None,
+			stop_bits: 1,
+			fifo_trigger: 14
+		}
+	}
+
+	fn line_control(&self) -> u8
+	{
+		let parity = match self.parity
+		{
+			Parity::None => 0x00,
+			Parity::Odd => 0x08,
+			Parity::Even => 0x18,
+			Parity::Mark => 0x28,
+			Parity::Space => 0x38
+		};
+		let stop_bits = if self.stop_bits == 2 { 0x04 } else { 0x00 };
+
+		(self.data_bits - 5) | stop_bits | parity
+	}
+
+	// enable the FIFOs and clear them
+	fn fifo_control(&self) -> u8
+	{
+		let trigger = match self.fifo_trigger
+		{
+			1 => 0x00,
+			4 => 0x40,
+			8 => 0x80,
+			_ => 0xc0
+		};
+		trigger | 0x07
+	}
+}
+
+impl FromArg for Config
+{
+	fn from_arg(value: Option<&'static str>) -> Option<Config>
+	{
+		let (mode, fifo_trigger) = match value?.split_once(',')
+		{
+			Some((mode, fifo)) => (mode, fifo.parse::<u8>().ok()?),
+			None => (value?, Config::default().fifo_trigger)
+		};
+		let digits = mode.find(|c: char| !c.is_ascii_digit()).unwrap_or(mode.len());
+		let baud = mode[..digits].parse::<u32>().ok()?;
+		let mut rest = mode[digits..].bytes();
+		let mut config = Config { baud, fifo_trigger, ..Config::default() };
+
+		if let Some(parity) = rest.next()
+		{
+			config.parity = match parity
+			{
+				b'n' => Parity::None,
+				b'o' => Parity::Odd,
+				b'e' => Parity::Even,
+				b'm' => Parity::Mark,
+				b's' => Parity::Space,
+				_ => return None
+			};
+		}
+		if let Some(data_bits) = rest.next()
+		{
+			config.data_bits = data_bits.wrapping_sub(b'0');
+		}
+		if let Some(stop_bits) = rest.next()
+		{
+			config.stop_bits = stop_bits.wrapping_sub(b'0');
+		}
+		let valid = rest.next().is_none()
+					&& baud != 0 && baud <= UART_CLOCK && UART_CLOCK.is_multiple_of(baud)
+					&& (5..=8).contains(&config.data_bits)
+					&& (1..=2).contains(&config.stop_bits)
+					&& matches!(fifo_trigger, 1 | 4 | 8 | 14);
+		if valid { Some(config) } else { None }
+	}
+
+	fn fmt_arg(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		let parity = match self.parity
+		{
+			Parity::None => 'n',
+			Parity::Odd => 'o',
+			Parity::Even => 'e',
+			Parity::Mark => 'm',
+			Parity::Space => 's'
+		};
+		write!(f, "{}{}{}{},{}", self.baud, parity, self.data_bits, self.stop_bits, self.fifo_trigger)
+	}
+}
+
+// the configuration of ttyS<index> given on the command line
+pub fn config(index: usize) -> Config
+{
+	if let Some(config) = super::console_config(index)
+	{
+		return config;
+	}
+	match index
+	{
+		0 => TTYS0.get(),
+		1 => TTYS1.get(),
+		2 => TTYS2.get(),
+		_ => TTYS3.get()
+	}
+}
+
+fn check_serial_chip(port: u16) -> bool
+{
+	outb(port, 0xAE);
+	inb(port) == 0xAE
+}
+
+pub fn init(port: u16, config: &Config) -> bool
+{
+	let divisor = UART_CLOCK / config.baud;
+
+	outb(port + INTERRUPT_ENABLE, 0x00); // Disable all interrupts
+	outb(port + LINE_CONTROL, LCR_DLAB); // Enable DLAB (set baud rate divisor)
+	outb(port + DATA, divisor as u8); // Set divisor (lo byte) (115 200 / divisor => baud)
+	outb(port + INTERRUPT_ENABLE, (divisor >> 8) as u8); //        (hi byte)
+	outb(port + LINE_CONTROL, config.line_control()); // data bits, parity, stop bits
+	outb(port + INTERRUPT_ID, config.fifo_control()); // Enable FIFO, clear them, with the trigger level
+	outb(port + MODEM_CONTROL, 0x0B); // IRQs enabled, RTS/DSR set
+	outb(port + MODEM_CONTROL, 0x1E); // Set in loopback mode, test the serial chip
+
+	if !check_serial_chip(port)
+	{
+		return false;
+	}
+
+	// serial is not faulty, set to normal operation mode
+	outb(port + MODEM_CONTROL, 0x0F);
+	true
+}
+
+fn is_transmit_empty(port: u16) -> bool
+{
+	inb(port + LINE_STATUS) & LSR_TRANSMIT_EMPTY != 0
+}
+
+// polled, for the code that owns the port (gdb) or runs without interrupts
+pub fn write(a: u8, port: u16)
+{
+	while !is_transmit_empty(port) {}
+	outb(port, a);
+}
+
+fn is_data_ready(port: u16) -> bool
+{
+	inb(port + LINE_STATUS) & LSR_DATA_READY != 0
+}
+
+pub fn read(port: u16) -> u8
+{
+	while !is_data_ready(port) {}
+	inb(port)
+}
+
+pub fn try_read(port: u16) -> Option<u8>
+{
+	if is_data_ready(port)
+	{
+		Some(inb(port))
+	}
+	else
+	{
+		None
+	}
+}
+
+pub fn enable_receive_interrupt(port: u16)
+{
+	outb(port + INTERRUPT_ENABLE, IER_RECEIVE);
+}
+
+struct RingBuffer<const N: usize>
+{
+	bytes: [u8; N],
+	start: usize,
+	len: usize
+}
+
+impl<const N: usize> RingBuffer<N>
+{
+	const fn new() -> RingBuffer<N>
+	{
+		RingBuffer
+		{
+			bytes: [0; N],
+			start: 0,
+			len: 0
+		}
+	}
+
+	fn is_full(&self) -> bool
+	{
+		self.len == N
+	}
+
+	fn is_empty(&self) -> bool
+	{
+		self.len == 0
+	}
+
+	// the new bytes are dropped while the buffer is full
+	fn push(&mut self, byte: u8)
+	{
+		if !self.is_full()
+		{
+			self.bytes[(self.start + self.len) % N] = byte;
+			self.len += 1;
+		}
+	}
+
+	fn pop(&mut self) -> Option<u8>
+	{
+		if self.is_empty()
+		{
+			return None;
+		}
+		let byte = self.bytes[self.start];
+		self.start = (self.start + 1) % N;
+		self.len -= 1;
+		Some(byte)
+	}
+}
+
+// ttyS<n>, sending from a buffer refilled by the transmit interrupt and
+// receiving into another one
+struct Uart
+{
+	port: u16,
+	open: bool,
+	fifo: bool,
+	interrupts: u8,
+	tx: RingBuffer<TX_SIZE>,
+	rx: RingBuffer<RX_SIZE>
+}
+
+impl Uart
+{
+	const fn new(port: u16) -> Uart
+	{
+		Uart
+		{
+			port,
+			open: false,
+			fifo: false,
+			interrupts: 0,
+			tx: RingBuffer::new(),
+			rx: RingBuffer::new()
+		}
+	}
+
+	fn set_interrupts(&mut self, interrupts: u8)
+	{
+		if self.interrupts != interrupts
+		{
+			self.interrupts = interrupts;
+			outb(self.port + INTERRUPT_ENABLE, interrupts);
+		}
+	}
+
+	// fill the transmit FIFO once it is empty
+	fn transmit(&mut self)
+	{
+		if is_transmit_empty(self.port)
+		{
+			let size = if self.fifo { FIFO_SIZE } else { 1 };
+			for _ in 0..size
+			{
+				match self.tx.pop()
+				{
+					Some(byte) => outb(self.port + DATA, byte),
+					None => break
+				}
+			}
+		}
+		let interrupts = if self.tx.is_empty() { IER_RECEIVE } else { IER_RECEIVE | IER_TRANSMIT };
+		self.set_interrupts(interrupts);
+	}
+
+	fn receive(&mut self)
+	{
+		while let Some(byte) = try_read(self.port)
+		{
+			self.rx.push(byte);
+		}
+	}
+
+	// without the interrupts, nothing would send the rest of the buffer
+	fn flush(&mut self)
+	{
+		while !self.tx.is_empty()
+		{
+			while !is_transmit_empty(self.port) {}
+			self.transmit();
+		}
+	}
+
+	fn interrupt(&mut self)
+	{
+		loop
+		{
+			let id = inb(self.port + INTERRUPT_ID);
+			if id & IIR_NO_INTERRUPT != 0
+			{
+				break;
+			}
+			match id & IIR_ID_MASK
+			{
+				IIR_RECEIVE | IIR_RECEIVE_TIMEOUT => self.receive(),
+				IIR_TRANSMIT_EMPTY => self.transmit(),
+				IIR_LINE_STATUS => { inb(self.port + LINE_STATUS); },
+				_ => { inb(self.port + MODEM_STATUS); }
+			}
+		}
+	}
+}
+
+static UARTS: [IrqSpinlock<Uart>; 4] =
+[
+	IrqSpinlock::new(Uart::new(COM1)),
+	IrqSpinlock::new(Uart::new(COM2)),
+	IrqSpinlock::new(Uart::new(COM3)),
+	IrqSpinlock::new(Uart::new(COM4))
+];
+
+// program ttyS<index> with its configuration, once
+pub fn open(index: usize) -> bool
+{
+	let mut uart = UARTS[index].lock();
+
+	if uart.open
+	{
+		return true;
+	}
+	if !init(uart.port, &config(index))
+	{
+		return false;
+	}
+	uart.fifo = inb(uart.port + INTERRUPT_ID) & IIR_FIFO_ENABLED == IIR_FIFO_ENABLED;
+	uart.open = true;
+	uart.set_interrupts(IER_RECEIVE);
+	true
+}
+
+// the bytes are queued, and only sent right away when the interrupts are
+// disabled or the buffer is full
+pub fn send(index: usize, bytes: &[u8])
+{
+	let interrupts = arch::interrupts::are_enabled();
+	let mut uart = UARTS[index].lock();
+
+	if !uart.open
+	{
+		return;
+	}
+	for byte in bytes
+	{
+		if uart.tx.is_full()
+		{
+			while !is_transmit_empty(uart.port) {}
+			uart.transmit();
+		}
+		uart.tx.push(*byte);
+	}
+	uart.transmit();
+	if !interrupts
+	{
+		uart.flush();
+	}
+}
+
+pub fn receive(index: usize) -> Option<u8>
+{
+	UARTS[index].lock().rx.pop()
+}
+
+pub fn flush(index: usize)
+{
+	UARTS[index].lock().flush();
+}
+
+// COM1 and COM3 share IRQ4, COM2 and COM4 IRQ3
+pub fn interrupt(irq: u8)
+{
+	for (index, uart) in UARTS.iter().enumerate()
+	{
+		let uart_irq = if index % 2 == 0 { 4 } else { 3 };
+		let mut uart = uart.lock();
+
+		if uart_irq == irq && uart.open
+		{
+			uart.interrupt();
+		}
+	}
+}
+
+// only for the panic path, where the owner will never release the lock
+pub unsafe fn force_unlock()
+{
+	for uart in &UARTS
+	{
+		uart.force_unlock();
+	}
+}
@@ -0,0 +1,122 @@
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use crate::arch::{instructions, interrupts};
+use super::spinlock::{Spinlock, SpinlockGuard};
+
+// spinlock that also disables the interrupts of the cpu while held, so the
+// data can be shared with interrupt handlers without deadlocking against them
+pub struct IrqSpinlock<T>
+{
+	lock: Spinlock<T>
+}
+
+pub struct IrqSpinlockGuard<'a, T>
+{
+	guard: ManuallyDrop<SpinlockGuard<'a, T>>,
+	interrupts: bool
+}
+
+impl<T> IrqSpinlock<T>
+{
+	pub const fn new(data: T) -> IrqSpinlock<T>
+	{
+		IrqSpinlock
+		{
+			lock: Spinlock::new(data)
+		}
+	}
+
+	pub fn lock(&self) -> IrqSpinlockGuard<'_, T>
+	{
+		let interrupts = save_and_disable();
+
+		IrqSpinlockGuard
+		{
+			guard: ManuallyDrop::new(self.lock.lock()),
+			interrupts
+		}
+	}
+
+	pub fn try_lock(&self) -> Option<IrqSpinlockGuard<'_, T>>
+	{
+		let interrupts = save_and_disable();
+
+		match self.lock.try_lock()
+		{
+			Some(guard) => Some(IrqSpinlockGuard
+			{
+				guard: ManuallyDrop::new(guard),
+				interrupts
+			}),
+			None =>
+			{
+				restore(interrupts);
+				None
+			}
+		}
+	}
+
+	pub fn is_locked(&self) -> bool
+	{
+		self.lock.is_locked()
+	}
+
+	// only for the panic path, where the owner will never release the lock
+	pub unsafe fn force_unlock(&self)
+	{
+		self.lock.force_unlock();
+	}
+}
+
+fn save_and_disable() -> bool
+{
+	let interrupts = interrupts::are_enabled();
+
+	unsafe
+	{
+		instructions::cli();
+	}
+	interrupts
+}
+
+fn restore(interrupts: bool)
+{
+	if interrupts
+	{
+		unsafe
+		{
+			instructions::sti();
+		}
+	}
+}
+
+impl<T> Deref for IrqSpinlockGuard<'_, T>
+{
+	type Target = T;
+
+	fn deref(&self) -> &T
+	{
+		&self.guard
+	}
+}
+
+impl<T> DerefMut for IrqSpinlockGuard<'_, T>
+{
+	fn deref_mut(&mut self) -> &mut T
+	{
+		&mut self.guard
+	}
+}
+
+impl<T> Drop for IrqSpinlockGuard<'_, T>
+{
+	fn drop(&mut self)
+	{
+		// release the lock before allowing interrupts again
+		unsafe
+		{
+			ManuallyDrop::drop(&mut self.guard);
+		}
+		restore(self.interrupts);
+	}
+}
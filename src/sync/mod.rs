@@ -0,0 +1,9 @@
+mod irq_spinlock;
+mod mutex;
+mod once;
+mod spinlock;
+
+pub use irq_spinlock::{IrqSpinlock, IrqSpinlockGuard};
+pub use mutex::Mutex;
+pub use once::Once;
+pub use spinlock::Spinlock;
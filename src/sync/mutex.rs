@@ -0,0 +1,54 @@
+use core::hint::spin_loop;
+use crate::arch;
+use super::spinlock::{Spinlock, SpinlockGuard};
+
+// sleeping lock for long critical sections: there is no scheduler yet, so
+// waiting means halting the cpu until the next interrupt. it must never be
+// taken from an interrupt handler, use an IrqSpinlock there
+pub struct Mutex<T>
+{
+	lock: Spinlock<T>
+}
+
+pub type MutexGuard<'a, T> = SpinlockGuard<'a, T>;
+
+impl<T> Mutex<T>
+{
+	pub const fn new(data: T) -> Mutex<T>
+	{
+		Mutex
+		{
+			lock: Spinlock::new(data)
+		}
+	}
+
+	pub fn lock(&self) -> MutexGuard<'_, T>
+	{
+		loop
+		{
+			if let Some(guard) = self.lock.try_lock()
+			{
+				return guard;
+			}
+			// nothing would wake us up with the interrupts disabled
+			if arch::interrupts::are_enabled()
+			{
+				arch::halt();
+			}
+			else
+			{
+				spin_loop();
+			}
+		}
+	}
+
+	pub fn try_lock(&self) -> Option<MutexGuard<'_, T>>
+	{
+		self.lock.try_lock()
+	}
+
+	pub fn is_locked(&self) -> bool
+	{
+		self.lock.is_locked()
+	}
+}
@@ -0,0 +1,71 @@
+use core::cell::UnsafeCell;
+use core::hint::spin_loop;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+const INCOMPLETE: u8 = 0;
+const RUNNING: u8 = 1;
+const COMPLETE: u8 = 2;
+
+// value initialized by the first caller of call_once, the other cpus wait
+// for it to be ready
+pub struct Once<T>
+{
+	state: AtomicU8,
+	data: UnsafeCell<MaybeUninit<T>>
+}
+
+unsafe impl<T: Send + Sync> Sync for Once<T> {}
+unsafe impl<T: Send> Send for Once<T> {}
+
+impl<T> Once<T>
+{
+	pub const fn new() -> Once<T>
+	{
+		Once
+		{
+			state: AtomicU8::new(INCOMPLETE),
+			data: UnsafeCell::new(MaybeUninit::uninit())
+		}
+	}
+
+	pub fn call_once<F: FnOnce() -> T>(&self, f: F) -> &T
+	{
+		if self.state.compare_exchange(INCOMPLETE, RUNNING, Ordering::Acquire, Ordering::Acquire).is_ok()
+		{
+			unsafe
+			{
+				(*self.data.get()).write(f());
+			}
+			self.state.store(COMPLETE, Ordering::Release);
+		}
+		while self.state.load(Ordering::Acquire) != COMPLETE
+		{
+			spin_loop();
+		}
+		unsafe
+		{
+			(*self.data.get()).assume_init_ref()
+		}
+	}
+
+	pub fn get(&self) -> Option<&T>
+	{
+		if self.is_completed()
+		{
+			unsafe
+			{
+				Some((*self.data.get()).assume_init_ref())
+			}
+		}
+		else
+		{
+			None
+		}
+	}
+
+	pub fn is_completed(&self) -> bool
+	{
+		self.state.load(Ordering::Acquire) == COMPLETE
+	}
+}
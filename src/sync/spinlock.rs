@@ -0,0 +1,101 @@
+use core::cell::UnsafeCell;
+use core::hint::spin_loop;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+pub struct Spinlock<T>
+{
+	locked: AtomicBool,
+	data: UnsafeCell<T>
+}
+
+unsafe impl<T: Send> Sync for Spinlock<T> {}
+unsafe impl<T: Send> Send for Spinlock<T> {}
+
+pub struct SpinlockGuard<'a, T>
+{
+	lock: &'a Spinlock<T>
+}
+
+impl<T> Spinlock<T>
+{
+	pub const fn new(data: T) -> Spinlock<T>
+	{
+		Spinlock
+		{
+			locked: AtomicBool::new(false),
+			data: UnsafeCell::new(data)
+		}
+	}
+
+	pub fn lock(&self) -> SpinlockGuard<'_, T>
+	{
+		while self.locked.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err()
+		{
+			// only read while waiting, to avoid bouncing the cache line between cpus
+			while self.locked.load(Ordering::Relaxed)
+			{
+				spin_loop();
+			}
+		}
+		SpinlockGuard
+		{
+			lock: self
+		}
+	}
+
+	pub fn try_lock(&self) -> Option<SpinlockGuard<'_, T>>
+	{
+		match self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+		{
+			Ok(_) => Some(SpinlockGuard
+			{
+				lock: self
+			}),
+			Err(_) => None
+		}
+	}
+
+	pub fn is_locked(&self) -> bool
+	{
+		self.locked.load(Ordering::Relaxed)
+	}
+
+	// only for the panic path, where the owner will never release the lock
+	pub unsafe fn force_unlock(&self)
+	{
+		self.locked.store(false, Ordering::Release);
+	}
+}
+
+impl<T> Deref for SpinlockGuard<'_, T>
+{
+	type Target = T;
+
+	fn deref(&self) -> &T
+	{
+		unsafe
+		{
+			&*self.lock.data.get()
+		}
+	}
+}
+
+impl<T> DerefMut for SpinlockGuard<'_, T>
+{
+	fn deref_mut(&mut self) -> &mut T
+	{
+		unsafe
+		{
+			&mut *self.lock.data.get()
+		}
+	}
+}
+
+impl<T> Drop for SpinlockGuard<'_, T>
+{
+	fn drop(&mut self)
+	{
+		self.lock.locked.store(false, Ordering::Release);
+	}
+}
@@ -1,4 +1,10 @@
 use crate::arch;
+use crate::tty::ldisc::{self, Termios};
+
+// the actions of tcsetattr
+pub const TCSANOW: u32 = 0;
+pub const TCSADRAIN: u32 = 1;
+pub const TCSAFLUSH: u32 = 2;
 
 #[inline(always)]
 pub unsafe fn syscall(syscall_number: u32, arg1: u32, arg2: u32, arg3: u32) -> usize
@@ -17,3 +23,26 @@ pub unsafe fn write(file_descriptor: u32, buffer: u32, len: u32) -> usize
 {
 	syscall(1, file_descriptor, buffer, len)
 }
+
+#[inline(always)]
+pub unsafe fn getrandom(buffer: u32, len: u32, flags: u32) -> usize
+{
+	syscall(2, buffer, len, flags)
+}
+
+#[inline(always)]
+pub unsafe fn ioctl(file_descriptor: u32, request: u32, arg: u32) -> usize
+{
+	syscall(3, file_descriptor, request, arg)
+}
+
+pub unsafe fn tcgetattr(file_descriptor: u32, termios: &mut Termios) -> usize
+{
+	ioctl(file_descriptor, ldisc::TCGETS, termios as *mut Termios as u32)
+}
+
+// TCSETS, TCSETSW and TCSETSF follow each other
+pub unsafe fn tcsetattr(file_descriptor: u32, action: u32, termios: &Termios) -> usize
+{
+	ioctl(file_descriptor, ldisc::TCSETS + action, termios as *const Termios as u32)
+}
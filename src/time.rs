@@ -1 +1,18 @@
-pub static mut JIFFIES: u128 = 0;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+pub const HZ: u32 = 100;
+
+// counted by the boot cpu, read from all of them
+pub static JIFFIES: AtomicU64 = AtomicU64::new(0);
+
+crate::shell_command!("jiffies", &[], "show the ticks since the boot", |_| print_jiffies());
+
+pub fn jiffies() -> u64
+{
+	JIFFIES.load(Ordering::Relaxed)
+}
+
+fn print_jiffies()
+{
+	crate::logln!("jiffies = {}", jiffies());
+}
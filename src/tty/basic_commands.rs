@@ -3,100 +3,63 @@ use crate::arch;
 use crate::ferramenta;
 use crate::memory;
 use crate::vga;
+use super::command::{self, Arg, Args, Kind};
 
-pub fn execute(command: &str)
-{
-	match command
-	{
-		"help" => help(),
-		"clear" => clear(),
-		"halt" | "exit" => halt(),
-		"reboot" => reboot(),
-		"scheen" => scheen(),
-		"pm" => printmem_at(0 as *const u8, false),
-		"pb" => printmem_at(0 as *const u8, true),
-		"ps" => print_stack(),
-		"pt" => printtty(),
-		"jiffies" => jiffies(),
-		"yesss" => yesss(),
-		"panic" => panic(),
-		"rand" => rand(),
-		"elsass" => crate::elsass::flag(),
-		"" => {},
-		_ =>
-		{
-			if let Some(command_end) = command.find(' ')
-			{
-				let arg = &command[command_end + 1..];
-				let command = &command[..command_end];
-				match command
-				{
-					"pm" | "pb" | "pk" | "pv" | "km" | "vm" | "kfree" | "vfree" =>
-					{
-						let address = u32::from_str_radix(arg, 16)
-													 .unwrap_or_else(|_|
-														{
-															crate::println!("invalid argument for {}: {}",
-																			command, arg);
-															0
-														});
-						match command
-						{
-							"pm" => printmem_at(address as *const u8, false),
-							"pb" => printmem_at(address as *const u8, true),
-							"pk" => print_var(address as *const u8, true),
-							"pv" => print_var(address as *const u8, false),
-							"km" => allocate(address as usize, true),
-							"vm" => allocate(address as usize, false),
-							"kfree" => free(address as *mut c_void, true),
-							"vfree" => free(address as *mut c_void, false),
-							_ => {}
-						};
-					},
-					"int" =>
-					{
-						let n = i32::from_str_radix(arg, 16).unwrap_or_else(|_| { -1 });
-						if n >= 0x00 && n <= 0xff
-						{
-							int(n as u8);
-						}
-					},
-					"loadkeys" =>
-					{
-						loadkeys(arg);
-					},
-					"str" =>
-					{
-						let a = alloc::string::String::from(arg);
-						crate::logln!("Allocated String containing \"{}\"", a);
-						crate::logln!(" heap content address {:p}", a.as_ptr());
-						crate::logln!("stack pointer address {:p}", &a);
-					},
-					_ => crate::println!("{}: unknown or invalid command. Use help for more", command)
-				}
-			}
-			else
-			{
-				crate::println!("{}: unknown or invalid command. Use help for more", command);
-			}
-		}
-	};
-}
+crate::shell_command!("clear", &[], "clear the screen", |_| clear());
+crate::shell_command!("halt", &[], "power off the machine (ACPI, or qemu only without it)", |_| halt());
+crate::shell_command!("exit", &[], "same as halt", |_| halt());
+crate::shell_command!("reboot", &[], "reboot the machine", |_| reboot());
+crate::shell_command!("scheen", &[], "yo yo", |_| scheen());
+// debug commands
+crate::shell_command!("pm", &[Arg::optional("address", Kind::Hex), Arg::optional("length", Kind::Int)],
+					"print memory at address (0 if not specified), 0x1000 bytes by default",
+					|args| printmem_at(args.number(0).unwrap_or(0) as *const u8, args.number(1), false));
+crate::shell_command!("pb", &[Arg::optional("address", Kind::Hex), Arg::optional("length", Kind::Int)],
+					"same in binary, 256 bytes by default",
+					|args| printmem_at(args.number(0).unwrap_or(0) as *const u8, args.number(1), true));
+crate::shell_command!("pk", &[Arg::required("address", Kind::Hex)], "print a kmalloc allocation",
+					|args| print_var(args.number(0).unwrap_or(0) as *const u8, true));
+crate::shell_command!("pv", &[Arg::required("address", Kind::Hex)], "print a vmalloc allocation",
+					|args| print_var(args.number(0).unwrap_or(0) as *const u8, false));
+crate::shell_command!("km", &[Arg::required("size", Kind::Hex)], "allocate with kmalloc",
+					|args| allocate(args.number(0).unwrap_or(0), true));
+crate::shell_command!("vm", &[Arg::required("size", Kind::Hex)], "allocate with vmalloc",
+					|args| allocate(args.number(0).unwrap_or(0), false));
+crate::shell_command!("kfree", &[Arg::required("address", Kind::Hex)], "free a kmalloc allocation",
+					|args| free(args.number(0).unwrap_or(0) as *mut c_void, true));
+crate::shell_command!("vfree", &[Arg::required("address", Kind::Hex)], "free a vmalloc allocation",
+					|args| free(args.number(0).unwrap_or(0) as *mut c_void, false));
+crate::shell_command!("ps", &[], "print stack", |_| print_stack());
+crate::shell_command!("pt", &[], "print the screen of the current tty to serial", |_| printtty());
+crate::shell_command!("int", &[Arg::required("vector", Kind::Hex)], "raise a software interrupt", interrupt);
+crate::shell_command!("str", &[Arg::required("text", Kind::Rest)], "copy the text to a heap String", |args| string(args.str(0).unwrap_or("")));
+crate::shell_command!("yesss", &[], "yesss", |_| yesss());
+crate::shell_command!("panic", &[], "trigger a rust panic", |_| panic());
 
 fn clear()
 {
-	super::Tty::current().clear();
+	if super::serial::is_active()
+	{
+		crate::print!("\x1B[2J\x1B[H");
+		return;
+	}
 	vga::Buffer::clear();
 }
 
 fn halt()
 {
-	ferramenta::shutdown_qemu();
+	if !crate::acpi::shutdown()
+	{
+		ferramenta::shutdown_qemu();
+	}
 }
 
-// use the 8042 keyboard controller to pulse the reset pin of the CPU
+// try the ACPI reset register first, then use the 8042 keyboard controller to
+// pulse the reset pin of the CPU
 fn reboot()
 {
+	crate::acpi::reboot();
+
 	let mut good: u8 = 0x02;
 	while good & 0x02 != 0
 	{
@@ -111,28 +74,37 @@ fn scheen()
 	crate::println!("yo yo des esch d'becht OS eh ?");
 }
 
+// the rows are copied one by one, the serial port is slow
 fn printtty()
 {
 	crate::println!("ok");
 	crate::serial_println!("==============");
-	for i in 0..super::BUFFER_SIZE
+	for row in 0..vga::height()
 	{
-		crate::serial_print!("{}", super::Tty::current().chars[i] as char);
+		let mut line = [b' '; vga::MAX_WIDTH];
+		{
+			let writer = vga::W.lock();
+			for (col, byte) in line[..vga::width()].iter_mut().enumerate()
+			{
+				*byte = writer.cell(row, col).character;
+			}
+		}
+		crate::serial_println!("{}", core::str::from_utf8(&line[..vga::width()]).unwrap_or("").trim_end());
 	}
 	crate::serial_println!("==============");
 }
 
-fn printmem_at(address: *const u8, binary: bool)
+fn printmem_at(address: *const u8, length: Option<usize>, binary: bool)
 {
 	unsafe
 	{
 		if binary
 		{
-			ferramenta::print_memory_bin(address, 256);
+			ferramenta::print_memory_bin(address, length.unwrap_or(256));
 		}
 		else
 		{
-			ferramenta::print_memory(address, 0x1000);
+			ferramenta::print_memory(address, length.unwrap_or(0x1000));
 		}
 	}
 }
@@ -206,16 +178,24 @@ fn free(address: *mut c_void, kernel_space: bool)
 	}
 }
 
-fn loadkeys(layout: &str)
+fn string(text: &str)
 {
-	unsafe
+	let a = alloc::string::String::from(text);
+	crate::logln!("Allocated String containing \"{}\"", a);
+	crate::logln!(" heap content address {:p}", a.as_ptr());
+	crate::logln!("stack pointer address {:p}", &a);
+}
+
+fn interrupt(args: &Args)
+{
+	match args.number(0)
 	{
-		match layout
+		Some(n) if n <= 0xff => int(n as u8),
+		_ =>
 		{
-			"us" => { crate::SETTINGS.layout = 1 },
-			"fr" => { crate::SETTINGS.layout = 0 },
-			_ => { crate::SETTINGS.layout = 0 }
-		};
+			crate::println!("int: the vector is between 0 and ff");
+			command::fail();
+		}
 	}
 }
 
@@ -490,6 +470,7 @@ fn print_stack()
 	{
 		ferramenta::print_memory(crate::get_reg!("esp") as *const u8, 10 * 16);
 	}
+	crate::debug::backtrace::print();
 }
 
 fn panic()
@@ -497,19 +478,6 @@ fn panic()
 	panic!("panic()");
 }
 
-fn jiffies()
-{
-	unsafe
-	{
-		crate::logln!("jiffies = {}", crate::time::JIFFIES);
-	}
-}
-
-fn rand()
-{
-	crate::logln!("{}", crate::arch::rand());
-}
-
 fn yesss()
 {
 	crate::serial_println!("###################%%%%%%%%%####################%%%%%%%%%%%%###((//*        ");
@@ -559,18 +527,3 @@ fn yesss()
 	crate::serial_println!("**********///////(((((((((##############%%%%%%%####(//**,                   ");
 	crate::serial_println!("***********////////((((((((#############%%%%%%####((/*,.                    ");
 }
-
-fn help()
-{
-	crate::println!("Supported commands:");
-	crate::println!("  help:        show this help");
-	crate::println!("  clear:       clear the screen");
-	crate::println!("  halt | exit: stop the virtual machine (qemu only)");
-	crate::println!("  reboot:      reboot the machine");
-	crate::println!("Debug commands:");
-	crate::println!("  pm <address>: print 256 bytes of memory at address (0 if not specified)");
-	crate::println!("  pb <address>: |-------------- same in binary");
-	crate::println!("  ps:           print stack");
-	crate::println!("  pt:           print current tty buffer to serial");
-	crate::println!("  panic:        trigger a rust panic");
-}
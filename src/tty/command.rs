@@ -0,0 +1,351 @@
+use alloc::string::String;
+use core::ffi::c_void;
+use core::fmt;
+use core::slice;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+// more than any command takes
+pub const MAX_ARGS: usize = 4;
+
+extern "C"
+{
+	static _shell_commands_start: c_void;
+	static _shell_commands_end: c_void;
+}
+
+// registers a shell command, listed by help and completed with tab:
+//     shell_command!("dmesg", &[Arg::optional("level", Kind::Str)], "show the kernel log", dmesg);
+// the commands are gathered in the .shell_commands section by the linker
+#[macro_export]
+macro_rules! shell_command
+{
+	($name:literal, $args:expr, $help:literal, $run:expr) =>
+	{
+		const _: () =
+		{
+			#[used]
+			#[link_section = ".shell_commands"]
+			static COMMAND: &'static $crate::tty::command::Command = &$crate::tty::command::Command::new($name, $args, $help, $run);
+		};
+	};
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind
+{
+	// with or without 0x
+	Hex,
+	// decimal, or 0x prefixed hexadecimal
+	Int,
+	// a single word
+	Str,
+	// the end of the line, spaces included
+	Rest,
+	// given by its name, anywhere on the line
+	Flag
+}
+
+pub struct Arg
+{
+	name: &'static str,
+	kind: Kind,
+	optional: bool
+}
+
+impl Arg
+{
+	pub const fn required(name: &'static str, kind: Kind) -> Arg
+	{
+		Arg { name, kind, optional: false }
+	}
+
+	pub const fn optional(name: &'static str, kind: Kind) -> Arg
+	{
+		Arg { name, kind, optional: true }
+	}
+
+	// "-c"
+	pub const fn flag(name: &'static str) -> Arg
+	{
+		Arg { name, kind: Kind::Flag, optional: true }
+	}
+}
+
+impl fmt::Display for Arg
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match (self.kind, self.optional)
+		{
+			(Kind::Flag, _) => write!(f, "[{}]", self.name),
+			(Kind::Rest, false) => write!(f, "<{}...>", self.name),
+			(Kind::Rest, true) => write!(f, "[{}...]", self.name),
+			(_, false) => write!(f, "<{}>", self.name),
+			(_, true) => write!(f, "[{}]", self.name)
+		}
+	}
+}
+
+#[derive(Clone, Copy)]
+pub enum Value<'a>
+{
+	None,
+	Number(usize),
+	Str(&'a str),
+	Flag
+}
+
+// the values in the order of the arguments of the command
+pub struct Args<'a>
+{
+	values: [Value<'a>; MAX_ARGS]
+}
+
+impl<'a> Args<'a>
+{
+	pub fn number(&self, index: usize) -> Option<usize>
+	{
+		match self.values[index]
+		{
+			Value::Number(number) => Some(number),
+			_ => None
+		}
+	}
+
+	pub fn str(&self, index: usize) -> Option<&'a str>
+	{
+		match self.values[index]
+		{
+			Value::Str(s) => Some(s),
+			_ => None
+		}
+	}
+
+	pub fn flag(&self, index: usize) -> bool
+	{
+		matches!(self.values[index], Value::Flag)
+	}
+}
+
+pub enum Error<'a>
+{
+	Missing(&'static str),
+	Invalid(&'static str, &'a str),
+	TooMany(&'a str)
+}
+
+pub struct Command
+{
+	pub name: &'static str,
+	pub args: &'static [Arg],
+	pub help: &'static str,
+	pub run: fn(&Args)
+}
+
+impl Command
+{
+	pub const fn new(name: &'static str, args: &'static [Arg], help: &'static str, run: fn(&Args)) -> Command
+	{
+		assert!(args.len() <= MAX_ARGS, "too many arguments for a shell command");
+		Command { name, args, help, run }
+	}
+
+	pub fn parse<'a>(&self, line: &'a str) -> Result<Args<'a>, Error<'a>>
+	{
+		let mut rest = line;
+
+		self.parse_words(core::iter::from_fn(move ||
+		{
+			rest = rest.trim_start_matches(' ');
+			if rest.is_empty()
+			{
+				return None;
+			}
+			let (word, after) = rest.split_once(' ').unwrap_or((rest, ""));
+			let item = (word, rest);
+			rest = after;
+			Some(item)
+		}))
+	}
+
+	// each word comes with the end of the line it starts, for Kind::Rest
+	pub fn parse_words<'a>(&self, words: impl Iterator<Item = (&'a str, &'a str)>) -> Result<Args<'a>, Error<'a>>
+	{
+		let mut values = [Value::None; MAX_ARGS];
+		let mut positional = self.args.iter().enumerate().filter(|(_, arg)| arg.kind != Kind::Flag);
+
+		for (word, rest) in words
+		{
+			if let Some(flag) = self.args.iter().position(|arg| arg.kind == Kind::Flag && arg.name == word)
+			{
+				values[flag] = Value::Flag;
+				continue;
+			}
+			let (index, arg) = positional.next().ok_or(Error::TooMany(word))?;
+			let invalid = Error::Invalid(arg.name, word);
+			values[index] = match arg.kind
+			{
+				Kind::Hex => Value::Number(usize::from_str_radix(word.trim_start_matches("0x"), 16).map_err(|_| invalid)?),
+				Kind::Int => Value::Number(parse_int(word).ok_or(invalid)?),
+				Kind::Str => Value::Str(word),
+				Kind::Rest =>
+				{
+					values[index] = Value::Str(rest);
+					break;
+				}
+				Kind::Flag => unreachable!()
+			};
+		}
+		for (arg, value) in self.args.iter().zip(values)
+		{
+			if !arg.optional && matches!(value, Value::None)
+			{
+				return Err(Error::Missing(arg.name));
+			}
+		}
+		Ok(Args { values })
+	}
+}
+
+// "name <arg> [arg]"
+pub struct Usage(pub &'static Command);
+
+impl fmt::Display for Usage
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		write!(f, "{}", self.0.name)?;
+		for arg in self.0.args
+		{
+			write!(f, " {}", arg)?;
+		}
+		Ok(())
+	}
+}
+
+fn parse_int(word: &str) -> Option<usize>
+{
+	match word.strip_prefix("0x")
+	{
+		Some(hex) => usize::from_str_radix(hex, 16).ok(),
+		None => word.parse::<usize>().ok()
+	}
+}
+
+pub fn commands() -> &'static [&'static Command]
+{
+	unsafe
+	{
+		let start = &_shell_commands_start as *const _ as *const &'static Command;
+		let end = &_shell_commands_end as *const _ as *const &'static Command;
+		slice::from_raw_parts(start, end.offset_from(start) as usize)
+	}
+}
+
+// set by the command running, for && and || in the scripts
+static FAILED: AtomicBool = AtomicBool::new(false);
+
+pub fn fail()
+{
+	FAILED.store(true, Ordering::Relaxed);
+}
+
+pub fn find(name: &str) -> Option<&'static Command>
+{
+	commands().iter().find(|command| command.name == name).copied()
+}
+
+fn lookup(name: &str) -> Option<&'static Command>
+{
+	let command = find(name);
+
+	if command.is_none()
+	{
+		crate::println!("{}: unknown or invalid command. Use help for more", name);
+	}
+	command
+}
+
+fn run(command: &'static Command, args: Result<Args, Error>) -> bool
+{
+	match args
+	{
+		Ok(args) =>
+		{
+			FAILED.store(false, Ordering::Relaxed);
+			(command.run)(&args);
+			!FAILED.load(Ordering::Relaxed)
+		}
+		Err(error) =>
+		{
+			let name = command.name;
+			match error
+			{
+				Error::Missing(arg) => crate::println!("{}: missing {}", name, arg),
+				Error::Invalid(arg, word) => crate::println!("{}: invalid {}: {}", name, arg, word),
+				Error::TooMany(word) => crate::println!("{}: unexpected argument {}", name, word)
+			}
+			crate::println!("usage: {}", Usage(command));
+			false
+		}
+	}
+}
+
+// false for an unknown command, a usage error or a command that failed
+pub fn execute(line: &str) -> bool
+{
+	let line = line.trim_start_matches(' ');
+	if line.is_empty()
+	{
+		return true;
+	}
+	let (name, args) = line.split_once(' ').unwrap_or((line, ""));
+	match lookup(name)
+	{
+		Some(command) => run(command, command.parse(args)),
+		None => false
+	}
+}
+
+// the words already split by the script, kept as they are, spaces and
+// empty words included
+pub fn execute_words(words: &[String]) -> bool
+{
+	let (name, words) = match words.split_first()
+	{
+		Some(split) => split,
+		None => return true
+	};
+	let command = match lookup(name)
+	{
+		Some(command) => command,
+		None => return false
+	};
+
+	// the end of the line for Kind::Rest, the words joined back
+	let line = words.join(" ");
+	let mut start = 0;
+	let words = words.iter().map(|word|
+	{
+		let rest = &line[start..];
+		start += word.len() + 1;
+		(word.as_str(), rest)
+	});
+	run(command, command.parse_words(words))
+}
+
+// generated from the registry, sorted by name
+fn help(_: &Args)
+{
+	let mut commands = alloc::vec::Vec::from(commands());
+
+	commands.sort_unstable_by_key(|command| command.name);
+	crate::println!("Supported commands:");
+	for command in commands
+	{
+		let usage = alloc::format!("{}", Usage(command));
+		crate::println!("  {:24} {}", usage, command.help);
+	}
+}
+
+crate::shell_command!("help", &[], "show this help", help);
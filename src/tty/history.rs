@@ -0,0 +1,142 @@
+use core::ops::Range;
+
+// the last lines typed on a tty, numbered from 1 like in bash
+pub const HISTORY_SIZE: usize = 32;
+// longer lines are not kept
+const ENTRY_SIZE: usize = 128;
+
+#[derive(Clone, Copy)]
+pub struct History
+{
+	entries: [[u8; ENTRY_SIZE]; HISTORY_SIZE],
+	lens: [usize; HISTORY_SIZE],
+	// the number of the last line
+	last: usize,
+	// the line recalled with up and down, last + 1 for the one being typed
+	shown: usize
+}
+
+fn append(out: &mut [u8], len: &mut usize, bytes: &[u8])
+{
+	let count = bytes.len().min(out.len() - *len);
+
+	out[*len..*len + count].copy_from_slice(&bytes[..count]);
+	*len += count;
+}
+
+impl History
+{
+	pub const fn new() -> History
+	{
+		History
+		{
+			entries: [[0; ENTRY_SIZE]; HISTORY_SIZE],
+			lens: [0; HISTORY_SIZE],
+			last: 0,
+			shown: 1
+		}
+	}
+
+	pub fn first(&self) -> usize
+	{
+		if self.last > HISTORY_SIZE { self.last - HISTORY_SIZE + 1 } else { 1 }
+	}
+
+	pub fn last(&self) -> usize
+	{
+		self.last
+	}
+
+	pub fn get(&self, number: usize) -> Option<&[u8]>
+	{
+		if number < self.first() || number > self.last
+		{
+			return None;
+		}
+		let index = (number - 1) % HISTORY_SIZE;
+		Some(&self.entries[index][..self.lens[index]])
+	}
+
+	// the blank lines and the repeated ones are not kept
+	pub fn add(&mut self, line: &[u8])
+	{
+		self.rewind();
+		if line.iter().all(|byte| *byte == b' ') || line.len() > ENTRY_SIZE || self.get(self.last) == Some(line)
+		{
+			return;
+		}
+		let index = self.last % HISTORY_SIZE;
+
+		self.entries[index][..line.len()].copy_from_slice(line);
+		self.lens[index] = line.len();
+		self.last += 1;
+		self.rewind();
+	}
+
+	// the numbers start again from 1
+	pub fn clear(&mut self)
+	{
+		self.lens = [0; HISTORY_SIZE];
+		self.last = 0;
+		self.rewind();
+	}
+
+	// back to the line being typed
+	pub fn rewind(&mut self)
+	{
+		self.shown = self.last + 1;
+	}
+
+	pub fn previous(&mut self) -> Option<&[u8]>
+	{
+		if self.shown <= self.first()
+		{
+			return None;
+		}
+		self.shown -= 1;
+		self.get(self.shown)
+	}
+
+	// after the last line comes an empty one
+	pub fn next(&mut self) -> Option<&[u8]>
+	{
+		if self.shown > self.last
+		{
+			return None;
+		}
+		self.shown += 1;
+		Some(self.get(self.shown).unwrap_or(&[]))
+	}
+
+	// "!!" is the last line and "!n" the line n, the range of a missing one
+	// is returned
+	pub fn expand(&self, line: &[u8], out: &mut [u8]) -> Result<usize, Range<usize>>
+	{
+		let mut len = 0;
+		let mut i = 0;
+
+		while i < line.len()
+		{
+			let (event, end) = match (line[i], line.get(i + 1))
+			{
+				(b'!', Some(b'!')) => (self.get(self.last), i + 2),
+				(b'!', Some(b'0'..=b'9')) =>
+				{
+					let digits = line[i + 1..].iter().take_while(|byte| byte.is_ascii_digit()).count();
+					let number = line[i + 1..i + 1 + digits].iter()
+						.fold(0usize, |number, digit| number.saturating_mul(10).saturating_add((digit - b'0') as usize));
+					(self.get(number), i + 1 + digits)
+				}
+				(byte, _) =>
+				{
+					append(out, &mut len, &[byte]);
+					i += 1;
+					continue;
+				}
+			};
+			append(out, &mut len, event.ok_or(i..end)?);
+			i = end;
+		}
+		Ok(len)
+	}
+}
@@ -0,0 +1,496 @@
+// line discipline between the decoded input of a tty and its readers: the
+// line editing and the echo in canonical mode, the bytes as they come in raw
+// mode, and the signals in both
+
+pub const LINE_SIZE: usize = 1024;
+
+// the termios of linux on i386, as read and written by the ioctls
+pub const NCCS: usize = 19;
+
+// indexes in cc
+pub const VINTR: usize = 0;
+pub const VQUIT: usize = 1;
+pub const VERASE: usize = 2;
+pub const VKILL: usize = 3;
+pub const VEOF: usize = 4;
+pub const VMIN: usize = 6;
+pub const VWERASE: usize = 14;
+
+pub const DELETE: u8 = 0x7f;
+
+// iflag
+pub const ICRNL: u32 = 0o400;
+// oflag
+pub const OPOST: u32 = 0o1;
+pub const ONLCR: u32 = 0o4;
+// cflag, only reported
+const B38400: u32 = 0o17;
+const CS8: u32 = 0o60;
+const CREAD: u32 = 0o200;
+// lflag
+pub const ISIG: u32 = 0o1;
+pub const ICANON: u32 = 0o2;
+pub const ECHO: u32 = 0o10;
+pub const ECHOE: u32 = 0o20;
+pub const ECHOK: u32 = 0o40;
+pub const ECHOCTL: u32 = 0o1000;
+
+// ioctl requests
+pub const TCGETS: u32 = 0x5401;
+pub const TCSETS: u32 = 0x5402;
+pub const TCSETSW: u32 = 0x5403;
+pub const TCSETSF: u32 = 0x5404;
+
+const fn control(key: u8) -> u8
+{
+	key & 0x1f
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Termios
+{
+	pub iflag: u32,
+	pub oflag: u32,
+	pub cflag: u32,
+	pub lflag: u32,
+	pub line: u8,
+	pub cc: [u8; NCCS]
+}
+
+impl Termios
+{
+	pub const fn sane() -> Termios
+	{
+		let mut cc = [0; NCCS];
+
+		cc[VINTR] = control(b'C');
+		cc[VQUIT] = control(b'\\');
+		cc[VERASE] = DELETE;
+		cc[VKILL] = control(b'U');
+		cc[VEOF] = control(b'D');
+		cc[VMIN] = 1;
+		cc[VWERASE] = control(b'W');
+		Termios
+		{
+			iflag: ICRNL,
+			oflag: OPOST | ONLCR,
+			cflag: B38400 | CS8 | CREAD,
+			lflag: ISIG | ICANON | ECHO | ECHOE | ECHOK | ECHOCTL,
+			line: 0,
+			cc
+		}
+	}
+
+	pub fn is_set(&self, lflag: u32) -> bool
+	{
+		self.lflag & lflag != 0
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal
+{
+	Interrupt = 2,
+	Quit = 3
+}
+
+pub enum Input
+{
+	Byte(u8),
+	Left,
+	Right,
+	Home,
+	End,
+	// the character under the cursor
+	Delete
+}
+
+pub enum Event
+{
+	None,
+	// a line for the shell, copied to the given buffer
+	Line(usize),
+	// a line went to the reader, the tty goes to the next one
+	Read,
+	Signal(Signal),
+	Eof
+}
+
+// what the tty shows of the line being edited
+pub trait Echo
+{
+	// at the cursor, the rest of the line moves to the right
+	fn insert(&mut self, byte: u8);
+	// the character before the cursor
+	fn erase(&mut self);
+	fn move_left(&mut self);
+	fn move_right(&mut self);
+}
+
+#[derive(Clone, Copy)]
+pub struct LineDiscipline
+{
+	pub termios: Termios,
+	line: [u8; LINE_SIZE],
+	len: usize,
+	cursor: usize,
+	// the input waiting for a reader
+	queue: [u8; LINE_SIZE],
+	queue_len: usize,
+	eof: bool,
+	// a reader is waiting, the lines go to the queue instead of the shell
+	pub reading: bool,
+	interrupted: bool
+}
+
+impl LineDiscipline
+{
+	pub const fn new() -> LineDiscipline
+	{
+		LineDiscipline
+		{
+			termios: Termios::sane(),
+			line: [0; LINE_SIZE],
+			len: 0,
+			cursor: 0,
+			queue: [0; LINE_SIZE],
+			queue_len: 0,
+			eof: false,
+			reading: false,
+			interrupted: false
+		}
+	}
+
+	pub fn set_termios(&mut self, termios: Termios, flush: bool)
+	{
+		self.termios = termios;
+		if flush
+		{
+			self.flush();
+		}
+	}
+
+	// forget the line and the input nobody read yet
+	pub fn flush(&mut self)
+	{
+		self.len = 0;
+		self.cursor = 0;
+		self.queue_len = 0;
+		self.eof = false;
+	}
+
+	fn echo_control(&self, byte: u8, echo: &mut dyn Echo)
+	{
+		if self.termios.is_set(ECHO)
+		{
+			if self.termios.is_set(ECHOCTL) && byte < 0x20
+			{
+				echo.insert(b'^');
+				echo.insert(byte + b'@');
+			}
+			else
+			{
+				echo.insert(byte);
+			}
+		}
+	}
+
+	fn queue(&mut self, bytes: &[u8])
+	{
+		let len = bytes.len().min(LINE_SIZE - self.queue_len);
+
+		self.queue[self.queue_len..self.queue_len + len].copy_from_slice(&bytes[..len]);
+		self.queue_len += len;
+	}
+
+	fn insert(&mut self, byte: u8, echo: &mut dyn Echo)
+	{
+		if self.len == LINE_SIZE
+		{
+			return;
+		}
+		self.line.copy_within(self.cursor..self.len, self.cursor + 1);
+		self.line[self.cursor] = byte;
+		self.len += 1;
+		self.cursor += 1;
+		if self.termios.is_set(ECHO)
+		{
+			echo.insert(byte);
+		}
+	}
+
+	fn erase(&mut self, echo: &mut dyn Echo) -> bool
+	{
+		if self.cursor == 0
+		{
+			return false;
+		}
+		self.line.copy_within(self.cursor..self.len, self.cursor - 1);
+		self.len -= 1;
+		self.cursor -= 1;
+		if self.termios.is_set(ECHO)
+		{
+			echo.erase();
+		}
+		true
+	}
+
+	// the spaces before the cursor, then the word
+	fn erase_word(&mut self, echo: &mut dyn Echo)
+	{
+		while self.cursor > 0 && self.line[self.cursor - 1] == b' '
+		{
+			self.erase(echo);
+		}
+		while self.cursor > 0 && self.line[self.cursor - 1] != b' '
+		{
+			self.erase(echo);
+		}
+	}
+
+	// the line ends, to the reader when there is one, or else to the shell
+	fn deliver(&mut self, newline: bool, out: &mut [u8]) -> Event
+	{
+		let len = self.len;
+
+		self.len = 0;
+		self.cursor = 0;
+		if self.reading
+		{
+			for i in 0..len
+			{
+				let byte = self.line[i];
+				self.queue(&[byte]);
+			}
+			if !newline
+			{
+				// ^D, the reader gets the line as it is
+				self.eof = true;
+				return Event::None;
+			}
+			self.queue(b"\n");
+			return Event::Read;
+		}
+		let len = len.min(out.len());
+		out[..len].copy_from_slice(&self.line[..len]);
+		Event::Line(len)
+	}
+
+	fn signal(&mut self, signal: Signal, byte: u8, echo: &mut dyn Echo) -> Event
+	{
+		self.echo_control(byte, echo);
+		self.flush();
+		if self.reading
+		{
+			self.interrupted = true;
+		}
+		Event::Signal(signal)
+	}
+
+	pub fn receive(&mut self, input: Input, echo: &mut dyn Echo, out: &mut [u8]) -> Event
+	{
+		// without a reader the input goes to the shell, which only takes lines
+		let canonical = self.termios.is_set(ICANON) || !self.reading;
+		let signals = self.termios.is_set(ISIG) || !self.reading;
+		let echoed = self.termios.is_set(ECHO);
+		let mut byte = match input
+		{
+			Input::Byte(byte) => byte,
+			Input::Left if canonical && self.cursor > 0 =>
+			{
+				self.cursor -= 1;
+				if echoed
+				{
+					echo.move_left();
+				}
+				return Event::None;
+			}
+			Input::Right if canonical && self.cursor < self.len =>
+			{
+				self.cursor += 1;
+				if echoed
+				{
+					echo.move_right();
+				}
+				return Event::None;
+			}
+			Input::Home if canonical =>
+			{
+				while self.cursor > 0
+				{
+					self.cursor -= 1;
+					if echoed
+					{
+						echo.move_left();
+					}
+				}
+				return Event::None;
+			}
+			Input::End if canonical =>
+			{
+				self.move_to_end(echo);
+				return Event::None;
+			}
+			Input::Delete if canonical && self.cursor < self.len =>
+			{
+				self.cursor += 1;
+				if echoed
+				{
+					echo.move_right();
+				}
+				self.erase(echo);
+				return Event::None;
+			}
+			_ => return Event::None
+		};
+		let cc = self.termios.cc;
+
+		if byte == b'\r' && self.termios.iflag & ICRNL != 0
+		{
+			byte = b'\n';
+		}
+		if signals
+		{
+			if byte == cc[VINTR]
+			{
+				return self.signal(Signal::Interrupt, byte, echo);
+			}
+			if byte == cc[VQUIT]
+			{
+				return self.signal(Signal::Quit, byte, echo);
+			}
+		}
+		if !canonical
+		{
+			self.queue(&[byte]);
+			if echoed
+			{
+				echo.insert(byte);
+			}
+			return Event::None;
+		}
+
+		match byte
+		{
+			b'\n' => return self.deliver(true, out),
+			_ if byte == cc[VERASE] || byte == crate::vga::BACKSPACE =>
+			{
+				self.erase(echo);
+			}
+			_ if byte == cc[VKILL] =>
+			{
+				while self.erase(echo) {}
+			}
+			_ if byte == cc[VWERASE] => self.erase_word(echo),
+			_ if byte == cc[VEOF] =>
+			{
+				// the shell only takes whole lines
+				if self.len > 0 && self.reading
+				{
+					return self.deliver(false, out);
+				}
+				if self.len > 0
+				{
+					return Event::None;
+				}
+				if self.reading
+				{
+					self.eof = true;
+				}
+				return Event::Eof;
+			}
+			0x20..=0x7e => self.insert(byte, echo),
+			_ => {}
+		}
+		Event::None
+	}
+
+	fn move_to_end(&mut self, echo: &mut dyn Echo)
+	{
+		while self.cursor < self.len
+		{
+			self.cursor += 1;
+			if self.termios.is_set(ECHO)
+			{
+				echo.move_right();
+			}
+		}
+	}
+
+	// the word completed by the shell ends at the cursor
+	pub fn before_cursor(&self) -> &[u8]
+	{
+		&self.line[..self.cursor]
+	}
+
+	pub fn insert_bytes(&mut self, bytes: &[u8], echo: &mut dyn Echo)
+	{
+		for byte in bytes
+		{
+			self.insert(*byte, echo);
+		}
+	}
+
+	// a line from the history takes the place of the one being edited
+	pub fn replace(&mut self, bytes: &[u8], echo: &mut dyn Echo)
+	{
+		self.move_to_end(echo);
+		while self.erase(echo) {}
+		self.insert_bytes(bytes, echo);
+	}
+
+	// the line is echoed again, after something was printed over it
+	pub fn redraw(&self, echo: &mut dyn Echo)
+	{
+		if !self.termios.is_set(ECHO)
+		{
+			return;
+		}
+		for byte in &self.line[..self.len]
+		{
+			echo.insert(*byte);
+		}
+		for _ in self.cursor..self.len
+		{
+			echo.move_left();
+		}
+	}
+
+	// None until there is something to read: a whole line in canonical mode,
+	// VMIN bytes in raw mode
+	pub fn read(&mut self, buffer: &mut [u8]) -> Option<Result<usize, Signal>>
+	{
+		if core::mem::replace(&mut self.interrupted, false)
+		{
+			return Some(Err(Signal::Interrupt));
+		}
+		let available = if self.termios.is_set(ICANON)
+		{
+			match self.queue[..self.queue_len].iter().position(|byte| *byte == b'\n')
+			{
+				Some(newline) => newline + 1,
+				None if self.eof || self.queue_len == LINE_SIZE =>
+				{
+					self.eof = false;
+					self.queue_len
+				}
+				None => return None
+			}
+		}
+		else if self.queue_len >= (self.termios.cc[VMIN] as usize).max(1)
+		{
+			self.queue_len
+		}
+		else
+		{
+			return None;
+		};
+		let len = available.min(buffer.len());
+
+		buffer[..len].copy_from_slice(&self.queue[..len]);
+		self.queue.copy_within(len..self.queue_len, 0);
+		self.queue_len -= len;
+		Some(Ok(len))
+	}
+}
@@ -1,316 +1,546 @@
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use crate::keyboard;
+use crate::sync::IrqSpinlock;
 use crate::vga;
-pub use crate::tty::print::_print;
+use command::{Arg, Kind};
+use history::History;
+use ldisc::{Echo, Event, Input, LineDiscipline, Signal, Termios};
+pub use crate::tty::print::{_print, print_to};
 
 mod basic_commands;
+pub mod command;
+mod history;
+pub mod ldisc;
 mod print;
+pub mod script;
+pub mod serial;
+mod shell;
 
-const BUFFER_HEIGHT: usize = vga::BUFFER_HEIGHT;
-const BUFFER_WIDTH: usize = vga::BUFFER_WIDTH;
-const BUFFER_SIZE: usize = BUFFER_WIDTH * BUFFER_HEIGHT;
-const INPUT_SIZE: usize = BUFFER_SIZE / 2;
+pub const TTY_COUNT: usize = 8;
 
-static mut VGA: *mut vga::Buffer = 0xb8000 as *mut vga::Buffer;
-
-#[derive(Copy, Clone)]
-#[repr(C)]
+// the screen of a tty is kept here while another one is shown, the one of
+// the current tty is vga::W
 struct Tty
 {
 	has_init: bool,
-	chars: [u8; BUFFER_SIZE],
-	pos: usize,
-	pos_offset: usize,
-	cursor_offset: usize,
-	scroll: usize,
-	input: [u8; INPUT_SIZE],
-	command: &'static str
+	writer: vga::Writer,
+	// the characters of the line being edited after the cursor
+	cursor_offset: usize
 }
 
 impl Tty
 {
-	fn prompt(&mut self)
+	const fn new() -> Tty
 	{
-		if !self.has_init
+		Tty
 		{
-			self.has_init = true;
-			unsafe
-			{
-				crate::println!("elsOS tty{}\n", CURRENT_TTY + 1);
-			}
+			has_init: false,
+			writer: vga::Writer::new(false),
+			cursor_offset: 0
 		}
-		crate::print!("\x1B[30;47mWas esch los ? >\x1B[39;49m ");
 	}
+}
+
+struct Ttys
+{
+	current: usize,
+	ttys: [Tty; TTY_COUNT],
+	ldiscs: [LineDiscipline; TTY_COUNT],
+	histories: [History; TTY_COUNT]
+}
 
-	fn execute(&mut self)
+impl Ttys
+{
+	fn current(&mut self) -> &mut Tty
 	{
-		basic_commands::execute(self.command);
-		self.prompt();
+		&mut self.ttys[self.current]
 	}
 
-	fn increase_pos_with_offset(&mut self)
+	fn current_ldisc(&mut self) -> &mut LineDiscipline
 	{
-		let tmp_offset = self.pos_offset;
-		self.pos_offset = 0;
-		self.increase_pos_by(tmp_offset);
+		&mut self.ldiscs[self.current]
 	}
 
-	fn remove_first_line(&mut self)
+	fn current_history(&mut self) -> &mut History
 	{
-		let mut len_to_remove: usize = 0;
+		&mut self.histories[self.current]
+	}
 
-		for byte in &Tty::current().chars[..]
-		{
-			len_to_remove += 1;
-			if *byte == b'\n'
-			{
-				break;
-			}
-		}
-		for i in 0..len_to_remove
+	// the tty a command runs on, or else the one shown
+	fn output(&self) -> usize
+	{
+		match OUTPUT.load(Ordering::Relaxed)
 		{
-			Tty::current().chars[i] = b'\0';
+			SHOWN => self.current,
+			tty => tty
 		}
-		self.chars.rotate_left(len_to_remove);
-		self.pos -= len_to_remove;
 	}
 
-	fn increase_pos_by(&mut self, offset: usize)
+	// the writer of the tty shown is vga::W
+	fn with_writer<T>(&mut self, tty: usize, f: impl FnOnce(&mut vga::Writer) -> T) -> T
 	{
-		if self.pos + self.pos_offset + offset >= BUFFER_SIZE
+		if tty == self.current
 		{
-			self.remove_first_line();
+			return f(&mut vga::W.lock());
 		}
-		self.pos += offset;
+		f(&mut self.ttys[tty].writer)
 	}
+}
 
-	fn current() -> &'static mut Tty
-	{
-		unsafe { &mut TTYS[CURRENT_TTY] }
-	}
+const SHOWN: usize = usize::MAX;
+
+// set while the shell runs a command, its output stays on its tty
+static OUTPUT: AtomicUsize = AtomicUsize::new(SHOWN);
+
+// shared with the keyboard interrupt handler, which also runs the shell
+// commands: never print while holding it
+static TTYS: IrqSpinlock<Ttys> = IrqSpinlock::new(Ttys
+{
+	current: 0,
+	ttys: [const { Tty::new() }; TTY_COUNT],
+	ldiscs: [LineDiscipline::new(); TTY_COUNT],
+	histories: [History::new(); TTY_COUNT]
+});
 
-	fn clear(&mut self)
+pub fn prompt()
+{
+	let tty = TTYS.lock().output();
+
+	prompt_on(tty);
+}
+
+fn prompt_on(tty: usize)
+{
+	let first_prompt = !core::mem::replace(&mut TTYS.lock().ttys[tty].has_init, true);
+
+	if first_prompt
 	{
-		self.chars.fill(b'\0');
-		self.pos = 0;
-		self.pos_offset = 0;
-		self.cursor_offset = 0;
-		self.scroll = 0;
-		self.input.fill(b'\0');
-		self.command = "";
+		print_to(tty, format_args!("elsOS tty{}\n\n", tty + 1));
 	}
+	print_to(tty, format_args!("\x1B[30;47mWas esch los ? >\x1B[39;49m "));
 }
 
-pub fn prompt()
+// redraw the current tty, after something else used the screen
+pub fn refresh()
 {
-	unsafe
+	vga::W.lock().redraw();
+}
+
+// the scrollback of the ttys needs the memory, the lines that went off the
+// screen before are lost
+pub fn init_scrollback() -> bool
+{
+	let mut allocated = true;
+
+	for tty in 0..TTY_COUNT
 	{
-		TTYS[CURRENT_TTY].prompt();
+		let scrollback = vga::scrollback::Scrollback::alloc();
+
+		allocated &= scrollback.capacity() == vga::scrollback::SCROLLBACK.get();
+		TTYS.lock().with_writer(tty, |writer| writer.set_scrollback(scrollback));
 	}
+	allocated
 }
 
-static mut CURRENT_TTY: usize = 0;
-static mut TTYS: [Tty; 8] =
-[
-	Tty
-	{
-		has_init: false,
-		chars: [b'\0'; BUFFER_SIZE],
-		pos: 0,
-		pos_offset: 0,
-		cursor_offset: 0,
-		scroll: 0,
-		input: [b'\0'; INPUT_SIZE],
-		command: ""
-	}; 8
-];
+// only for the panic path, where the owner will never release the lock
+pub unsafe fn force_unlock()
+{
+	TTYS.force_unlock();
+}
+
+// the line being edited is echoed on the screen of the current tty
+struct VgaEcho<'a>
+{
+	cursor_offset: &'a mut usize
+}
 
-pub fn write_byte(byte: u8, offset: bool)
+impl Echo for VgaEcho<'_>
 {
-	unsafe
+	fn insert(&mut self, byte: u8)
 	{
-		if Tty::current().pos + Tty::current().pos_offset >= BUFFER_SIZE
-		{
-			Tty::current().remove_first_line();
-		}
-		let pos = Tty::current().pos + Tty::current().pos_offset - Tty::current().cursor_offset;
-		if Tty::current().cursor_offset > 0
-		{
-			let right_part: &mut [u8] = &mut Tty::current().chars[pos..];
-			right_part.rotate_right(1);
-		}
-		Tty::current().chars[pos] = byte;
-		if offset
-		{
-			TTYS[CURRENT_TTY].pos_offset += 1;
-		}
-		else
-		{
-			TTYS[CURRENT_TTY].pos += 1;
-		}
+		vga::W.lock().insert(byte, *self.cursor_offset);
+	}
+
+	fn erase(&mut self)
+	{
+		vga::W.lock().delete(*self.cursor_offset);
+	}
+
+	fn move_left(&mut self)
+	{
+		*self.cursor_offset += 1;
+		vga::W.lock().cursor_left();
+	}
+
+	fn move_right(&mut self)
+	{
+		*self.cursor_offset -= 1;
+		vga::W.lock().cursor_right();
 	}
 }
 
+// the keys are decoded here, then edited by the line discipline
 pub fn input(keyboard_input: &keyboard::KeyboardInput)
 {
-	if let Some(key) = keyboard::char_from_input(keyboard_input)
+	if keyboard_input.released
 	{
-		if key == '\n'
-		{
-			line_return(true);
-			return;
-		}
-		if keyboard_input.state.ctrl
+		return;
+	}
+	let state = &keyboard_input.state;
+	let input = match keyboard::char_from_scancode(keyboard_input)
+	{
+		Some(key) if state.ctrl && key.is_ascii_alphabetic() => Input::Byte(key as u8 & 0x1f),
+		Some(key) => Input::Byte(key as u8),
+		None => match (keyboard_input.extended, keyboard_input.scancode)
 		{
-			match key
+			(false, 0x0e) => Input::Byte(ldisc::DELETE),
+			(false, 0x01) => Input::Byte(vga::ESCAPE_START),
+			// alt+left and right go to the previous and next ttys
+			(true, 0x4B) if state.alt => return switch_by(TTY_COUNT - 1),
+			(true, 0x4D) if state.alt => return switch_by(1),
+			(true, 0x4B) => Input::Left,
+			(true, 0x4D) => Input::Right,
+			(true, 0x47) => Input::Home,
+			(true, 0x4F) => Input::End,
+			(true, 0x53) => Input::Delete,
+			(true, 0x48) => return shell_key(shell::Key::Up),
+			(true, 0x50) => return shell_key(shell::Key::Down),
+			(false, 0x0F) => return shell_key(shell::Key::Tab),
+			// shift+page up and down scroll the screen
+			(true, 0x49) if state.shift => return vga::scroll_up(),
+			(true, 0x51) if state.shift => return vga::scroll_down(),
+			// alt+Fn switches, as on the linux console
+			(false, 0x3B..=0x42) if state.alt => return handle_tty_change((keyboard_input.scancode - 0x3B).into()),
+			(false, 0x3B..=0x42) => return function_key((keyboard_input.scancode - 0x3B).into()),
+			// ctrl+F12 breaks into the debugger
+			(false, 0x58) if state.ctrl && crate::debug::kdb::is_enabled() => return crate::debug::kdb::breakpoint(),
+			(extended, scancode) =>
 			{
-				'C' | 'c' => {
-					write_byte(b'^', true);
-					write_byte(b'C', true);
-					print::print_to_vga();
-					line_return(false);
-					prompt();
-					return;
-				},
-				_ => {}
-			};
+				crate::serial_println!("scancode: {}{:#x}", if extended { "0xe0 " } else { "" }, scancode);
+				return;
+			}
 		}
-		if Tty::current().pos_offset < BUFFER_SIZE / 2
+	};
+
+	let mut line = [b'\0'; ldisc::LINE_SIZE];
+	let event = edit_line(|ldisc, _, echo| ldisc.receive(input, echo, &mut line));
+
+	match event
+	{
+		Event::Line(len) => line_return(Some(&line[..len])),
+		Event::Read => line_return(None),
+		Event::Signal(signal) => self::signal(signal),
+		Event::Eof | Event::None => {}
+	}
+}
+
+// an edit of the line of the current tty
+fn edit_line<T>(f: impl FnOnce(&mut LineDiscipline, &mut History, &mut dyn Echo) -> T) -> T
+{
+	let mut ttys = TTYS.lock();
+	let Ttys { current, ttys, ldiscs, histories } = &mut *ttys;
+	let mut echo = VgaEcho
+	{
+		cursor_offset: &mut ttys[*current].cursor_offset
+	};
+
+	f(&mut ldiscs[*current], &mut histories[*current], &mut echo)
+}
+
+// the history and the completion, a reader gets the tab as it is
+fn shell_key(key: shell::Key)
+{
+	let reading = TTYS.lock().current_ldisc().reading;
+
+	if reading
+	{
+		if let shell::Key::Tab = key
 		{
-			write_byte(key as u8, true);
-			if Tty::current().scroll > 0
-			{
-				unsafe
-				{
-					vga::W.scroll = 0;
-					print::print_to_vga();
-				}
-			}
-			else if Tty::current().cursor_offset > 0
-			{
-				print::print_to_vga();
-			}
-			else
-			{
-				print::print_byte_to_vga(key as u8);
-			}
+			let mut line = [b'\0'; ldisc::LINE_SIZE];
+			edit_line(|ldisc, _, echo| ldisc.receive(Input::Byte(b'\t'), echo, &mut line));
 		}
+		return;
+	}
+	if let Some(candidates) = edit_line(|ldisc, history, echo| shell::edit(key, ldisc, history, echo))
+	{
+		line_return(None);
+		shell::list(&candidates);
+		prompt();
+		edit_line(|ldisc, _, echo| ldisc.redraw(echo));
 	}
-	else
+}
+
+// the linux console sequences of F1 to F8
+const FUNCTION_KEYS: [&[u8]; 8] =
+[
+	b"\x1B[[A", b"\x1B[[B", b"\x1B[[C", b"\x1B[[D", b"\x1B[[E", b"\x1B[17~", b"\x1B[18~", b"\x1B[19~"
+];
+
+// only the programs reading in raw mode get them, the line editing has no
+// use for them
+fn function_key(index: usize)
+{
+	let raw =
+	{
+		let mut ttys = TTYS.lock();
+		let ldisc = ttys.current_ldisc();
+		ldisc.reading && !ldisc.termios.is_set(ldisc::ICANON)
+	};
+
+	if raw
 	{
-		match keyboard_input.scancode
+		let mut line = [b'\0'; ldisc::LINE_SIZE];
+		edit_line(|ldisc, _, echo|
 		{
-			0x0e => backspace(),
-			0x4B => cursor_left(),
-			0x4D => cursor_right(),
-			0x48 => cursor_up(),
-			0x50 => cursor_down(),
-			0x3B..=0x42 => handle_tty_change((keyboard_input.scancode - 0x3B).into()),
-			_ => if keyboard_input.scancode & 0x80 == 0
-				{
-					crate::serial_println!("scancode: {:#x}", keyboard_input.scancode);
-				}
-		};
+			for byte in FUNCTION_KEYS[index]
+			{
+				ldisc.receive(Input::Byte(*byte), echo, &mut line);
+			}
+		});
 	}
 }
 
-fn cursor_left()
+// the screens are swapped, the one of the tty left is kept in its slot and
+// only drawn again once shown
+fn handle_tty_change(tty: usize)
 {
-	if Tty::current().pos_offset - Tty::current().cursor_offset > 0
+	let has_init =
 	{
-		let position = vga::cursor::Cursor::get_position();
+		let mut ttys = TTYS.lock();
+		let previous = ttys.current;
 
-		Tty::current().cursor_offset += 1;
-		unsafe
+		if tty != previous
 		{
-			vga::cursor::CURSOR.offset = Tty::current().cursor_offset;
+			let mut writer = vga::W.lock();
+			core::mem::swap(&mut *writer, &mut ttys.ttys[previous].writer);
+			core::mem::swap(&mut *writer, &mut ttys.ttys[tty].writer);
+			ttys.ttys[previous].writer.set_visible(false);
+			writer.set_visible(true);
+			ttys.current = tty;
 		}
-		vga::cursor::Cursor::move_to(position.0, position.1);
+		ttys.current().has_init
+	};
+	if !has_init
+	{
+		prompt_on(tty);
 	}
 }
 
-fn cursor_right()
+// by an offset, around the ttys
+fn switch_by(offset: usize)
+{
+	let current = TTYS.lock().current;
+
+	handle_tty_change((current + offset) % TTY_COUNT);
+}
+
+// the cursor goes to the end of the echoed line, then the shell runs it with
+// its output on this tty
+fn line_return(command: Option<&[u8]>)
 {
-	if Tty::current().cursor_offset > 0
+	let tty =
 	{
-		Tty::current().cursor_offset -= 1;
-		unsafe
+		let mut ttys = TTYS.lock();
+		let cursor_offset = core::mem::replace(&mut ttys.current().cursor_offset, 0);
+		let mut writer = vga::W.lock();
+
+		for _ in 0..cursor_offset
 		{
-			vga::cursor::CURSOR.offset -= 1;
+			writer.cursor_right();
 		}
+		ttys.current
+	};
+	print_to(tty, format_args!("\n"));
+	if let Some(command) = command
+	{
+		let output = OUTPUT.swap(tty, Ordering::Relaxed);
+		shell::run(command);
+		prompt_on(tty);
+		OUTPUT.store(output, Ordering::Relaxed);
+	}
+}
+
+// the shell is the foreground program while nobody reads the tty, a reader
+// is woken up by the line discipline
+fn signal(_signal: Signal)
+{
+	let reading =
+	{
+		let mut ttys = TTYS.lock();
+		ttys.current_history().rewind();
+		ttys.current_ldisc().reading
+	};
 
-		let position = vga::cursor::Cursor::get_position();
-		vga::cursor::Cursor::move_to(position.0 + 1, position.1);
+	line_return(None);
+	if !reading
+	{
+		prompt();
 	}
 }
 
-fn cursor_down()
+// the line discipline of the tty the command runs on
+fn with_ldisc<T>(f: impl FnOnce(&mut LineDiscipline) -> T) -> T
 {
-	if vga::scroll_down()
+	if serial::is_active()
 	{
-		Tty::current().scroll -= 1;
-		print::print_to_vga();
+		return serial::with_ldisc(f);
 	}
+	let mut ttys = TTYS.lock();
+	let tty = ttys.output();
+	f(&mut ttys.ldiscs[tty])
 }
 
-fn cursor_up()
+// the history of the tty the command runs on
+fn with_history<T>(f: impl FnOnce(&mut History) -> T) -> T
 {
-	if vga::scroll_up()
+	if serial::is_active()
 	{
-		Tty::current().scroll += 1;
-		print::print_to_vga();
+		return serial::with_history(f);
 	}
+	let mut ttys = TTYS.lock();
+	let tty = ttys.output();
+	f(&mut ttys.histories[tty])
 }
 
-fn handle_tty_change(tty: usize)
+// read(2) on the tty, None until there is enough input for the mode
+pub fn read(buffer: &mut [u8]) -> Option<Result<usize, Signal>>
 {
-	unsafe
+	with_ldisc(|ldisc|
 	{
-		CURRENT_TTY = tty;
-		if !Tty::current().has_init
+		ldisc.reading = true;
+		let result = ldisc.read(buffer);
+		if result.is_some()
 		{
-			Tty::current().prompt();
+			ldisc.reading = false;
 		}
-		vga::cursor::CURSOR.offset = Tty::current().cursor_offset;
-		vga::W.scroll = Tty::current().scroll;
-		print::print_to_vga();
-	}
+		result
+	})
 }
 
-fn line_return(execute: bool)
+// the termios ioctls, false for the other requests
+pub fn ioctl(request: u32, termios: &mut Termios) -> bool
 {
-	let input_start = Tty::current().pos;
-	let input_len = Tty::current().pos_offset;
-	Tty::current().input.fill(b'\0');
-	Tty::current().input[..input_len].copy_from_slice(&Tty::current().chars[input_start..input_start + input_len]);
-	Tty::current().command = &core::str::from_utf8(&Tty::current().input).unwrap()[..input_len];
-	Tty::current().increase_pos_with_offset();
-	Tty::current().increase_pos_by(1);
-	Tty::current().cursor_offset = 0;
-	unsafe
-	{
-		vga::cursor::CURSOR.offset = 0;
-	}
-	crate::println!();
-	if execute
+	with_ldisc(|ldisc|
 	{
-		Tty::current().execute();
+		match request
+		{
+			ldisc::TCGETS => *termios = ldisc.termios,
+			ldisc::TCSETS | ldisc::TCSETSW => ldisc.set_termios(*termios, false),
+			ldisc::TCSETSF => ldisc.set_termios(*termios, true),
+			_ => return false
+		}
+		true
+	})
+}
+
+crate::shell_command!("chvt", &[Arg::required("n", Kind::Int)], "switch to the tty n, same as alt+Fn", chvt);
+
+fn chvt(args: &command::Args)
+{
+	match args.number(0)
+	{
+		Some(tty @ 1..=TTY_COUNT) => handle_tty_change(tty - 1),
+		_ =>
+		{
+			crate::println!("chvt: there are only {} ttys", TTY_COUNT);
+			command::fail();
+		}
 	}
 }
 
-fn backspace()
+const LFLAGS: [(&str, u32); 6] =
+[
+	("isig", ldisc::ISIG),
+	("icanon", ldisc::ICANON),
+	("echo", ldisc::ECHO),
+	("echoe", ldisc::ECHOE),
+	("echok", ldisc::ECHOK),
+	("echoctl", ldisc::ECHOCTL)
+];
+
+// the caret notation for the control characters, the others as they are
+struct ControlName(u8);
+
+impl fmt::Display for ControlName
 {
-	if Tty::current().pos_offset - Tty::current().cursor_offset > 0
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
 	{
-		Tty::current().pos_offset -= 1;
+		match self.0
+		{
+			0x00..=0x1f => write!(f, "^{}", (self.0 ^ 0x40) as char),
+			ldisc::DELETE => write!(f, "^?"),
+			byte => write!(f, "{}", byte as char)
+		}
+	}
+}
 
-		let pos = Tty::current().pos + Tty::current().pos_offset - Tty::current().cursor_offset;
+crate::shell_command!("stty", &[Arg::optional("setting", Kind::Str)],
+					"show or change the tty modes (icanon, echo, isig, raw, sane, ...)", |args| stty(args.str(0)));
 
-		Tty::current().chars[pos] = b'\0';
-		if Tty::current().cursor_offset > 0
+// "stty", or "stty [-]flag", "stty raw", "stty sane"
+fn stty(arg: Option<&str>)
+{
+	let mut termios = with_ldisc(|ldisc| ldisc.termios);
+
+	let arg = match arg
+	{
+		Some(arg) => arg,
+		None =>
 		{
-			let right_part: &mut [u8] = &mut Tty::current().chars[pos..];
-			right_part.rotate_left(1);
+			for (name, index) in [("intr", ldisc::VINTR), ("quit", ldisc::VQUIT), ("erase", ldisc::VERASE),
+								("kill", ldisc::VKILL), ("eof", ldisc::VEOF), ("werase", ldisc::VWERASE)]
+			{
+				crate::print!("{} = {}; ", name, ControlName(termios.cc[index]));
+			}
+			crate::println!("min = {};", termios.cc[ldisc::VMIN]);
+			for (name, flag) in LFLAGS
+			{
+				crate::print!("{}{} ", if termios.is_set(flag) { "" } else { "-" }, name);
+			}
+			crate::println!("{}icrnl {}opost {}onlcr", if termios.iflag & ldisc::ICRNL != 0 { "" } else { "-" },
+							if termios.oflag & ldisc::OPOST != 0 { "" } else { "-" },
+							if termios.oflag & ldisc::ONLCR != 0 { "" } else { "-" });
+			return;
+		}
+	};
+
+	let (name, set) = match arg.strip_prefix('-')
+	{
+		Some(name) => (name, false),
+		None => (arg, true)
+	};
+	let raw = ldisc::ICANON | ldisc::ISIG;
+	match name
+	{
+		"sane" => termios = Termios::sane(),
+		"raw" if set =>
+		{
+			termios.lflag &= !raw;
+			termios.iflag &= !ldisc::ICRNL;
+			termios.oflag &= !ldisc::OPOST;
+		}
+		"raw" | "cooked" =>
+		{
+			termios.lflag |= raw;
+			termios.iflag |= ldisc::ICRNL;
+			termios.oflag |= ldisc::OPOST;
+		}
+		_ =>
+		{
+			let flag = LFLAGS.iter().find(|(flag, _)| *flag == name).map(|(_, flag)| *flag);
+			match flag
+			{
+				Some(flag) if set => termios.lflag |= flag,
+				Some(flag) => termios.lflag &= !flag,
+				None =>
+				{
+					crate::println!("stty: invalid argument {}", arg);
+					command::fail();
+					return;
+				}
+			}
 		}
-		print::print_to_vga();
 	}
+	with_ldisc(|ldisc| ldisc.set_termios(termios, false));
 }
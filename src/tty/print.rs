@@ -1,5 +1,4 @@
 use core::fmt;
-use crate::vga;
 
 #[macro_export]
 macro_rules! print
@@ -14,60 +13,23 @@ macro_rules! println
     ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
 }
 
-pub fn print_to_vga()
-{
-	vga::Buffer::clear();
-	unsafe
-	{
-		let len = super::TTYS[super::CURRENT_TTY].pos + super::TTYS[super::CURRENT_TTY].pos_offset;
-		crate::vga_print!("{}", core::str::from_utf8(&super::TTYS[super::CURRENT_TTY].chars[0..len]).unwrap());
-	}
-}
-
-pub fn print_byte_to_vga(byte: u8)
-{
-	unsafe
-	{
-		vga::W.write_byte(byte);
-	}
-}
-
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments)
 {
-	unsafe
-	{
-		use core::fmt::Write;
-		W.write_fmt(args).unwrap();
-		vga::W.write_fmt(args).unwrap();
-	}
-}
-
-pub struct Writer {}
-
-impl Writer // base stuff
-{
-	pub fn write_byte(&mut self, byte: u8)
+	use core::fmt::Write;
+	if super::serial::is_active()
 	{
-		super::write_byte(byte, false);
-	}
-
-	pub fn write_string(&mut self, s: &str)
-	{
-		for byte in s.bytes()
-		{
-			self.write_byte(byte);
-		}
+		super::serial::_print(args);
+		return;
 	}
+	let mut ttys = super::TTYS.lock();
+	let tty = ttys.output();
+	ttys.with_writer(tty, |writer| writer.write_fmt(args).unwrap());
 }
 
-impl fmt::Write for Writer
+// to a given tty, drawn only if it is the one shown
+pub fn print_to(tty: usize, args: fmt::Arguments)
 {
-	fn write_str(&mut self, s: &str) -> fmt::Result
-	{
-		self.write_string(s);
-		Ok(())
-	}
+	use core::fmt::Write;
+	super::TTYS.lock().with_writer(tty, |writer| writer.write_fmt(args).unwrap());
 }
-
-pub static mut W: Writer = Writer {};
@@ -0,0 +1,553 @@
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::multiboot;
+use crate::sync::IrqSpinlock;
+use super::command::{self, Arg, Args, Kind};
+
+// a script loaded as a multiboot module, run once the shells are up
+crate::boot_param!(INIT: Option<&'static str> = None, "init");
+
+// the language of the shell: the commands separated by ";", "&&" and "||",
+// "if" and "for", the variables and the quotes; there are no files to pipe
+// or redirect the commands to
+const KEYWORDS: [&str; 8] = ["if", "then", "else", "fi", "for", "in", "do", "done"];
+
+// shared by all the ttys
+static VARIABLES: IrqSpinlock<Vec<(String, String)>> = IrqSpinlock::new(Vec::new());
+// $?
+static LAST_FAILED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token
+{
+	// as typed, the quotes are removed when it is expanded
+	Word(String),
+	Separator,
+	And,
+	Or,
+	Pipe,
+	Redirect(&'static str)
+}
+
+pub enum Error
+{
+	Syntax(String),
+	Unsupported(&'static str),
+	UnterminatedQuote
+}
+
+impl fmt::Display for Error
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match self
+		{
+			Error::Syntax(near) => write!(f, "syntax error near {}", near),
+			Error::Unsupported(operator) => write!(f, "{} is not supported, the commands have no file descriptors yet", operator),
+			Error::UnterminatedQuote => write!(f, "unterminated quote")
+		}
+	}
+}
+
+enum Node
+{
+	Simple(Vec<String>),
+	And(Box<Node>, Box<Node>),
+	Or(Box<Node>, Box<Node>),
+	If
+	{
+		condition: Vec<Node>,
+		then: Vec<Node>,
+		otherwise: Vec<Node>
+	},
+	For
+	{
+		name: String,
+		words: Vec<String>,
+		body: Vec<Node>
+	}
+}
+
+fn is_name(word: &str) -> bool
+{
+	!word.is_empty() && !word.starts_with(|c: char| c.is_ascii_digit())
+		&& word.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, Error>
+{
+	let mut tokens = Vec::new();
+	let mut word = String::new();
+	let mut in_word = false;
+	let mut quote = None;
+	let mut chars = text.chars().peekable();
+
+	while let Some(c) = chars.next()
+	{
+		if let Some(closing) = quote
+		{
+			word.push(c);
+			if c == closing
+			{
+				quote = None;
+			}
+			else if c == '\\' && closing == '"'
+			{
+				word.extend(chars.next());
+			}
+			continue;
+		}
+		let token = match c
+		{
+			'\'' | '"' =>
+			{
+				quote = Some(c);
+				word.push(c);
+				in_word = true;
+				continue;
+			}
+			'\\' =>
+			{
+				word.push(c);
+				word.extend(chars.next());
+				in_word = true;
+				continue;
+			}
+			' ' | '\t' => None,
+			'#' if !in_word =>
+			{
+				while chars.next_if(|c| *c != '\n').is_some() {}
+				None
+			}
+			';' | '\n' | '\r' => Some(Token::Separator),
+			'&' if chars.next_if_eq(&'&').is_some() => Some(Token::And),
+			'&' => return Err(Error::Unsupported("&")),
+			'|' if chars.next_if_eq(&'|').is_some() => Some(Token::Or),
+			'|' => Some(Token::Pipe),
+			'>' if chars.next_if_eq(&'>').is_some() => Some(Token::Redirect(">>")),
+			'>' => Some(Token::Redirect(">")),
+			'<' => Some(Token::Redirect("<")),
+			_ =>
+			{
+				word.push(c);
+				in_word = true;
+				continue;
+			}
+		};
+		if in_word
+		{
+			tokens.push(Token::Word(core::mem::take(&mut word)));
+			in_word = false;
+		}
+		tokens.extend(token);
+	}
+	if quote.is_some()
+	{
+		return Err(Error::UnterminatedQuote);
+	}
+	if in_word
+	{
+		tokens.push(Token::Word(word));
+	}
+	Ok(tokens)
+}
+
+struct Parser
+{
+	tokens: Vec<Token>,
+	pos: usize
+}
+
+impl Parser
+{
+	fn peek(&self) -> Option<&Token>
+	{
+		self.tokens.get(self.pos)
+	}
+
+	fn keyword(&self) -> Option<&'static str>
+	{
+		match self.peek()
+		{
+			Some(Token::Word(word)) => KEYWORDS.iter().find(|keyword| *keyword == word).copied(),
+			_ => None
+		}
+	}
+
+	fn unexpected(&self) -> Error
+	{
+		Error::Syntax(match self.peek()
+		{
+			None => "end of file".to_string(),
+			Some(Token::Word(word)) => word.clone(),
+			Some(Token::Separator) => "newline".to_string(),
+			Some(Token::And) => "&&".to_string(),
+			Some(Token::Or) => "||".to_string(),
+			Some(Token::Pipe) => "|".to_string(),
+			Some(Token::Redirect(operator)) => operator.to_string()
+		})
+	}
+
+	fn skip_separators(&mut self)
+	{
+		while self.peek() == Some(&Token::Separator)
+		{
+			self.pos += 1;
+		}
+	}
+
+	fn expect(&mut self, keyword: &str) -> Result<(), Error>
+	{
+		if self.keyword() != Some(keyword)
+		{
+			return Err(self.unexpected());
+		}
+		self.pos += 1;
+		Ok(())
+	}
+
+	// the commands up to one of the keywords, or to the end for the script
+	fn list(&mut self, ends: &[&str]) -> Result<Vec<Node>, Error>
+	{
+		let mut nodes = Vec::new();
+
+		loop
+		{
+			self.skip_separators();
+			if self.peek().is_none()
+			{
+				return if ends.is_empty() { Ok(nodes) } else { Err(self.unexpected()) };
+			}
+			if self.keyword().is_some_and(|keyword| ends.contains(&keyword))
+			{
+				return Ok(nodes);
+			}
+			nodes.push(self.and_or()?);
+			if !matches!(self.peek(), None | Some(Token::Separator))
+			{
+				return Err(self.unexpected());
+			}
+		}
+	}
+
+	fn and_or(&mut self) -> Result<Node, Error>
+	{
+		let mut node = self.command()?;
+
+		loop
+		{
+			let and = match self.peek()
+			{
+				Some(Token::And) => true,
+				Some(Token::Or) => false,
+				_ => return Ok(node)
+			};
+			self.pos += 1;
+			self.skip_separators();
+			let right = Box::new(self.command()?);
+			node = if and { Node::And(Box::new(node), right) } else { Node::Or(Box::new(node), right) };
+		}
+	}
+
+	fn command(&mut self) -> Result<Node, Error>
+	{
+		match self.keyword()
+		{
+			Some("if") =>
+			{
+				self.pos += 1;
+				let condition = self.list(&["then"])?;
+				self.expect("then")?;
+				let then = self.list(&["else", "fi"])?;
+				let otherwise = if self.keyword() == Some("else")
+				{
+					self.pos += 1;
+					self.list(&["fi"])?
+				}
+				else
+				{
+					Vec::new()
+				};
+				self.expect("fi")?;
+				return Ok(Node::If { condition, then, otherwise });
+			}
+			Some("for") =>
+			{
+				self.pos += 1;
+				let name = match self.peek()
+				{
+					Some(Token::Word(name)) if is_name(name) => name.clone(),
+					_ => return Err(self.unexpected())
+				};
+				self.pos += 1;
+				self.expect("in")?;
+				let mut words = Vec::new();
+				while let Some(Token::Word(word)) = self.peek()
+				{
+					words.push(word.clone());
+					self.pos += 1;
+				}
+				self.skip_separators();
+				self.expect("do")?;
+				let body = self.list(&["done"])?;
+				self.expect("done")?;
+				return Ok(Node::For { name, words, body });
+			}
+			Some(_) => return Err(self.unexpected()),
+			None => {}
+		}
+
+		let mut words = Vec::new();
+		loop
+		{
+			match self.peek()
+			{
+				Some(Token::Word(word)) => words.push(word.clone()),
+				Some(Token::Pipe) => return Err(Error::Unsupported("|")),
+				Some(Token::Redirect(operator)) => return Err(Error::Unsupported(operator)),
+				_ => break
+			}
+			self.pos += 1;
+		}
+		if words.is_empty()
+		{
+			return Err(self.unexpected());
+		}
+		Ok(Node::Simple(words))
+	}
+}
+
+fn get(name: &str) -> Option<String>
+{
+	VARIABLES.lock().iter().find(|(variable, _)| variable == name).map(|(_, value)| value.clone())
+}
+
+fn set(name: &str, value: &str)
+{
+	let mut variables = VARIABLES.lock();
+
+	match variables.iter_mut().find(|(variable, _)| variable == name)
+	{
+		Some((_, old)) => *old = value.to_string(),
+		None => variables.push((name.to_string(), value.to_string()))
+	}
+}
+
+fn unset(name: &str)
+{
+	VARIABLES.lock().retain(|(variable, _)| variable != name);
+}
+
+// $NAME, ${NAME} or $?
+fn expand_variable(chars: &mut core::iter::Peekable<core::str::Chars>, out: &mut String)
+{
+	if chars.next_if_eq(&'?').is_some()
+	{
+		out.push(if LAST_FAILED.load(Ordering::Relaxed) { '1' } else { '0' });
+		return;
+	}
+	let mut name = String::new();
+	if chars.next_if_eq(&'{').is_some()
+	{
+		name.extend(chars.by_ref().take_while(|c| *c != '}'));
+	}
+	else
+	{
+		while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_')
+		{
+			name.push(c);
+		}
+	}
+	if name.is_empty()
+	{
+		out.push('$');
+		return;
+	}
+	out.push_str(&get(&name).unwrap_or_default());
+}
+
+// the variables are replaced, except between single quotes, and the quotes
+// are removed
+fn expand(word: &str) -> String
+{
+	let mut out = String::new();
+	let mut quote = None;
+	let mut chars = word.chars().peekable();
+
+	while let Some(c) = chars.next()
+	{
+		match (quote, c)
+		{
+			(Some('\''), '\'') | (Some('"'), '"') => quote = None,
+			(Some('\''), _) => out.push(c),
+			(None, '\'' | '"') => quote = Some(c),
+			(_, '\\') => out.extend(chars.next()),
+			(_, '$') => expand_variable(&mut chars, &mut out),
+			_ => out.push(c)
+		}
+	}
+	out
+}
+
+// the values of the unquoted words are split on the spaces
+fn expand_fields(word: &str) -> Vec<String>
+{
+	let value = expand(word);
+
+	if word.contains(['\'', '"'])
+	{
+		return alloc::vec![value];
+	}
+	value.split(' ').filter(|field| !field.is_empty()).map(String::from).collect()
+}
+
+// "NAME=value" alone sets a variable
+fn assignment(word: &str) -> Option<(&str, &str)>
+{
+	word.split_once('=').filter(|(name, _)| is_name(name))
+}
+
+fn simple(words: &[String]) -> bool
+{
+	if words.iter().all(|word| assignment(word).is_some())
+	{
+		for word in words
+		{
+			if let Some((name, value)) = assignment(word)
+			{
+				set(name, &expand(value));
+			}
+		}
+		return true;
+	}
+	let words = words.iter().map(|word| expand(word)).collect::<Vec<String>>();
+	command::execute_words(&words)
+}
+
+fn run_node(node: &Node) -> bool
+{
+	let ok = match node
+	{
+		Node::Simple(words) => simple(words),
+		Node::And(left, right) => run_node(left) && run_node(right),
+		Node::Or(left, right) => run_node(left) || run_node(right),
+		Node::If { condition, then, otherwise } =>
+		{
+			if run_nodes(condition) { run_nodes(then) } else { run_nodes(otherwise) }
+		}
+		Node::For { name, words, body } =>
+		{
+			let mut ok = true;
+			for value in words.iter().flat_map(|word| expand_fields(word))
+			{
+				set(name, &value);
+				ok = run_nodes(body);
+			}
+			ok
+		}
+	};
+	LAST_FAILED.store(!ok, Ordering::Relaxed);
+	ok
+}
+
+fn run_nodes(nodes: &[Node]) -> bool
+{
+	let mut ok = true;
+
+	for node in nodes
+	{
+		ok = run_node(node);
+	}
+	ok
+}
+
+// a line typed at the prompt, or a whole script
+pub fn run(text: &str) -> bool
+{
+	let nodes = tokenize(text).and_then(|tokens| Parser { tokens, pos: 0 }.list(&[]));
+
+	match nodes
+	{
+		Ok(nodes) => run_nodes(&nodes),
+		Err(error) =>
+		{
+			crate::println!("sh: {}", error);
+			LAST_FAILED.store(true, Ordering::Relaxed);
+			false
+		}
+	}
+}
+
+// the scripts are the multiboot modules, named by their command line
+pub fn run_script(name: &str) -> bool
+{
+	let module = match multiboot::find_module(name)
+	{
+		Some(module) => module,
+		None =>
+		{
+			crate::println!("sh: {}: no such script", name);
+			return false;
+		}
+	};
+	match core::str::from_utf8(module.bytes())
+	{
+		Ok(text) => run(text),
+		Err(_) =>
+		{
+			crate::println!("sh: {}: not a text file", name);
+			false
+		}
+	}
+}
+
+// init=<script>, for the tests run at boot
+pub fn run_init()
+{
+	if let Some(name) = INIT.get()
+	{
+		let ok = run_script(name);
+		crate::logln!("[{}] ran the init script {}", crate::ok_fail(ok), name);
+	}
+}
+
+crate::shell_command!("sh", &[Arg::optional("script", Kind::Str)], "run a script, or list them", sh);
+crate::shell_command!("echo", &[Arg::optional("text", Kind::Rest)], "print the text", |args| crate::println!("{}", args.str(0).unwrap_or("")));
+crate::shell_command!("set", &[], "show the variables, NAME=value sets one", |_| print_variables());
+crate::shell_command!("unset", &[Arg::required("name", Kind::Str)], "remove a variable", |args| unset(args.str(0).unwrap_or("")));
+crate::shell_command!("true", &[], "succeed", |_| {});
+crate::shell_command!("false", &[], "fail", |_| command::fail());
+
+fn sh(args: &Args)
+{
+	let name = match args.str(0)
+	{
+		Some(name) => name,
+		None =>
+		{
+			for module in multiboot::boot_info().modules()
+			{
+				crate::println!("{:16} {} bytes", module.name(), module.bytes().len());
+			}
+			return;
+		}
+	};
+	if !run_script(name)
+	{
+		command::fail();
+	}
+}
+
+// copied first, nothing is printed with the lock held
+fn print_variables()
+{
+	let variables = VARIABLES.lock().clone();
+
+	for (name, value) in variables
+	{
+		crate::println!("{}={}", name, value);
+	}
+}
@@ -0,0 +1,273 @@
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::serial;
+use crate::sync::IrqSpinlock;
+use super::history::History;
+use super::ldisc::{self, Echo, Event, Input, LineDiscipline};
+use super::shell;
+
+// ttyS0, on COM1
+const INDEX: usize = 0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Escape
+{
+	None,
+	Start, // after ESC
+	Sequence // after the CSI or SS3 introducer
+}
+
+enum Key
+{
+	Input(Input),
+	Shell(shell::Key)
+}
+
+// a shell on COM1, next to the ones on the screen: the terminal on the other
+// side draws the line, the output of the commands goes back to the port
+struct SerialTty
+{
+	has_init: bool,
+	ldisc: LineDiscipline,
+	history: History,
+	// the characters on the right of the terminal cursor
+	cursor_offset: usize,
+	// only the arrows, home and end of the escape sequences sent by the
+	// terminal are used
+	escape: Escape,
+	// "\r\n" is a single line return
+	last_cr: bool
+}
+
+static TTY: IrqSpinlock<SerialTty> = IrqSpinlock::new(SerialTty
+{
+	has_init: false,
+	ldisc: LineDiscipline::new(),
+	history: History::new(),
+	cursor_offset: 0,
+	escape: Escape::None,
+	last_cr: false
+});
+
+// set while a command typed on COM1 runs, print! is sent to the port
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+pub fn is_active() -> bool
+{
+	ACTIVE.load(Ordering::Acquire)
+}
+
+// the terminal needs "\r\n" to go back to the first column, unless onlcr is off
+struct Writer;
+
+impl fmt::Write for Writer
+{
+	fn write_str(&mut self, s: &str) -> fmt::Result
+	{
+		let oflag = TTY.lock().ldisc.termios.oflag;
+		let onlcr = oflag & (ldisc::OPOST | ldisc::ONLCR) == ldisc::OPOST | ldisc::ONLCR;
+		let mut lines = s.split('\n');
+
+		if let Some(line) = lines.next()
+		{
+			serial::send(INDEX, line.as_bytes());
+		}
+		for line in lines
+		{
+			serial::send(INDEX, if onlcr { b"\r\n" } else { b"\n" });
+			serial::send(INDEX, line.as_bytes());
+		}
+		Ok(())
+	}
+}
+
+pub fn _print(args: fmt::Arguments)
+{
+	use core::fmt::Write;
+	Writer.write_fmt(args).unwrap();
+}
+
+// insert and delete character sequences, for the edits in the middle of the line
+struct SerialEcho<'a>
+{
+	cursor_offset: &'a mut usize
+}
+
+impl Echo for SerialEcho<'_>
+{
+	fn insert(&mut self, byte: u8)
+	{
+		if *self.cursor_offset > 0
+		{
+			serial::send(INDEX, b"\x1B[@");
+		}
+		serial::send(INDEX, &[byte]);
+	}
+
+	fn erase(&mut self)
+	{
+		if *self.cursor_offset > 0
+		{
+			serial::send(INDEX, b"\x08\x1B[P");
+		}
+		else
+		{
+			serial::send(INDEX, b"\x08 \x08");
+		}
+	}
+
+	fn move_left(&mut self)
+	{
+		*self.cursor_offset += 1;
+		serial::send(INDEX, b"\x1B[D");
+	}
+
+	fn move_right(&mut self)
+	{
+		*self.cursor_offset -= 1;
+		serial::send(INDEX, b"\x1B[C");
+	}
+}
+
+pub fn init() -> bool
+{
+	serial::open(INDEX)
+}
+
+pub fn prompt()
+{
+	let first_prompt = !core::mem::replace(&mut TTY.lock().has_init, true);
+
+	if first_prompt
+	{
+		_print(format_args!("elsOS ttyS0\n\n"));
+	}
+	_print(format_args!("\x1B[30;47mWas esch los ? >\x1B[39;49m "));
+}
+
+pub fn with_ldisc<T>(f: impl FnOnce(&mut LineDiscipline) -> T) -> T
+{
+	f(&mut TTY.lock().ldisc)
+}
+
+pub fn with_history<T>(f: impl FnOnce(&mut History) -> T) -> T
+{
+	f(&mut TTY.lock().history)
+}
+
+fn line_return(command: Option<&[u8]>)
+{
+	TTY.lock().cursor_offset = 0;
+	_print(format_args!("\n"));
+	if let Some(command) = command
+	{
+		ACTIVE.store(true, Ordering::Release);
+		shell::run(command);
+		ACTIVE.store(false, Ordering::Release);
+		prompt();
+	}
+}
+
+// the arrows, home and end, from "ESC [ C" or "ESC O C", and the tab
+fn decode(tty: &mut SerialTty, byte: u8) -> Option<Key>
+{
+	let last_cr = core::mem::replace(&mut tty.last_cr, byte == b'\r');
+
+	match tty.escape
+	{
+		Escape::Start =>
+		{
+			tty.escape = if byte == b'[' || byte == b'O' { Escape::Sequence } else { Escape::None };
+			None
+		}
+		Escape::Sequence =>
+		{
+			if !(0x40..=0x7e).contains(&byte)
+			{
+				return None;
+			}
+			tty.escape = Escape::None;
+			match byte
+			{
+				b'A' => Some(Key::Shell(shell::Key::Up)),
+				b'B' => Some(Key::Shell(shell::Key::Down)),
+				b'C' => Some(Key::Input(Input::Right)),
+				b'D' => Some(Key::Input(Input::Left)),
+				b'H' => Some(Key::Input(Input::Home)),
+				b'F' => Some(Key::Input(Input::End)),
+				_ => None
+			}
+		}
+		Escape::None => match byte
+		{
+			crate::vga::ESCAPE_START =>
+			{
+				tty.escape = Escape::Start;
+				None
+			}
+			b'\n' if last_cr => None,
+			b'\t' if !tty.ldisc.reading => Some(Key::Shell(shell::Key::Tab)),
+			_ => Some(Key::Input(Input::Byte(byte)))
+		}
+	}
+}
+
+fn handle_byte(byte: u8)
+{
+	let mut line = [b'\0'; ldisc::LINE_SIZE];
+	let (event, candidates) =
+	{
+		let mut tty = TTY.lock();
+		let key = decode(&mut tty, byte);
+		let SerialTty { ldisc, history, cursor_offset, .. } = &mut *tty;
+		let echo = &mut SerialEcho { cursor_offset };
+
+		match key
+		{
+			Some(Key::Input(input)) => (ldisc.receive(input, echo, &mut line), None),
+			Some(Key::Shell(key)) if !ldisc.reading => (Event::None, shell::edit(key, ldisc, history, echo)),
+			Some(Key::Shell(_)) | None => return
+		}
+	};
+
+	// the line is drawn again under the names
+	if let Some(candidates) = candidates
+	{
+		line_return(None);
+		shell::list(&candidates);
+		prompt();
+		let mut tty = TTY.lock();
+		let SerialTty { ldisc, cursor_offset, .. } = &mut *tty;
+		ldisc.redraw(&mut SerialEcho { cursor_offset });
+	}
+
+	match event
+	{
+		Event::Line(len) => line_return(Some(&line[..len])),
+		Event::Read => line_return(None),
+		Event::Signal(_) =>
+		{
+			let reading =
+			{
+				let mut tty = TTY.lock();
+				tty.history.rewind();
+				tty.ldisc.reading
+			};
+			line_return(None);
+			if !reading
+			{
+				prompt();
+			}
+		}
+		Event::Eof | Event::None => {}
+	}
+}
+
+// from the IRQ4 handler, the commands run here like the keyboard ones
+pub fn input()
+{
+	while let Some(byte) = serial::receive(INDEX)
+	{
+		handle_byte(byte);
+	}
+}
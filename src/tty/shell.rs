@@ -0,0 +1,157 @@
+use super::command::{self, Arg, Args};
+use super::script;
+use super::history::History;
+use super::ldisc::{self, Echo, LineDiscipline};
+
+// longer than the command names
+const WORD_SIZE: usize = 16;
+
+// the keys the shell handles itself while nobody reads the tty
+pub enum Key
+{
+	Up,
+	Down,
+	Tab
+}
+
+// the word a tab could not complete, its commands are listed under the line
+pub struct Candidates
+{
+	word: [u8; WORD_SIZE],
+	len: usize
+}
+
+impl Candidates
+{
+	fn names(&self) -> impl Iterator<Item = &'static str> + '_
+	{
+		command::commands().iter().map(|command| command.name).filter(|name| name.as_bytes().starts_with(&self.word[..self.len]))
+	}
+}
+
+fn common_len(a: &str, b: &str) -> usize
+{
+	a.bytes().zip(b.bytes()).take_while(|(a, b)| a == b).count()
+}
+
+// the command name before the cursor is completed as far as it is the same
+// for all the commands starting with it
+fn complete(ldisc: &mut LineDiscipline, echo: &mut dyn Echo) -> Option<Candidates>
+{
+	let (word_len, name, common, count) =
+	{
+		let word = ldisc.before_cursor();
+		if word.contains(&b' ')
+		{
+			return None;
+		}
+		let mut names = command::commands().iter().map(|command| command.name).filter(|name| name.as_bytes().starts_with(word));
+		let first = names.next()?;
+		let (common, count) = names.fold((first.len(), 1), |(common, count), name| (common.min(common_len(first, name)), count + 1));
+		(word.len(), first, common, count)
+	};
+
+	ldisc.insert_bytes(&name.as_bytes()[word_len..common], echo);
+	if count == 1
+	{
+		ldisc.insert_bytes(b" ", echo);
+		return None;
+	}
+	if common > word_len
+	{
+		return None;
+	}
+	let mut candidates = Candidates { word: [0; WORD_SIZE], len: word_len };
+	candidates.word[..word_len].copy_from_slice(&name.as_bytes()[..word_len]);
+	Some(candidates)
+}
+
+// up and down recall the history, the candidates of an ambiguous tab are
+// returned to be listed once the tty is unlocked
+pub fn edit(key: Key, ldisc: &mut LineDiscipline, history: &mut History, echo: &mut dyn Echo) -> Option<Candidates>
+{
+	match key
+	{
+		Key::Up =>
+		{
+			if let Some(line) = history.previous()
+			{
+				ldisc.replace(line, echo);
+			}
+			None
+		}
+		Key::Down =>
+		{
+			if let Some(line) = history.next()
+			{
+				ldisc.replace(line, echo);
+			}
+			None
+		}
+		Key::Tab => complete(ldisc, echo)
+	}
+}
+
+pub fn list(candidates: &Candidates)
+{
+	for name in candidates.names()
+	{
+		crate::print!("{}  ", name);
+	}
+	crate::println!();
+}
+
+// the line is expanded and kept in the history of the tty, then executed
+pub fn run(typed: &[u8])
+{
+	let mut expanded = [b'\0'; ldisc::LINE_SIZE];
+	let result = super::with_history(|history|
+	{
+		let result = history.expand(typed, &mut expanded);
+		if let Ok(len) = result
+		{
+			history.add(&expanded[..len]);
+		}
+		result
+	});
+
+	match result
+	{
+		Ok(len) =>
+		{
+			let line = core::str::from_utf8(&expanded[..len]).unwrap_or("");
+			if expanded[..len] != *typed
+			{
+				crate::println!("{}", line);
+			}
+			script::run(line);
+		}
+		Err(event) => crate::println!("{}: event not found", core::str::from_utf8(&typed[event]).unwrap_or("!"))
+	}
+}
+
+crate::shell_command!("history", &[Arg::flag("-c")], "show the last commands, !! and !<n> run them again", history);
+
+// the lines are copied one by one, printing with the tty locked would deadlock
+fn history(args: &Args)
+{
+	if args.flag(0)
+	{
+		super::with_history(|history| history.clear());
+		return;
+	}
+
+	let (first, last) = super::with_history(|history| (history.first(), history.last()));
+
+	for number in first..=last
+	{
+		let mut line = [b'\0'; ldisc::LINE_SIZE];
+		let len = super::with_history(|history|
+		{
+			let entry = history.get(number).unwrap_or(&[]);
+			line[..entry.len()].copy_from_slice(entry);
+			entry.len()
+		});
+		crate::println!("{:>5}  {}", number, core::str::from_utf8(&line[..len]).unwrap_or(""));
+	}
+}
@@ -37,12 +37,12 @@ impl ColorCode
 		ColorCode((background << 4) | foreground)
 	}
 
-	pub fn fg(self) -> u8
+	pub const fn fg(self) -> u8
 	{
 		self.0 & 0xf
 	}
 
-	pub fn bg(self) -> u8
+	pub const fn bg(self) -> u8
 	{
 		self.0 >> 4
 	}
@@ -57,3 +57,56 @@ impl ColorCode
 		ColorCode::new(Color::White, Color::Red)
 	}
 }
+
+// the 16 VGA text colors
+pub const PALETTE: [u32; 16] =
+[
+	0x000000, 0x0000aa, 0x00aa00, 0x00aaaa, 0xaa0000, 0xaa00aa, 0xaa5500, 0xaaaaaa,
+	0x555555, 0x5555ff, 0x55ff55, 0x55ffff, 0xff5555, 0xff55ff, 0xffff55, 0xffffff
+];
+
+// the ansi colors are ordered as black, red, green, yellow, blue, magenta, cyan, white
+const ANSI: [Color; 8] =
+[
+	Color::Black, Color::Red, Color::Green, Color::Brown,
+	Color::Blue, Color::Magenta, Color::Cyan, Color::LightGray
+];
+
+// levels of the 6x6x6 color cube of the 256 colors palette
+const CUBE_LEVELS: [u32; 6] = [0x00, 0x5f, 0x87, 0xaf, 0xd7, 0xff];
+
+pub fn from_ansi(index: u8, bright: bool) -> u8
+{
+	ANSI[index as usize & 7] as u8 | if bright { 8 } else { 0 }
+}
+
+// the VGA color closest to an 0xrrggbb color
+pub fn nearest(rgb: u32) -> u8
+{
+	let distance = |color: u32|
+	{
+		let channel = |shift: u32| ((rgb >> shift & 0xff) as i32 - (color >> shift & 0xff) as i32).pow(2);
+		channel(16) + channel(8) + channel(0)
+	};
+	(0..PALETTE.len()).min_by_key(|&i| distance(PALETTE[i])).unwrap_or(0) as u8
+}
+
+// 0-15 are the ansi colors, then a color cube and 24 levels of gray
+pub fn from_256(index: u8) -> u8
+{
+	match index
+	{
+		0..=7 => from_ansi(index, false),
+		8..=15 => from_ansi(index - 8, true),
+		16..=231 =>
+		{
+			let index = (index - 16) as usize;
+			nearest(CUBE_LEVELS[index / 36] << 16 | CUBE_LEVELS[index / 6 % 6] << 8 | CUBE_LEVELS[index % 6])
+		}
+		_ =>
+		{
+			let level = 8 + 10 * (index - 232) as u32;
+			nearest(level << 16 | level << 8 | level)
+		}
+	}
+}
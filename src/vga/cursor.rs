@@ -1,5 +1,5 @@
 use crate::arch::port::{inb, outb};
-use crate::vga::BUFFER_WIDTH;
+use crate::vga::{self, framebuffer};
 
 const CRT_ADDR_REG: u16 = 0x3D4;
 const CRT_DATA_REG: u16 = 0x3D5;
@@ -10,15 +10,18 @@ const CURSOR_END_REG: u8 = 0x0B;
 const CURSOR_HIGH_REG: u8 = 0x0E;
 const CURSOR_LOW_REG: u8 = 0x0F;
 
-pub struct Cursor
-{
-	pub offset: usize
-}
+pub struct Cursor;
 
 impl Cursor
 {
+	// the framebuffer console only draws a block cursor
 	pub fn init(cursor_start: u8, cursor_end: u8)
 	{
+		if let Some(mut console) = framebuffer::console()
+		{
+			console.show_cursor(true);
+			return;
+		}
 		outb(CRT_ADDR_REG, CURSOR_START_REG);
 		outb(CRT_DATA_REG, (inb(CRT_DATA_REG) & 0xC0) | cursor_start);
 	
@@ -28,20 +31,23 @@ impl Cursor
 	
 	pub fn disable()
 	{
+		if let Some(mut console) = framebuffer::console()
+		{
+			console.show_cursor(false);
+			return;
+		}
 		outb(CRT_ADDR_REG, 0x0A);
 		outb(CRT_DATA_REG, 0x20);
 	}
 	
 	pub fn move_to(x: u16, y: u16)
 	{
-		let mut pos: u16 = y * BUFFER_WIDTH as u16 + x;
+		let pos: u16 = y * vga::width() as u16 + x;
 
-		unsafe
+		if let Some(mut console) = framebuffer::console()
 		{
-			if CURSOR.offset as u16 <= pos
-			{
-				pos -= CURSOR.offset as u16;
-			}
+			console.move_cursor(pos as usize);
+			return;
 		}
 		outb(CRT_ADDR_REG, CURSOR_LOW_REG);
 		outb(CRT_DATA_REG, pos as u8);
@@ -53,20 +59,17 @@ impl Cursor
 	{
 		let mut pos: u16;
 	
-		outb(CRT_ADDR_REG, CURSOR_LOW_REG);
-		pos = inb(CRT_DATA_REG) as u16;
-		outb(CRT_ADDR_REG, CURSOR_HIGH_REG);
-		pos |= (inb(CRT_DATA_REG) as u16) << 8;
-		unsafe
+		if let Some(console) = framebuffer::console()
+		{
+			pos = console.cursor() as u16;
+		}
+		else
 		{
-			pos += CURSOR.offset as u16;
+			outb(CRT_ADDR_REG, CURSOR_LOW_REG);
+			pos = inb(CRT_DATA_REG) as u16;
+			outb(CRT_ADDR_REG, CURSOR_HIGH_REG);
+			pos |= (inb(CRT_DATA_REG) as u16) << 8;
 		}
-		(pos % BUFFER_WIDTH as u16, pos / BUFFER_WIDTH as u16)
+		(pos % vga::width() as u16, pos / vga::width() as u16)
 	}
 }
-
-pub static mut CURSOR: Cursor = Cursor
-{
-	offset: 0
-};
-
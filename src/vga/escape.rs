@@ -1,94 +1,270 @@
-use crate::vga::Writer;
-use crate::vga::colors::{Color, ColorCode};
+use crate::vga::{self, Writer};
+use crate::vga::colors::{self, ColorCode};
 
+const MAX_PARAMS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State
+{
+	Escape, // after ESC
+	Csi // after ESC [
+}
+
+// the parser of the escape sequences, with the graphic rendition it sets
 pub struct Escaper
 {
-	pub foreground: bool, // first number is 3
-	pub background: bool, // first number is 4
-	pub color: ColorCode,
+	pub state: State,
+	pub params: [u16; MAX_PARAMS],
+	pub count: usize, // index of the parameter being read
+	pub saved: (usize, usize),
+	pub foreground: u8,
+	pub background: u8,
+	pub bold: bool,
+	pub reverse: bool
 }
 
-impl Writer //color escape commands
+impl Escaper
 {
-	fn default_color(&self) -> Color
+	pub const fn new() -> Escaper
+	{
+		let color = ColorCode::default();
+
+		Escaper
+		{
+			state: State::Escape,
+			params: [0; MAX_PARAMS],
+			count: 0,
+			saved: (0, 0),
+			foreground: color.fg(),
+			background: color.bg(),
+			bold: false,
+			reverse: false
+		}
+	}
+
+	// a missing or zero parameter takes the default value
+	fn param(&self, index: usize, default: u16) -> u16
 	{
-		if self.cmd.foreground
+		match self.params[index]
 		{
-			Color::White
+			0 => default,
+			value => value
+		}
+	}
+
+	fn color_code(&self) -> ColorCode
+	{
+		let foreground = if self.bold { self.foreground | 8 } else { self.foreground };
+
+		if self.reverse
+		{
+			ColorCode::new_i(self.background, foreground)
 		}
 		else
 		{
-			Color::Black
+			ColorCode::new_i(foreground, self.background)
 		}
 	}
 
-	fn colors(&mut self, byte: u8)
+	fn reset_rendition(&mut self)
+	{
+		let default = Escaper::new();
+
+		self.foreground = default.foreground;
+		self.background = default.background;
+		self.bold = false;
+		self.reverse = false;
+	}
+
+	// 38;5;n and 38;2;r;g;b, returns the color and the number of parameters read
+	fn extended_color(params: &[u16]) -> (Option<u8>, usize)
 	{
-		if self.cmd.foreground ^ self.cmd.background
+		match params
 		{
-			let color = match byte
+			[5, index, ..] => (Some(colors::from_256(*index as u8)), 2),
+			[2, red, green, blue, ..] =>
 			{
-				b'0' => Color::Black,
-				b'1' => Color::Red,
-				b'2' => Color::Green,
-				b'3' => Color::Brown,
-				b'4' => Color::Blue,
-				b'5' => Color::Magenta,
-				b'6' => Color::Cyan,
-				b'7' => Color::White,
-				_ => self.default_color(),
-			};
-			if self.cmd.foreground
-			{
-				self.cmd.color = self.color_code;
-				self.color_code = ColorCode::new_i(color as u8, self.color_code.bg());
-				self.cmd.background = true;
-			}
-			else if self.cmd.background
-			{
-				self.cmd.color = self.color_code;
-				self.color_code = ColorCode::new_i(self.color_code.fg(), color as u8);
-				self.cmd.foreground = true;
+				let channel = |value: u16| value.min(0xff) as u32;
+				(Some(colors::nearest(channel(*red) << 16 | channel(*green) << 8 | channel(*blue))), 4)
 			}
+			_ => (None, params.len())
 		}
-		else if !self.cmd.foreground && !self.cmd.background
+	}
+
+	// select graphic rendition, the bright colors are the upper 8 VGA colors
+	fn rendition(&mut self)
+	{
+		let params = self.params;
+		let params = &params[..=self.count];
+		let mut i = 0;
+
+		while i < params.len()
 		{
-			match byte
+			match params[i]
 			{
-				b'3' => self.cmd.foreground = true,
-				b'4' => self.cmd.background = true,
-				_ => {},
+				0 => self.reset_rendition(),
+				1 => self.bold = true,
+				22 => self.bold = false,
+				7 => self.reverse = true,
+				27 => self.reverse = false,
+				code @ 30..=37 => self.foreground = colors::from_ansi((code - 30) as u8, false),
+				code @ 40..=47 => self.background = colors::from_ansi((code - 40) as u8, false),
+				code @ 90..=97 => self.foreground = colors::from_ansi((code - 90) as u8, true),
+				code @ 100..=107 => self.background = colors::from_ansi((code - 100) as u8, true),
+				39 => self.foreground = Escaper::new().foreground,
+				49 => self.background = Escaper::new().background,
+				code @ (38 | 48) =>
+				{
+					let (color, len) = Escaper::extended_color(&params[i + 1..]);
+					match (code, color)
+					{
+						(38, Some(color)) => self.foreground = color,
+						(48, Some(color)) => self.background = color,
+						_ => {}
+					}
+					i += len;
+				}
+				_ => {}
 			}
-		}
-		else
-		{
-			self.color_code = self.cmd.color;
+			i += 1;
 		}
 	}
 }
 
-impl Writer // Escaper related stuff
+impl Writer // cursor and erase commands
 {
-	fn escape_off(&mut self)
+	fn move_cursor(&mut self, col: usize, row: usize)
 	{
-		self.cmd.foreground = false;
-		self.cmd.background = false;
+		self.col = col.min(vga::width() - 1);
+		self.row = row.min(vga::height() - 1);
+		self.update_cursor();
 	}
 
+	fn erase(&mut self, row: usize, cols: core::ops::Range<usize>)
+	{
+		for col in cols
+		{
+			self.put(row, col, self.blank());
+		}
+	}
+
+	// 0 from the cursor, 1 up to the cursor, 2 the whole line
+	fn erase_in_line(&mut self, mode: u16)
+	{
+		let (col, row) = (self.col.min(vga::width()), self.row);
+
+		match mode
+		{
+			0 => self.erase(row, col..vga::width()),
+			1 => self.erase(row, 0..(col + 1).min(vga::width())),
+			2 => self.erase(row, 0..vga::width()),
+			_ => {}
+		}
+	}
+
+	// same modes as the lines, the cursor does not move, 3 also forgets the
+	// scrollback
+	fn erase_in_display(&mut self, mode: u16)
+	{
+		if mode == 3
+		{
+			self.clear_scrollback();
+		}
+		let rows = match mode
+		{
+			0 => self.row + 1..vga::height(),
+			1 => 0..self.row,
+			2 | 3 => 0..vga::height(),
+			_ => return
+		};
+		if mode < 2
+		{
+			self.erase_in_line(mode);
+		}
+		for row in rows
+		{
+			self.erase(row, 0..vga::width());
+		}
+	}
+
+	fn csi(&mut self, byte: u8)
+	{
+		let n = self.cmd.param(0, 1) as usize;
+		let (col, row) = (self.col, self.row);
+
+		match byte
+		{
+			b'A' => self.move_cursor(col, row.saturating_sub(n)),
+			b'B' => self.move_cursor(col, row + n),
+			b'C' => self.move_cursor(col + n, row),
+			b'D' => self.move_cursor(col.saturating_sub(n), row),
+			b'G' => self.move_cursor(n - 1, row),
+			b'H' | b'f' => self.move_cursor(self.cmd.param(1, 1) as usize - 1, n - 1),
+			b'J' => self.erase_in_display(self.cmd.params[0]),
+			b'K' => self.erase_in_line(self.cmd.params[0]),
+			b's' => self.cmd.saved = (col, row),
+			b'u' =>
+			{
+				let (col, row) = self.cmd.saved;
+				self.move_cursor(col, row);
+			}
+			b'm' =>
+			{
+				self.cmd.rendition();
+				self.color_code = self.cmd.color_code();
+			}
+			_ => {}
+		}
+	}
+}
+
+impl Writer // Escaper related stuff
+{
 	fn cmd_off(&mut self)
 	{
 		self.is_command = false;
-		self.escape_off();
+		self.cmd.state = State::Escape;
 	}
 
 	pub fn escape(&mut self, byte: u8)
 	{
-		match byte
+		match (self.cmd.state, byte)
 		{
-			b'a'..b'z' | b'A'..b'Z' => self.cmd_off(),
-			b';' => { self.escape_off(); return; },
-			_ => {},
+			(State::Escape, b'[') =>
+			{
+				self.cmd.state = State::Csi;
+				self.cmd.params = [0; MAX_PARAMS];
+				self.cmd.count = 0;
+				return;
+			}
+			(State::Escape, b'7') => self.cmd.saved = (self.col, self.row),
+			(State::Escape, b'8') =>
+			{
+				let (col, row) = self.cmd.saved;
+				self.move_cursor(col, row);
+			}
+			(State::Escape, b'c') =>
+			{
+				self.cmd.reset_rendition();
+				self.color_code = self.cmd.color_code();
+				self.clear();
+			}
+			(State::Csi, b'0'..=b'9') =>
+			{
+				let param = &mut self.cmd.params[self.cmd.count];
+				*param = param.saturating_mul(10).saturating_add((byte - b'0') as u16);
+				return;
+			}
+			(State::Csi, b';') =>
+			{
+				self.cmd.count = (self.cmd.count + 1).min(MAX_PARAMS - 1);
+				return;
+			}
+			// private and intermediate bytes are ignored
+			(State::Csi, 0x20..=0x3f) => return,
+			(State::Csi, 0x40..=0x7e) => self.csi(byte),
+			_ => {}
 		}
-		self.colors(byte);
+		self.cmd_off();
 	}
 }
@@ -0,0 +1,258 @@
+use core::ptr;
+use crate::libc;
+use crate::memory;
+use crate::multiboot::{self, FramebufferType};
+use crate::sync::{IrqSpinlock, IrqSpinlockGuard, Once};
+use super::ScreenChar;
+use super::colors::PALETTE;
+
+// pixel console on the linear framebuffer set by the bootloader, it keeps a
+// copy of the characters to redraw the cells under the cursor
+pub const MAX_WIDTH: usize = 128;
+pub const MAX_HEIGHT: usize = 48;
+
+const FONT: &[u8] = include_bytes!("font.psf");
+const PSF2_MAGIC: u32 = 0x864a_b572;
+const BYTES_PER_PIXEL: usize = 4;
+
+struct Font
+{
+	glyphs: &'static [u8],
+	count: usize,
+	glyph_size: usize,
+	width: usize,
+	height: usize
+}
+
+impl Font
+{
+	fn read_u32(offset: usize) -> u32
+	{
+		u32::from_le_bytes([FONT[offset], FONT[offset + 1], FONT[offset + 2], FONT[offset + 3]])
+	}
+
+	fn load() -> Option<Font>
+	{
+		if FONT.len() < 32 || Font::read_u32(0) != PSF2_MAGIC
+		{
+			return None;
+		}
+		let header_size = Font::read_u32(8) as usize;
+		let count = Font::read_u32(16) as usize;
+		let glyph_size = Font::read_u32(20) as usize;
+		let height = Font::read_u32(24) as usize;
+		let width = Font::read_u32(28) as usize;
+
+		// one byte per glyph row
+		if width > 8 || glyph_size < height || FONT.len() < header_size + count * glyph_size
+		{
+			return None;
+		}
+		Some(Font
+		{
+			glyphs: &FONT[header_size..],
+			count,
+			glyph_size,
+			width,
+			height
+		})
+	}
+
+	fn glyph(&self, character: u8) -> &[u8]
+	{
+		let index = if (character as usize) < self.count { character as usize } else { 0 };
+		&self.glyphs[index * self.glyph_size..index * self.glyph_size + self.height]
+	}
+}
+
+pub struct Console
+{
+	address: usize,
+	pitch: usize,
+	width: usize,
+	height: usize,
+	font: Font,
+	palette: [u32; 16],
+	chars: [[ScreenChar; MAX_WIDTH]; MAX_HEIGHT],
+	// position of the cursor as in the VGA cursor register
+	cursor: usize,
+	cursor_visible: bool
+}
+
+// too large for the boot stack, the console is filled in place by init
+static CONSOLE: IrqSpinlock<Console> = IrqSpinlock::new(Console
+{
+	address: 0,
+	pitch: 0,
+	width: 0,
+	height: 0,
+	font: Font { glyphs: &[], count: 0, glyph_size: 0, width: 0, height: 0 },
+	palette: [0; 16],
+	chars: [[ScreenChar::blank(); MAX_WIDTH]; MAX_HEIGHT],
+	cursor: 0,
+	cursor_visible: true
+});
+// the columns and rows, set once the console is ready
+static SIZE: Once<(usize, usize)> = Once::new();
+
+impl Console
+{
+	fn pixel_offset(&self, x: usize, y: usize) -> usize
+	{
+		y * self.pitch + x * BYTES_PER_PIXEL
+	}
+
+	fn draw(&self, row: usize, col: usize, screen_char: ScreenChar, inverted: bool)
+	{
+		let (mut foreground, mut background) = (self.palette[screen_char.color_code.fg() as usize],
+												self.palette[screen_char.color_code.bg() as usize]);
+		if inverted
+		{
+			core::mem::swap(&mut foreground, &mut background);
+		}
+
+		for (y, line) in self.font.glyph(screen_char.character).iter().enumerate()
+		{
+			let offset = self.pixel_offset(col * self.font.width, row * self.font.height + y);
+			for x in 0..self.font.width
+			{
+				let pixel = if line & (0x80 >> x) != 0 { foreground } else { background };
+				unsafe
+				{
+					ptr::write_volatile((self.address + offset + x * BYTES_PER_PIXEL) as *mut u32, pixel);
+				}
+			}
+		}
+	}
+
+	fn draw_cursor(&self, visible: bool)
+	{
+		let (col, row) = (self.cursor % self.width, self.cursor / self.width);
+
+		if row < self.height
+		{
+			self.draw(row, col, self.chars[row][col], visible);
+		}
+	}
+
+	pub fn write(&mut self, row: usize, col: usize, screen_char: ScreenChar)
+	{
+		self.chars[row][col] = screen_char;
+		self.draw(row, col, screen_char, self.cursor_visible && self.cursor == row * self.width + col);
+	}
+
+	pub fn read(&self, row: usize, col: usize) -> ScreenChar
+	{
+		self.chars[row][col]
+	}
+
+	// move the pixels up with a memmove, then clear the rows at the bottom
+	pub fn shift_rows(&mut self, offset: usize)
+	{
+		let row_size = self.pitch * self.font.height;
+
+		self.draw_cursor(false);
+		unsafe
+		{
+			ptr::copy((self.address + offset * row_size) as *const u8, self.address as *mut u8, (self.height - offset) * row_size);
+		}
+		self.chars.copy_within(offset..self.height, 0);
+		for row in self.height - offset..self.height
+		{
+			for col in 0..self.width
+			{
+				self.write(row, col, ScreenChar::blank());
+			}
+		}
+		self.draw_cursor(self.cursor_visible);
+	}
+
+	pub fn move_cursor(&mut self, position: usize)
+	{
+		self.draw_cursor(false);
+		self.cursor = position;
+		self.draw_cursor(self.cursor_visible);
+	}
+
+	pub fn cursor(&self) -> usize
+	{
+		self.cursor
+	}
+
+	pub fn show_cursor(&mut self, visible: bool)
+	{
+		self.cursor_visible = visible;
+		self.draw_cursor(visible);
+	}
+}
+
+// the color of each channel is cut to the size given by the bootloader
+fn pixel_color(color: u32, red: multiboot::ColorField, green: multiboot::ColorField, blue: multiboot::ColorField) -> u32
+{
+	let channel = |value: u32, field: multiboot::ColorField| (value >> (8 - field.size.min(8))) << field.position;
+
+	channel(color >> 16 & 0xff, red) | channel(color >> 8 & 0xff, green) | channel(color & 0xff, blue)
+}
+
+// only the 32 bits rgb framebuffers are supported, the text mode is kept otherwise
+pub fn init() -> bool
+{
+	let framebuffer = match multiboot::boot_info().framebuffer
+	{
+		Some(framebuffer) => framebuffer,
+		None => return false
+	};
+	let (red, green, blue) = match framebuffer.framebuffer_type
+	{
+		FramebufferType::Rgb { red, green, blue } if framebuffer.bpp as usize == BYTES_PER_PIXEL * 8 => (red, green, blue),
+		_ => return false
+	};
+	let font = match Font::load()
+	{
+		Some(font) => font,
+		None => return false
+	};
+	let address = framebuffer.address as usize;
+	let pitch = framebuffer.pitch as usize;
+	if framebuffer.address > usize::MAX as u64 || !memory::map_mmio(address, pitch * framebuffer.height as usize)
+	{
+		return false;
+	}
+
+	let mut palette = [0; 16];
+	for (pixel, color) in palette.iter_mut().zip(PALETTE)
+	{
+		*pixel = pixel_color(color, red, green, blue);
+	}
+	let width = (framebuffer.width as usize / font.width).min(MAX_WIDTH);
+	let height = (framebuffer.height as usize / font.height).min(MAX_HEIGHT);
+	{
+		let mut console = CONSOLE.lock();
+
+		console.address = address;
+		console.pitch = pitch;
+		console.width = width;
+		console.height = height;
+		console.font = font;
+		console.palette = palette;
+		unsafe
+		{
+			libc::memset(address as *mut _, 0, pitch * framebuffer.height as usize);
+		}
+	}
+	SIZE.call_once(|| (width, height));
+	true
+}
+
+// None while the text mode is used
+pub fn console() -> Option<IrqSpinlockGuard<'static, Console>>
+{
+	SIZE.get()?;
+	Some(CONSOLE.lock())
+}
+
+// the columns and rows of the console, without taking its lock
+pub fn size() -> Option<(usize, usize)>
+{
+	SIZE.get().copied()
+}
@@ -2,13 +2,20 @@ use core::fmt;
 use colors::ColorCode;
 use cursor::Cursor;
 use escape::Escaper;
+use scrollback::Scrollback;
+use crate::sync::IrqSpinlock;
 
 pub mod colors;
 pub mod cursor;
 pub mod escape;
+pub mod framebuffer;
+pub mod scrollback;
 
+// the text mode grid, the framebuffer console may be larger
 pub const BUFFER_HEIGHT: usize = 25;
 pub const BUFFER_WIDTH: usize = 80;
+pub const MAX_HEIGHT: usize = framebuffer::MAX_HEIGHT;
+pub const MAX_WIDTH: usize = framebuffer::MAX_WIDTH;
 
 pub const ESCAPE_START: u8 = 0x1B;
 pub const BACKSPACE: u8 = 0x08;
@@ -41,49 +48,55 @@ pub struct Buffer
 
 impl Buffer
 {
-	fn buffer() -> &'static mut Buffer
+	// the VGA text memory, when there is no framebuffer
+	fn text_mode() -> &'static mut Buffer
 	{
 		unsafe { &mut *(0xb8000 as *mut Buffer) }
 	}
 
-	pub fn clear_row(row: usize)
+	pub fn write(row: usize, col: usize, screen_char: ScreenChar)
 	{
-		for col in 0..BUFFER_WIDTH
+		match framebuffer::console()
 		{
-			Buffer::buffer().chars[row][col] = ScreenChar::blank();
+			Some(mut console) => console.write(row, col, screen_char),
+			None => Buffer::text_mode().chars[row][col] = screen_char
 		}
 	}
 
-	pub fn clear()
+	pub fn read(row: usize, col: usize) -> ScreenChar
 	{
-		for row in 0..BUFFER_HEIGHT
+		match framebuffer::console()
 		{
-			Buffer::clear_row(row);
-			unsafe
-			{
-				if W.scroll == 0
-				{
-					Cursor::init(0, 15);
-				}
-				else
-				{
-					Cursor::disable();
-				}
-				Cursor::move_to(0, 0);
-				W.col = 0;
-				W.row = 0;
-				W.has_overflown = false;
-			}
+			Some(console) => console.read(row, col),
+			None => Buffer::text_mode().chars[row][col]
 		}
 	}
 
+	pub fn clear_row(row: usize)
+	{
+		for col in 0..width()
+		{
+			Buffer::write(row, col, ScreenChar::blank());
+		}
+	}
+
+	pub fn clear()
+	{
+		W.lock().clear();
+	}
+
 	fn shift_rows(offset: usize)
 	{
+		if let Some(mut console) = framebuffer::console()
+		{
+			console.shift_rows(offset);
+			return;
+		}
 		for row in 0..BUFFER_HEIGHT - offset
 		{
 			for col in 0..BUFFER_WIDTH
 			{
-				Buffer::buffer().chars[row][col] = Buffer::buffer().chars[row + offset][col];
+				Buffer::text_mode().chars[row][col] = Buffer::text_mode().chars[row + offset][col];
 			}
 		}
 		for row in BUFFER_HEIGHT - offset..BUFFER_HEIGHT
@@ -93,31 +106,93 @@ impl Buffer
 	}
 }
 
+// the screen of a tty: the cells it shows, drawn on the console while it is
+// the visible one, and the lines that went off the top
 pub struct Writer
 {
+	visible: bool,
 	cmd: Escaper,
 	is_command: bool,
 	color_code: ColorCode,
 	pub col: usize,
 	pub row: usize,
-	pub scroll: usize,
-	has_overflown: bool
+	// the lines of scrollback shown above the screen, 0 for the screen itself
+	scroll: usize,
+	cells: [[ScreenChar; MAX_WIDTH]; MAX_HEIGHT],
+	scrollback: Scrollback
 }
 
 impl Writer // base stuff
 {
+	pub const fn new(visible: bool) -> Writer
+	{
+		Writer
+		{
+			visible,
+			cmd: Escaper::new(),
+			is_command: false,
+			color_code: ColorCode::default(),
+			col: 0,
+			row: 0,
+			scroll: 0,
+			cells: [[ScreenChar::blank(); MAX_WIDTH]; MAX_HEIGHT],
+			scrollback: Scrollback::new()
+		}
+	}
+
+	pub fn set_scrollback(&mut self, scrollback: Scrollback)
+	{
+		self.scrollback = scrollback;
+	}
+
+	pub fn clear_scrollback(&mut self)
+	{
+		self.scrollback.clear();
+		self.show_screen();
+	}
+
+	pub fn cell(&self, row: usize, col: usize) -> ScreenChar
+	{
+		self.cells[row][col]
+	}
+
+	// the other ttys are drawn once shown
+	pub fn set_visible(&mut self, visible: bool)
+	{
+		self.visible = visible;
+		self.redraw();
+	}
+
+	fn put(&mut self, row: usize, col: usize, screen_char: ScreenChar)
+	{
+		self.cells[row][col] = screen_char;
+		if self.visible
+		{
+			Buffer::write(row, col, screen_char);
+		}
+	}
+
+	fn update_cursor(&self)
+	{
+		if self.visible
+		{
+			Cursor::move_to(self.col as u16, self.row as u16);
+		}
+	}
+
 	pub fn write_byte(&mut self, byte: u8)
 	{
-		if self.row >= BUFFER_HEIGHT
+		if self.row >= height()
 		{
 			return;
 		}
+		self.show_screen();
 		if self.is_command == true
 		{
 			self.escape(byte);
 			return;
 		}
-		if self.col >= BUFFER_WIDTH && byte != b'\n'
+		if self.col >= width() && byte != b'\n'
 		{
 			self.new_line();
 		}
@@ -128,125 +203,85 @@ impl Writer // base stuff
 			ESCAPE_START  => self.is_command = true,
 			_ =>
 			{
-				Buffer::buffer().chars[self.row][self.col] = ScreenChar
+				self.put(self.row, self.col, ScreenChar
 				{
 					character: byte,
 					color_code: self.color_code,
-				};
+				});
 				self.next_col();
-				Cursor::move_to(self.col as u16, self.row as u16);
+				self.update_cursor();
 			},
 		}
 	}
 
-	fn count_lines(s: &str) -> usize
+	pub fn write_string(&mut self, s: &str)
 	{
-		let mut lines: usize = 0;
-		let mut col: usize = 0;
-
 		for byte in s.bytes()
 		{
-			if col >= BUFFER_WIDTH || byte == b'\n'
+			match byte
 			{
-				lines += 1;
-				col = 0;
-				continue;
+				0x00..0xfd => self.write_byte(byte),
+				_ => self.write_byte(0xfe),
 			}
-			col += 1;
 		}
-		lines
 	}
 
-	fn str_without_first_line(s: &str) -> &str
+	// the scrollback is kept
+	pub fn clear(&mut self)
 	{
-		let mut len_to_remove: usize = 0;
-
-		for byte in s.bytes()
+		self.scroll = 0;
+		for row in 0..MAX_HEIGHT
 		{
-			len_to_remove += 1;
-			if len_to_remove >= BUFFER_WIDTH || byte == b'\n'
-			{
-				break;
-			}
+			self.cells[row] = [ScreenChar::blank(); MAX_WIDTH];
 		}
-		&s[len_to_remove..]
+		self.col = 0;
+		self.row = 0;
+		self.redraw();
 	}
 
-	fn str_without_last_line(s: &str) -> &str
+	fn clear_row(&mut self, row: usize)
 	{
-		let mut start: usize = 0;
-		let mut start_candidate: usize = 0;
-
-		for byte in s.bytes()
+		for col in 0..width()
 		{
-			start += 1;
-			if start - start_candidate >= BUFFER_WIDTH || byte == b'\n'
-			{
-				start_candidate = start - 1;
-			}
+			self.put(row, col, self.blank());
 		}
-		&s[..start_candidate]
 	}
 
-	pub fn write_string(&mut self, s: &str)
+	// the first row goes to the scrollback
+	fn scroll_rows(&mut self)
 	{
-		let mut s_to_write: &str = &s[..];
+		let height = height();
 
-		unsafe
-		{
-			for _ in 0..W.scroll
-			{
-				s_to_write = Writer::str_without_last_line(s_to_write);
-			}
-		}
-		while Writer::count_lines(s_to_write) > BUFFER_HEIGHT
+		self.scrollback.push(&self.cells[0]);
+		self.cells.copy_within(1..height, 0);
+		if self.visible
 		{
-			s_to_write = Writer::str_without_first_line(s_to_write);
-		}
-
-		for byte in s_to_write.bytes()
-		{
-			match byte
-			{
-				0x00..0xfd => self.write_byte(byte),
-				_ => self.write_byte(0xfe),
-			}
-		}
-	}
-
-	fn clear_row(&mut self, row: usize)
-	{
-		for col in 0..BUFFER_WIDTH
-		{
-			Buffer::buffer().chars[row][col] = self.blank();
+			Buffer::shift_rows(1);
 		}
+		self.clear_row(height - 1);
 	}
 
 	fn new_line(&mut self)
 	{
-		for col in self.col..BUFFER_WIDTH
+		for col in self.col..width()
 		{
-			Buffer::buffer().chars[self.row][col] = ScreenChar::blank();
+			self.put(self.row, col, ScreenChar::blank());
 		}
 		self.col = 0;
 		self.row += 1;
-		if self.row >= BUFFER_HEIGHT
+		if self.row >= height()
 		{
-			self.row = BUFFER_HEIGHT - 1;
-			Buffer::shift_rows(1);
-			unsafe
-			{
-				W.has_overflown = true;
-			}
+			self.row = height() - 1;
+			self.scroll_rows();
 		}
 		self.clear_row(self.row);
-		Cursor::move_to(self.col as u16, self.row as u16);
+		self.update_cursor();
 	}
 
 	fn next_col(&mut self)
 	{
 		self.col += 1;
-		if self.col >= BUFFER_WIDTH
+		if self.col >= width()
 		{
 			self.new_line();
 		}
@@ -258,9 +293,9 @@ impl Writer // base stuff
 		{
 			self.col -= 1;
 		}
-		else
+		else if self.row > 0
 		{
-			self.col = BUFFER_WIDTH - 1;
+			self.col = width() - 1;
 			self.row -= 1;
 		}
 	}
@@ -268,14 +303,156 @@ impl Writer // base stuff
 	pub fn backspace(&mut self)
 	{
 		self.prev_col();
-		unsafe
+		self.put(self.row, self.col, ScreenChar::blank());
+		self.update_cursor();
+	}
+}
+
+impl Writer // scrollback
+{
+	// the rows shown come from the scrollback while scrolled up
+	pub fn redraw(&self)
+	{
+		if !self.visible
+		{
+			return;
+		}
+		let history = self.scrollback.len();
+
+		for row in 0..height()
 		{
-			if cursor::CURSOR.offset == 0
+			let line = history - self.scroll + row;
+			let cells = if line < history { self.scrollback.line(line) } else { &self.cells[line - history] };
+			for (col, cell) in cells[..width()].iter().enumerate()
 			{
-				Buffer::buffer().chars[self.row][self.col] = ScreenChar::blank();
+				Buffer::write(row, col, *cell);
 			}
 		}
-		Cursor::move_to(self.col as u16, self.row as u16);
+		if self.scroll == 0
+		{
+			Cursor::init(0, 15);
+			self.update_cursor();
+		}
+		else
+		{
+			Cursor::disable();
+		}
+	}
+
+	// back to the screen before anything is written
+	fn show_screen(&mut self)
+	{
+		if self.scroll > 0
+		{
+			self.scroll = 0;
+			self.redraw();
+		}
+	}
+
+	// half a screen at a time
+	pub fn scroll_up(&mut self)
+	{
+		let scroll = (self.scroll + height() / 2).min(self.scrollback.len());
+
+		if scroll != self.scroll
+		{
+			self.scroll = scroll;
+			self.redraw();
+		}
+	}
+
+	pub fn scroll_down(&mut self)
+	{
+		let scroll = self.scroll.saturating_sub(height() / 2);
+
+		if scroll != self.scroll
+		{
+			self.scroll = scroll;
+			self.redraw();
+		}
+	}
+}
+
+impl Writer // line editing, the line goes on for tail characters after the cursor
+{
+	fn position(&self) -> usize
+	{
+		self.row * width() + self.col
+	}
+
+	fn cell_at(&self, position: usize) -> ScreenChar
+	{
+		self.cells[position / width()][position % width()]
+	}
+
+	fn put_at(&mut self, position: usize, screen_char: ScreenChar)
+	{
+		self.put(position / width(), position % width(), screen_char);
+	}
+
+	// the tail moves to the right, the screen scrolls when it reaches the end
+	pub fn insert(&mut self, byte: u8, tail: usize)
+	{
+		self.show_screen();
+		if tail == 0
+		{
+			self.write_byte(byte);
+			return;
+		}
+		if self.position() + tail >= width() * height() && self.row > 0
+		{
+			self.scroll_rows();
+			self.row -= 1;
+		}
+		let start = self.position();
+		let end = (start + tail).min(width() * height() - 1);
+
+		for position in (start..end).rev()
+		{
+			self.put_at(position + 1, self.cell_at(position));
+		}
+		self.put_at(start, ScreenChar { character: byte, color_code: self.color_code });
+		self.cursor_right();
+	}
+
+	// the character before the cursor, the tail moves to the left
+	pub fn delete(&mut self, tail: usize)
+	{
+		self.show_screen();
+		self.cursor_left();
+
+		let start = self.position();
+		let end = (start + tail).min(width() * height() - 1);
+
+		for position in start..end
+		{
+			self.put_at(position, self.cell_at(position + 1));
+		}
+		self.put_at(end, ScreenChar::blank());
+	}
+
+	pub fn cursor_left(&mut self)
+	{
+		self.show_screen();
+		self.prev_col();
+		self.update_cursor();
+	}
+
+	pub fn cursor_right(&mut self)
+	{
+		self.show_screen();
+		self.col += 1;
+		if self.col >= width()
+		{
+			self.col = 0;
+			self.row += 1;
+		}
+		if self.row >= height()
+		{
+			self.row = height() - 1;
+			self.scroll_rows();
+		}
+		self.update_cursor();
 	}
 }
 
@@ -303,70 +480,43 @@ impl fmt::Write for Writer
 pub fn panic()
 {
 	Cursor::disable();
-	for row in 0..BUFFER_HEIGHT
+	for row in 0..height()
 	{
-		for col in 0..BUFFER_WIDTH
+		for col in 0..width()
 		{
-			Buffer::buffer().chars[row][col].color_code = ColorCode::panic();
+			let screen_char = Buffer::read(row, col);
+			Buffer::write(row, col, ScreenChar { color_code: ColorCode::panic(), ..screen_char });
 		}
 	}
 }
 
-pub static mut W: Writer = Writer
+pub fn width() -> usize
 {
-	cmd: Escaper
-	{
-		foreground: false,
-		background: false,
-		color: ColorCode::default()
-	},
-	is_command: false,
-	color_code: ColorCode::default(),
-	col: 0,
-	row: 0,
-	scroll: 0,
-	has_overflown: false
-};
-
-pub fn scroll_up() -> bool
+	framebuffer::size().map_or(BUFFER_WIDTH, |(width, _)| width)
+}
+
+pub fn height() -> usize
 {
-	unsafe
-	{
-		if W.has_overflown
-		{
-			W.scroll += 1;
-			Cursor::disable();
-			return true;
-		}
-	}
-	false
+	framebuffer::size().map_or(BUFFER_HEIGHT, |(_, height)| height)
+}
+
+pub static W: IrqSpinlock<Writer> = IrqSpinlock::new(Writer::new(true));
+
+pub fn scroll_up()
+{
+	W.lock().scroll_up();
 }
 
-pub fn scroll_down() -> bool
+pub fn scroll_down()
 {
-	unsafe
-	{
-		if W.scroll > 0
-		{
-			W.scroll -= 1;
-			if W.scroll == 0
-			{
-				Cursor::init(0, 15);
-			}
-			return true;
-		}
-	}
-	false
+	W.lock().scroll_down();
 }
 
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments)
 {
-    unsafe
-	{
-    	use core::fmt::Write;
-		W.write_fmt(args).unwrap();
-	}
+	use core::fmt::Write;
+	W.lock().write_fmt(args).unwrap();
 }
 
 #[macro_export]
@@ -0,0 +1,109 @@
+use core::mem::size_of;
+use crate::memory;
+use crate::vga::{ScreenChar, MAX_WIDTH};
+
+// the number of lines kept by each tty, 0 keeps none
+crate::boot_param!(pub SCROLLBACK: usize = 512, "scrollback");
+
+// the lines are kept as wide as the largest console, with their colors
+pub type Line = [ScreenChar; MAX_WIDTH];
+
+// the lines pushed off the top of the screen, in a ring allocated with
+// vmalloc once the memory is set up, oldest first
+pub struct Scrollback
+{
+	lines: *mut Line,
+	capacity: usize,
+	first: usize,
+	len: usize
+}
+
+// only reached through the writer owning it
+unsafe impl Send for Scrollback {}
+
+impl Scrollback
+{
+	// keeps nothing until one is allocated
+	pub const fn new() -> Scrollback
+	{
+		Scrollback
+		{
+			lines: core::ptr::null_mut(),
+			capacity: 0,
+			first: 0,
+			len: 0
+		}
+	}
+
+	// the length given on the command line, an empty one when too large or out
+	// of memory
+	pub fn alloc() -> Scrollback
+	{
+		let capacity = SCROLLBACK.get();
+
+		if capacity == 0
+		{
+			return Scrollback::new();
+		}
+		let size = match capacity.checked_mul(size_of::<Line>())
+		{
+			Some(size) => size,
+			None => return Scrollback::new()
+		};
+		let lines = memory::vmalloc(size) as *mut Line;
+		if lines.is_null()
+		{
+			return Scrollback::new();
+		}
+		Scrollback { lines, capacity, first: 0, len: 0 }
+	}
+
+	pub fn capacity(&self) -> usize
+	{
+		self.capacity
+	}
+
+	pub fn len(&self) -> usize
+	{
+		self.len
+	}
+
+	// the oldest line goes away once the ring is full
+	pub fn push(&mut self, line: &Line)
+	{
+		if self.capacity == 0
+		{
+			return;
+		}
+		let index = (self.first + self.len) % self.capacity;
+
+		unsafe
+		{
+			*self.lines.add(index) = *line;
+		}
+		if self.len == self.capacity
+		{
+			self.first = (self.first + 1) % self.capacity;
+		}
+		else
+		{
+			self.len += 1;
+		}
+	}
+
+	// from 0 for the oldest line
+	pub fn line(&self, index: usize) -> &Line
+	{
+		assert!(index < self.len);
+		unsafe
+		{
+			&*self.lines.add((self.first + index) % self.capacity)
+		}
+	}
+
+	pub fn clear(&mut self)
+	{
+		self.first = 0;
+		self.len = 0;
+	}
+}